@@ -0,0 +1,241 @@
+//! Hybrid logical clock for ordering changes across devices
+//!
+//! Stamps follow wall-clock time, but never go backwards and always come
+//! after every stamp this device has seen. A change made after syncing
+//! another device's change is ordered after it even when that device's
+//! clock runs ahead. Concurrent changes are ordered by time, then device id,
+//! so every device picks the same winner. Stamps too far ahead of this
+//! device's wall clock (see `MAX_CLOCK_DRIFT_MINUTES`) are not followed, and
+//! lose to every other stamp when changes are compared.
+
+use crate::config::MAX_CLOCK_DRIFT_MINUTES;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// When and where a change was made. Ordered by time, then counter, then
+/// device.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Stamp {
+    pub time: DateTime<Utc>,
+    /// Orders stamps made at the same time
+    pub counter: u32,
+    pub device: String,
+}
+
+impl Stamp {
+    /// Parse a stamp as written by `Display`. Plain RFC 3339 times, as
+    /// written before stamps, parse with counter 0 and no device.
+    pub fn parse(value: &str) -> Option<Stamp> {
+        let mut parts = value.splitn(3, '/');
+        let time = DateTime::parse_from_rfc3339(parts.next()?)
+            .ok()?
+            .with_timezone(&Utc);
+        let counter = match parts.next() {
+            Some(counter) => counter.parse().ok()?,
+            None => 0,
+        };
+        Some(Stamp {
+            time,
+            counter,
+            device: parts.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// The time part as RFC 3339
+    pub fn time_string(&self) -> String {
+        self.time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+    }
+
+    /// Whether the stamp is more than the allowed drift ahead of `physical`
+    fn too_far_ahead(&self, physical: DateTime<Utc>) -> bool {
+        self.time > physical + Duration::minutes(MAX_CLOCK_DRIFT_MINUTES)
+    }
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.time_string(), self.counter, self.device)
+    }
+}
+
+struct Clock {
+    latest: Option<(DateTime<Utc>, u32)>, // Newest stamp made or observed
+    device: String,
+}
+
+impl Clock {
+    fn tick(&mut self, physical: DateTime<Utc>) -> Stamp {
+        let (time, counter) = match self.latest {
+            Some((time, counter)) if time >= physical => (time, counter + 1),
+            _ => (physical, 0),
+        };
+        self.latest = Some((time, counter));
+        Stamp {
+            time,
+            counter,
+            device: self.device.clone(),
+        }
+    }
+
+    /// Move past `stamp` unless it is more than the allowed drift ahead of
+    /// `physical`. Returns false if it was ignored.
+    fn observe(&mut self, stamp: &Stamp, physical: DateTime<Utc>) -> bool {
+        if stamp.too_far_ahead(physical) {
+            return false;
+        }
+        let seen = (stamp.time, stamp.counter);
+        if self.latest.is_none_or(|latest| seen > latest) {
+            self.latest = Some(seen);
+        }
+        true
+    }
+}
+
+static CLOCK: Mutex<Clock> = Mutex::new(Clock {
+    latest: None,
+    device: String::new(),
+});
+
+fn lock() -> MutexGuard<'static, Clock> {
+    CLOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Set the device id new stamps carry
+pub fn set_device(device: &str) {
+    lock().device = device.to_string();
+}
+
+/// A stamp for a change made now, later than any stamp made or observed
+/// before
+pub fn now() -> Stamp {
+    lock().tick(Utc::now())
+}
+
+/// Advance the clock past a stamp (or RFC 3339 time) from stored or synced
+/// data, so changes made from now on are ordered after it
+pub fn observe(value: &str) {
+    if let Some(stamp) = Stamp::parse(value) {
+        if !lock().observe(&stamp, Utc::now()) {
+            eprintln!("Ignoring clock stamp too far in the future: {}", value);
+        }
+    }
+}
+
+/// True if `b` is strictly later than `a`. A stamp too far ahead of this
+/// device's wall clock counts as older than any other, so a device with a
+/// wrong clock can't win every change it makes. Values that aren't stamps or
+/// times are compared as text.
+pub fn is_newer(a: &str, b: &str) -> bool {
+    is_newer_at(a, b, Utc::now())
+}
+
+fn is_newer_at(a: &str, b: &str, physical: DateTime<Utc>) -> bool {
+    match (Stamp::parse(a), Stamp::parse(b)) {
+        (Some(a), Some(b)) => (!b.too_far_ahead(physical), b) > (!a.too_far_ahead(physical), a),
+        _ => b > a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_stamp_roundtrip_and_legacy_times() {
+        let stamp = now();
+        assert_eq!(Stamp::parse(&stamp.to_string()), Some(stamp));
+
+        let legacy = Stamp::parse("2024-01-01T02:00:00+02:00").unwrap();
+        assert_eq!(legacy.time_string(), "2024-01-01T00:00:00Z");
+        assert_eq!(legacy.counter, 0);
+        assert_eq!(legacy.device, "");
+        assert!(Stamp::parse("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_stamps_increase_past_clock_skew() {
+        let mut clock = Clock {
+            latest: None,
+            device: "slow".to_string(),
+        };
+        let start = Utc::now();
+        let first = clock.tick(start);
+        // The wall clock is set back
+        let second = clock.tick(start - Duration::minutes(5));
+        assert!(second > first);
+        assert_eq!(second.counter, 1);
+
+        // Another device's clock runs a few minutes ahead
+        let ahead = Stamp {
+            time: start + Duration::minutes(3),
+            counter: 4,
+            device: "fast".to_string(),
+        };
+        assert!(clock.observe(&ahead, start));
+        let after = clock.tick(start + Duration::seconds(1));
+        assert_eq!(after.time, ahead.time);
+        assert_eq!(after.counter, 5);
+        assert!(is_newer(&ahead.to_string(), &after.to_string()));
+
+        // Once the wall clock passes the observed time, it is followed again
+        let later = clock.tick(start + Duration::hours(2));
+        assert_eq!(later.counter, 0);
+    }
+
+    #[test]
+    fn test_stamps_far_ahead_are_not_followed() {
+        let mut clock = Clock {
+            latest: None,
+            device: "local".to_string(),
+        };
+        let start = Utc::now();
+        // A device whose clock is set a year ahead
+        let wrong = Stamp {
+            time: start + Duration::days(365),
+            counter: 0,
+            device: "wrong".to_string(),
+        };
+        assert!(!clock.observe(&wrong, start));
+        let after = clock.tick(start);
+        assert_eq!(after.time, start);
+        assert_eq!(after.counter, 0);
+    }
+
+    #[test]
+    fn test_stamps_far_ahead_lose_comparisons() {
+        let start = Utc::now();
+        let wrong = Stamp {
+            time: start + Duration::days(365),
+            counter: 0,
+            device: "wrong".to_string(),
+        }
+        .to_string();
+        let local = Stamp {
+            time: start,
+            counter: 0,
+            device: "local".to_string(),
+        }
+        .to_string();
+        assert!(is_newer_at(&wrong, &local, start));
+        assert!(!is_newer_at(&local, &wrong, start));
+        // Once the wall clock catches up, the stamp is ordered by time again
+        assert!(is_newer_at(&local, &wrong, start + Duration::days(365)));
+    }
+
+    #[test]
+    fn test_concurrent_stamps_are_ordered_by_device() {
+        let time = "2024-03-01T00:00:00Z";
+        let a = format!("{}/0/device-a", time);
+        let b = format!("{}/0/device-b", time);
+        assert!(is_newer(&a, &b));
+        assert!(!is_newer(&b, &a));
+        // A stamp beats a legacy time with the same value
+        assert!(is_newer(time, &a));
+        assert!(is_newer(
+            "2024-03-01T00:00:00Z",
+            "2024-03-01T00:00:01+00:00"
+        ));
+    }
+}
@@ -5,6 +5,51 @@
 /// Maximum number of tasks allowed in the Actual list
 pub const MAX_ACTUAL_TASKS: usize = 6;
 
+/// Maximum length (in characters) of a reminder's Markdown notes
+pub const MAX_NOTES_LENGTH: usize = 20_000;
+
+/// Ends the "warn" checklist refusal; the UI looks for it to offer completing anyway
+pub const CONFIRM_OPEN_CHECKLIST: &str = "confirm to complete anyway";
+
+/// Default number of days deletion tombstones are kept for sync
+pub const DEFAULT_TOMBSTONE_RETENTION_DAYS: u32 = 90;
+
+/// Default number of days deleted reminders stay in the trash
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// Default number of days after completion a reminder is moved to the archive
+pub const DEFAULT_ARCHIVE_AFTER_DAYS: u32 = 365;
+
+/// Number of rotating local backups kept in the app data directory
+pub const MAX_BACKUPS: usize = 10;
+
+/// Minimum time between two automatic local backups
+pub const BACKUP_INTERVAL_MINUTES: i64 = 60;
+
+/// Number of actions that can be undone
+pub const MAX_UNDO_HISTORY: usize = 50;
+
+/// Logged operations after which the local snapshot is rewritten
+pub const OPLOG_SNAPSHOT_INTERVAL: usize = 100;
+
+/// Newest logged operations kept for the audit log when the log is cut back
+pub const OPLOG_KEEP_ENTRIES: usize = 1_000;
+
+/// How far ahead of this device's clock a synced change's stamp may be for the
+/// clock to follow it. Stamps further ahead come from a device with a wrong
+/// clock and are ignored, so it can't drag every device's clock along.
+pub const MAX_CLOCK_DRIFT_MINUTES: i64 = 5;
+
+/// Conditional uploads tried, merging the remote's changes in between, before
+/// giving up on a remote that keeps changing until the next sync
+pub const MAX_UPLOAD_ATTEMPTS: u32 = 3;
+
+/// Number of sync conflict reports kept for review
+pub const MAX_SYNC_CONFLICTS: usize = 100;
+
+/// Shortest passphrase accepted for encrypting the data
+pub const MIN_PASSPHRASE_LENGTH: usize = 8;
+
 /// OAuth redirect port for Google Drive authentication
 pub const OAUTH_REDIRECT_PORT: u16 = 8085;
 
@@ -1,7 +1,10 @@
 mod appbar;
+mod clock;
 mod config;
 mod error;
+mod recurrence;
 mod reminder;
+mod sort_key;
 mod storage;
 mod updater;
 mod urlencoding;
@@ -20,8 +23,12 @@ use tauri::{
 };
 use chrono::Datelike;
 use config::{BAR_HEIGHT, DEFAULT_DRIVE_FOLDER_ID, ORGANIZE_PROMPT_HOURS, ORGANIZE_PROMPT_WINDOW_MINUTES};
-use reminder::{ListType, Reminder, Urgency};
-use storage::{OAuthCredentials, Storage};
+use reminder::{ListType, Reminder, ReminderId, Urgency};
+use storage::{
+    BackupInfo, ChecklistPolicy, ConflictChoice, EncryptionStatus, Finding, HistoryListing,
+    ListDefinition, LocalBackendKind, LocalBackendStatus, OAuthCredentials, Operation, Storage,
+    StoreSettings, SyncConflict, SyncStatus, TrashedReminder,
+};
 
 /// Monitor Windows display changes and power events to reposition the reminder bar
 /// Listens for WM_DISPLAYCHANGE (resolution/monitor changes) and WM_POWERBROADCAST (resume from sleep)
@@ -211,6 +218,48 @@ fn start_organize_prompt_scheduler(app_handle: tauri::AppHandle) {
     });
 }
 
+/// Reminder scheduler - checks every minute, escalates urgency as deadlines
+/// approach and brings back reminders whose snooze has run out
+fn start_reminder_scheduler(app_handle: tauri::AppHandle) {
+    use std::thread;
+    use std::time::Duration;
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(60));
+
+        let (urgency_changed, woken) = {
+            let state = app_handle.state::<AppState>();
+            let mut storage = state.lock_storage();
+            (
+                storage.refresh_due_urgencies(),
+                storage.wake_snoozed_reminders(),
+            )
+        };
+
+        let urgency_changed = urgency_changed.unwrap_or_else(|e| {
+            println!("Failed to refresh due urgencies: {}", e);
+            false
+        });
+        let woken = woken.unwrap_or_else(|e| {
+            println!("Failed to wake snoozed reminders: {}", e);
+            Vec::new()
+        });
+
+        if !woken.is_empty() {
+            println!("Woke {} snoozed reminder(s)", woken.len());
+            if let Err(e) = app_handle.emit("reminders-unsnoozed", &woken) {
+                println!("Failed to emit reminders-unsnoozed: {:?}", e);
+            }
+        }
+
+        if urgency_changed || !woken.is_empty() {
+            if let Err(e) = app_handle.emit("refresh-reminders", ()) {
+                println!("Failed to emit refresh-reminders: {:?}", e);
+            }
+        }
+    });
+}
+
 pub struct AppState {
     pub storage: Mutex<Storage>,
 }
@@ -240,6 +289,27 @@ fn get_backlog_reminders(state: tauri::State<AppState>) -> Result<Vec<Reminder>,
     Ok(storage.get_backlog_reminders())
 }
 
+#[tauri::command]
+fn get_snoozed_reminders(state: tauri::State<AppState>) -> Result<Vec<Reminder>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_snoozed_reminders())
+}
+
+#[tauri::command]
+fn get_reminders_by_tag(
+    state: tauri::State<AppState>,
+    tag: String,
+) -> Result<Vec<Reminder>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_reminders_by_tag(&tag))
+}
+
+#[tauri::command]
+fn get_all_tags(state: tauri::State<AppState>) -> Result<Vec<String>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_all_tags())
+}
+
 #[tauri::command]
 fn get_completed_reminders(state: tauri::State<AppState>) -> Result<Vec<Reminder>, String> {
     let storage = state.lock_storage();
@@ -247,17 +317,63 @@ fn get_completed_reminders(state: tauri::State<AppState>) -> Result<Vec<Reminder
 }
 
 #[tauri::command]
-fn get_completion_stats(state: tauri::State<AppState>) -> Result<(usize, usize), String> {
+fn get_completion_stats(
+    state: tauri::State<AppState>,
+    include_archive: Option<bool>,
+) -> Result<(usize, usize), String> {
     let storage = state.lock_storage();
-    Ok(storage.get_completion_stats())
+    Ok(storage.get_completion_stats(include_archive.unwrap_or(false)))
 }
 
 #[tauri::command]
 fn get_historical_stats(
     state: tauri::State<AppState>,
+    include_archive: Option<bool>,
 ) -> Result<(Vec<(String, usize)>, Vec<usize>, Vec<usize>, usize), String> {
     let storage = state.lock_storage();
-    Ok(storage.get_historical_stats())
+    Ok(storage.get_historical_stats(include_archive.unwrap_or(false)))
+}
+
+#[tauri::command]
+fn verify_store(state: tauri::State<AppState>) -> Result<Vec<Finding>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.verify_store())
+}
+
+#[tauri::command]
+fn repair_store(state: tauri::State<AppState>) -> Result<Vec<Finding>, String> {
+    let mut storage = state.lock_storage();
+    storage.repair_store()
+}
+
+#[tauri::command]
+fn get_archive_years(state: tauri::State<AppState>) -> Result<Vec<i32>, String> {
+    let mut storage = state.lock_storage();
+    Ok(storage.get_archive_years())
+}
+
+#[tauri::command]
+fn get_archived_reminders(
+    state: tauri::State<AppState>,
+    year: i32,
+) -> Result<Vec<Reminder>, String> {
+    let mut storage = state.lock_storage();
+    storage.get_archived_reminders(year)
+}
+
+#[tauri::command]
+fn search_archive(state: tauri::State<AppState>, query: String) -> Result<Vec<Reminder>, String> {
+    let mut storage = state.lock_storage();
+    storage.search_archive(&query)
+}
+
+/// Checklist progress of pending reminders: (id, done, total)
+#[tauri::command]
+fn get_checklist_stats(
+    state: tauri::State<AppState>,
+) -> Result<Vec<(ReminderId, usize, usize)>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_checklist_stats())
 }
 
 #[tauri::command]
@@ -294,7 +410,7 @@ fn add_reminder(
     message: String,
     urgency: String,
     list_type: String,
-) -> Result<i64, String> {
+) -> Result<ReminderId, String> {
     let mut storage = state.lock_storage();
     let urgency_enum = match urgency.as_str() {
         "now" => Urgency::Now,
@@ -302,18 +418,14 @@ fn add_reminder(
         "soon" => Urgency::Soon,
         _ => Urgency::Whenever,
     };
-    let list_type_enum = match list_type.as_str() {
-        "backlog" => ListType::Backlog,
-        _ => ListType::Actual,
-    };
-    let reminder = Reminder::new(message, urgency_enum, list_type_enum);
+    let reminder = Reminder::new(message, urgency_enum, ListType::parse(&list_type));
     storage.add_reminder(reminder)
 }
 
 #[tauri::command]
 fn update_reminder(
     state: tauri::State<AppState>,
-    id: i64,
+    id: String,
     message: String,
     urgency: String,
 ) -> Result<(), String> {
@@ -324,29 +436,75 @@ fn update_reminder(
         "soon" => Urgency::Soon,
         _ => Urgency::Whenever,
     };
-    storage.update_reminder(id, message, urgency_enum)
+    storage.update_reminder(&id, message, urgency_enum)
+}
+
+#[tauri::command]
+fn move_reminder(state: tauri::State<AppState>, id: String, to_list: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.move_reminder(&id, ListType::parse(&to_list))
+}
+
+#[tauri::command]
+fn get_lists(state: tauri::State<AppState>) -> Result<Vec<ListDefinition>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_lists())
 }
 
 #[tauri::command]
-fn move_reminder(
+fn get_list_reminders(
     state: tauri::State<AppState>,
-    id: i64,
-    to_list: String,
-) -> Result<(), String> {
+    list_id: String,
+) -> Result<Vec<Reminder>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_list_reminders(&ListType::parse(&list_id)))
+}
+
+#[tauri::command]
+fn create_list(
+    state: tauri::State<AppState>,
+    name: String,
+    capacity: Option<usize>,
+) -> Result<String, String> {
     let mut storage = state.lock_storage();
-    let list_type = match to_list.as_str() {
-        "backlog" => ListType::Backlog,
-        _ => ListType::Actual,
-    };
-    storage.move_reminder(id, list_type)
+    storage
+        .create_list(name, capacity)
+        .map(|list| list.as_str().to_string())
 }
 
 #[tauri::command]
-fn set_urgency(
+fn update_list(
     state: tauri::State<AppState>,
-    id: i64,
-    urgency: String,
+    id: String,
+    name: String,
+    capacity: Option<usize>,
+    overflow_list: Option<String>,
+    source_list: Option<String>,
 ) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.update_list(
+        &ListType::parse(&id),
+        name,
+        capacity,
+        overflow_list.as_deref().map(ListType::parse),
+        source_list.as_deref().map(ListType::parse),
+    )
+}
+
+#[tauri::command]
+fn reorder_lists(state: tauri::State<AppState>, ordered_ids: Vec<String>) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.reorder_lists(ordered_ids.iter().map(|id| ListType::parse(id)).collect())
+}
+
+#[tauri::command]
+fn delete_list(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.delete_list(&ListType::parse(&id))
+}
+
+#[tauri::command]
+fn set_urgency(state: tauri::State<AppState>, id: String, urgency: String) -> Result<(), String> {
     let mut storage = state.lock_storage();
     let urgency_enum = match urgency.as_str() {
         "now" => Urgency::Now,
@@ -354,33 +512,287 @@ fn set_urgency(
         "soon" => Urgency::Soon,
         _ => Urgency::Whenever,
     };
-    storage.set_urgency(id, urgency_enum)
+    storage.set_urgency(&id, urgency_enum)
+}
+
+#[tauri::command]
+fn set_due_date(state: tauri::State<AppState>, id: String, due_at: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_due_date(&id, due_at)
+}
+
+#[tauri::command]
+fn clear_due_date(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.clear_due_date(&id)
+}
+
+/// Set a recurrence rule from an RRULE-style string, e.g. "FREQ=WEEKLY;BYDAY=MO,WE"
+#[tauri::command]
+fn set_recurrence(state: tauri::State<AppState>, id: String, rule: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_recurrence(&id, &rule)
 }
 
 #[tauri::command]
-fn delete_reminder(state: tauri::State<AppState>, id: i64) -> Result<(), String> {
+fn clear_recurrence(state: tauri::State<AppState>, id: String) -> Result<(), String> {
     let mut storage = state.lock_storage();
-    storage.delete_reminder(id)
+    storage.clear_recurrence(&id)
 }
 
+/// Hide a reminder until the given RFC3339 time
 #[tauri::command]
-fn complete_reminder(state: tauri::State<AppState>, id: i64) -> Result<(), String> {
+fn snooze_reminder(state: tauri::State<AppState>, id: String, until: String) -> Result<(), String> {
     let mut storage = state.lock_storage();
-    storage.complete_reminder(id)
+    storage.snooze_reminder(&id, until)
 }
 
 #[tauri::command]
-fn uncomplete_reminder(state: tauri::State<AppState>, id: i64) -> Result<(), String> {
+fn unsnooze_reminder(state: tauri::State<AppState>, id: String) -> Result<(), String> {
     let mut storage = state.lock_storage();
-    storage.uncomplete_reminder(id)
+    storage.unsnooze_reminder(&id)
 }
 
+/// Replace the Markdown notes of a reminder (empty string clears them)
 #[tauri::command]
-fn reorder_reminders(state: tauri::State<AppState>, ordered_ids: Vec<i64>) -> Result<(), String> {
+fn set_notes(state: tauri::State<AppState>, id: String, notes: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_notes(&id, notes)
+}
+
+#[tauri::command]
+fn add_checklist_item(
+    state: tauri::State<AppState>,
+    id: String,
+    text: String,
+) -> Result<u32, String> {
+    let mut storage = state.lock_storage();
+    storage.add_checklist_item(&id, text)
+}
+
+#[tauri::command]
+fn toggle_checklist_item(
+    state: tauri::State<AppState>,
+    id: String,
+    item_id: u32,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.toggle_checklist_item(&id, item_id)
+}
+
+#[tauri::command]
+fn reorder_checklist_items(
+    state: tauri::State<AppState>,
+    id: String,
+    ordered_item_ids: Vec<u32>,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.reorder_checklist_items(&id, ordered_item_ids)
+}
+
+#[tauri::command]
+fn delete_checklist_item(
+    state: tauri::State<AppState>,
+    id: String,
+    item_id: u32,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.delete_checklist_item(&id, item_id)
+}
+
+#[tauri::command]
+fn add_tag(state: tauri::State<AppState>, id: String, tag: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.add_tag(&id, tag)
+}
+
+#[tauri::command]
+fn remove_tag(state: tauri::State<AppState>, id: String, tag: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.remove_tag(&id, tag)
+}
+
+/// Rename a tag across the whole store, returning how many reminders changed
+#[tauri::command]
+fn rename_tag(state: tauri::State<AppState>, from: String, to: String) -> Result<usize, String> {
+    let mut storage = state.lock_storage();
+    storage.rename_tag(from, to)
+}
+
+#[tauri::command]
+fn delete_reminder(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.delete_reminder(&id)
+}
+
+/// Complete a reminder; `force` confirms completion while checklist items are
+/// still open under the "warn" checklist policy
+#[tauri::command]
+fn complete_reminder(
+    state: tauri::State<AppState>,
+    id: String,
+    force: Option<bool>,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.complete_reminder(&id, force.unwrap_or(false))
+}
+
+#[tauri::command]
+fn uncomplete_reminder(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.uncomplete_reminder(&id)
+}
+
+#[tauri::command]
+fn reorder_reminders(
+    state: tauri::State<AppState>,
+    ordered_ids: Vec<ReminderId>,
+) -> Result<(), String> {
     let mut storage = state.lock_storage();
     storage.reorder_reminders(ordered_ids)
 }
 
+#[tauri::command]
+fn get_settings(state: tauri::State<AppState>) -> Result<StoreSettings, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_settings())
+}
+
+#[tauri::command]
+fn set_checklist_policy(
+    state: tauri::State<AppState>,
+    policy: ChecklistPolicy,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_checklist_policy(policy)
+}
+
+#[tauri::command]
+fn set_tombstone_retention_days(state: tauri::State<AppState>, days: u32) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_tombstone_retention_days(days)
+}
+
+#[tauri::command]
+fn set_trash_retention_days(state: tauri::State<AppState>, days: u32) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_trash_retention_days(days)
+}
+
+#[tauri::command]
+fn set_archive_after_days(state: tauri::State<AppState>, days: u32) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.set_archive_after_days(days)
+}
+
+#[tauri::command]
+fn get_trash(state: tauri::State<AppState>) -> Result<Vec<TrashedReminder>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_trash())
+}
+
+#[tauri::command]
+fn restore_from_trash(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.restore_from_trash(&id)
+}
+
+#[tauri::command]
+fn empty_trash(state: tauri::State<AppState>) -> Result<usize, String> {
+    let mut storage = state.lock_storage();
+    storage.empty_trash()
+}
+
+#[tauri::command]
+fn undo(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let mut storage = state.lock_storage();
+    storage.undo()
+}
+
+#[tauri::command]
+fn redo(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let mut storage = state.lock_storage();
+    storage.redo()
+}
+
+#[tauri::command]
+fn get_history(state: tauri::State<AppState>) -> Result<HistoryListing, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_history())
+}
+
+#[tauri::command]
+fn get_audit_log(
+    state: tauri::State<AppState>,
+    reminder_id: Option<String>,
+) -> Result<Vec<Operation>, String> {
+    let storage = state.lock_storage();
+    storage.get_audit_log(reminder_id.as_deref())
+}
+
+#[tauri::command]
+fn list_backups(state: tauri::State<AppState>) -> Result<Vec<BackupInfo>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.list_backups())
+}
+
+#[tauri::command]
+fn restore_backup(state: tauri::State<AppState>, name: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.restore_backup(&name)
+}
+
+#[tauri::command]
+fn get_local_backend(state: tauri::State<AppState>) -> Result<LocalBackendStatus, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_local_backend())
+}
+
+/// Move local data to reminders.json or reminders.db; returns how many
+/// reminders were moved
+#[tauri::command]
+fn switch_local_backend(
+    state: tauri::State<AppState>,
+    kind: LocalBackendKind,
+) -> Result<usize, String> {
+    let mut storage = state.lock_storage();
+    storage.switch_local_backend(kind)
+}
+
+#[tauri::command]
+fn get_encryption_status(state: tauri::State<AppState>) -> Result<EncryptionStatus, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_encryption_status())
+}
+
+#[tauri::command]
+fn unlock_storage(state: tauri::State<AppState>, passphrase: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.unlock(&passphrase)
+}
+
+#[tauri::command]
+fn enable_encryption(state: tauri::State<AppState>, passphrase: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.enable_encryption(&passphrase)
+}
+
+#[tauri::command]
+fn disable_encryption(state: tauri::State<AppState>, passphrase: String) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.disable_encryption(&passphrase)
+}
+
+#[tauri::command]
+fn change_passphrase(
+    state: tauri::State<AppState>,
+    current: String,
+    new: String,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.change_passphrase(&current, &new)
+}
+
 #[tauri::command]
 async fn sync_to_cloud_background(state: tauri::State<'_, AppState>) -> Result<(), String> {
     let mut storage = state.lock_storage();
@@ -401,13 +813,27 @@ fn sync_on_startup(state: tauri::State<AppState>) -> Result<bool, String> {
 }
 
 #[tauri::command]
-fn get_sync_status(
-    state: tauri::State<AppState>,
-) -> Result<(bool, bool, Option<String>, Option<String>), String> {
+fn get_sync_status(state: tauri::State<AppState>) -> Result<SyncStatus, String> {
     let storage = state.lock_storage();
     Ok(storage.get_sync_status())
 }
 
+#[tauri::command]
+fn get_sync_conflicts(state: tauri::State<AppState>) -> Result<Vec<SyncConflict>, String> {
+    let storage = state.lock_storage();
+    Ok(storage.get_sync_conflicts())
+}
+
+#[tauri::command]
+fn resolve_conflict(
+    state: tauri::State<AppState>,
+    id: String,
+    choice: ConflictChoice,
+) -> Result<(), String> {
+    let mut storage = state.lock_storage();
+    storage.resolve_conflict(&id, choice)
+}
+
 #[tauri::command]
 fn try_reconnect_drive(state: tauri::State<AppState>) -> Result<bool, String> {
     let mut storage = state.lock_storage();
@@ -516,7 +942,7 @@ fn disconnect_drive(state: tauri::State<AppState>) -> Result<(), String> {
 #[tauri::command]
 async fn show_notification_window(
     app: tauri::AppHandle,
-    reminder_id: i64,
+    reminder_id: String,
     message: String,
     due_time: String,
 ) -> Result<(), String> {
@@ -548,7 +974,7 @@ async fn show_notification_window(
     // Build the URL with query parameters
     let url = format!(
         "/notification.html?id={}&message={}&due_time={}",
-        reminder_id,
+        urlencoding::encode(&reminder_id),
         urlencoding::encode(&message),
         urlencoding::encode(&due_time)
     );
@@ -579,7 +1005,10 @@ async fn show_notification_window(
 }
 
 #[tauri::command]
-async fn close_notification_window(app: tauri::AppHandle, reminder_id: i64) -> Result<(), String> {
+async fn close_notification_window(
+    app: tauri::AppHandle,
+    reminder_id: String,
+) -> Result<(), String> {
     let label = format!("notification_{}", reminder_id);
     if let Some(window) = app.get_webview_window(&label) {
         window.close().map_err(|e| e.to_string())?;
@@ -1112,24 +1541,80 @@ pub fn run() {
                 start_organize_prompt_scheduler(app_handle);
             }
 
+            // Start due date / snooze scheduler
+            {
+                let app_handle = app.handle().clone();
+                start_reminder_scheduler(app_handle);
+            }
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_pending_reminders,
             get_actual_reminders,
             get_backlog_reminders,
+            get_snoozed_reminders,
+            get_reminders_by_tag,
+            get_all_tags,
             get_completed_reminders,
             get_completion_stats,
             get_historical_stats,
+            verify_store,
+            repair_store,
+            get_archive_years,
+            get_archived_reminders,
+            search_archive,
+            get_checklist_stats,
             dismiss_organize_prompt,
             add_reminder,
             update_reminder,
             move_reminder,
+            get_lists,
+            get_list_reminders,
+            create_list,
+            update_list,
+            reorder_lists,
+            delete_list,
             set_urgency,
+            set_due_date,
+            clear_due_date,
+            set_recurrence,
+            clear_recurrence,
+            snooze_reminder,
+            unsnooze_reminder,
+            set_notes,
+            add_checklist_item,
+            toggle_checklist_item,
+            reorder_checklist_items,
+            delete_checklist_item,
+            add_tag,
+            remove_tag,
+            rename_tag,
             delete_reminder,
             complete_reminder,
             uncomplete_reminder,
             reorder_reminders,
+            get_settings,
+            set_checklist_policy,
+            set_tombstone_retention_days,
+            set_trash_retention_days,
+            set_archive_after_days,
+            get_trash,
+            restore_from_trash,
+            empty_trash,
+            undo,
+            redo,
+            get_history,
+            get_audit_log,
+            list_backups,
+            restore_backup,
+            get_local_backend,
+            switch_local_backend,
+            get_encryption_status,
+            unlock_storage,
+            enable_encryption,
+            disable_encryption,
+            change_passphrase,
             sync_to_cloud_background,
             refresh_from_cloud,
             sync_on_startup,
@@ -1143,6 +1628,8 @@ pub fn run() {
             unregister_shortcuts,
             register_shortcuts,
             get_sync_status,
+            get_sync_conflicts,
+            resolve_conflict,
             try_reconnect_drive,
             get_oauth_status,
             check_auth_status,
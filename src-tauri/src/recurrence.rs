@@ -0,0 +1,420 @@
+//! Recurrence rules for repeating reminders.
+//!
+//! Rules are written in a small RRULE-style syntax, e.g.
+//! `FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10` or `FREQ=AFTER_COMPLETION;INTERVAL=3`.
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "freq", rename_all = "snake_case")]
+pub enum Frequency {
+    /// Every `interval` days
+    Daily { interval: u32 },
+    /// Every `interval` weeks on the given weekdays
+    Weekly {
+        interval: u32,
+        weekdays: Vec<Weekday>,
+    },
+    /// Every `interval` months on the given day (clamped to the month's last day)
+    Monthly { interval: u32, day_of_month: u32 },
+    /// `days` days after the previous occurrence was completed
+    AfterCompletion { days: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecurrenceRule {
+    #[serde(flatten)]
+    pub frequency: Frequency,
+    /// Last date ("YYYY-MM-DD") an occurrence may fall on
+    #[serde(default)]
+    pub until: Option<String>,
+    /// Total number of occurrences, including the first one
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// 1-based index of the occurrence this rule is attached to
+    #[serde(default = "first_occurrence")]
+    pub occurrence: u32,
+}
+
+fn first_occurrence() -> u32 {
+    1
+}
+
+impl RecurrenceRule {
+    pub fn new(frequency: Frequency) -> Self {
+        Self {
+            frequency,
+            until: None,
+            count: None,
+            occurrence: 1,
+        }
+    }
+
+    /// Parse an RRULE-style string such as `FREQ=MONTHLY;BYMONTHDAY=15;UNTIL=20251231`.
+    /// Monthly rules without BYMONTHDAY repeat on the day of `due`, the due
+    /// date of the reminder the rule is for (today if it has none).
+    pub fn parse(rule: &str, due: Option<DateTime<Utc>>) -> Result<Self, String> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut freq = None;
+        let mut interval = 1;
+        let mut weekdays = Vec::new();
+        let mut day_of_month = None;
+        let mut until = None;
+        let mut count = None;
+
+        for part in rule.split(';').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid recurrence part: {}", part))?;
+            let value = value.trim();
+
+            match key.trim().to_ascii_uppercase().as_str() {
+                "FREQ" => freq = Some(value.to_ascii_uppercase()),
+                "INTERVAL" => interval = parse_positive(key, value)?,
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        weekdays.push(parse_weekday(day)?);
+                    }
+                }
+                "BYMONTHDAY" => day_of_month = Some(parse_positive(key, value)?.min(31)),
+                "COUNT" => count = Some(parse_positive(key, value)?),
+                "UNTIL" => until = Some(parse_until(value)?),
+                other => return Err(format!("Unsupported recurrence field: {}", other)),
+            }
+        }
+
+        let frequency = match freq.as_deref() {
+            Some("DAILY") => Frequency::Daily { interval },
+            Some("WEEKLY") => Frequency::Weekly { interval, weekdays },
+            Some("MONTHLY") => Frequency::Monthly {
+                interval,
+                day_of_month: day_of_month.unwrap_or_else(|| anchor_date(due).day()),
+            },
+            Some("AFTER_COMPLETION") => Frequency::AfterCompletion { days: interval },
+            Some(other) => return Err(format!("Unsupported recurrence frequency: {}", other)),
+            None => return Err("Recurrence rule is missing FREQ".to_string()),
+        };
+
+        Ok(Self {
+            frequency,
+            until,
+            count,
+            occurrence: 1,
+        })
+    }
+
+    /// Map the free-form `recurrence` values of the legacy app onto a rule,
+    /// anchoring weekly and monthly rules on the legacy due date.
+    pub fn from_legacy(value: &str, due: Option<DateTime<Utc>>) -> Option<Self> {
+        let anchor = anchor_date(due);
+
+        let frequency = match value.trim().to_ascii_lowercase().as_str() {
+            "" | "none" | "never" | "once" => return None,
+            "daily" => Frequency::Daily { interval: 1 },
+            "weekdays" => Frequency::Weekly {
+                interval: 1,
+                weekdays: vec![
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ],
+            },
+            "weekly" => Frequency::Weekly {
+                interval: 1,
+                weekdays: vec![anchor.weekday()],
+            },
+            "biweekly" => Frequency::Weekly {
+                interval: 2,
+                weekdays: vec![anchor.weekday()],
+            },
+            "monthly" => Frequency::Monthly {
+                interval: 1,
+                day_of_month: anchor.day(),
+            },
+            _ => match Self::parse(value, due) {
+                Ok(rule) => return Some(rule),
+                Err(e) => {
+                    eprintln!("Dropping unrecognized legacy recurrence {:?}: {}", value, e);
+                    return None;
+                }
+            },
+        };
+
+        Some(Self::new(frequency))
+    }
+
+    /// Compute the `due_at` value of the next occurrence, or None once the
+    /// series has ended. Keeps the shape of `due_at`: date-only values stay
+    /// date-only, timestamps keep their local time of day.
+    pub fn next_due(&self, due_at: Option<&str>, completed_at: DateTime<Utc>) -> Option<String> {
+        if let Some(count) = self.count {
+            if self.occurrence >= count {
+                return None;
+            }
+        }
+
+        let completed_local = completed_at.with_timezone(&Local).naive_local();
+        let current_due = due_at.and_then(parse_local_due);
+        let time_of_day = current_due.and_then(|(dt, date_only)| (!date_only).then_some(dt.time()));
+
+        let next_date = match &self.frequency {
+            Frequency::AfterCompletion { days } => {
+                completed_local.date() + Duration::days(i64::from(*days))
+            }
+            frequency => {
+                // Step forward from the current due date until the occurrence is
+                // after the completion day, so late completions don't spawn
+                // already-overdue copies.
+                let mut date = current_due
+                    .map(|(dt, _)| dt.date())
+                    .unwrap_or_else(|| completed_local.date());
+                loop {
+                    date = step(frequency, date)?;
+                    if date > completed_local.date() {
+                        break date;
+                    }
+                }
+            }
+        };
+
+        if let Some(until) = self.until.as_deref() {
+            let until = NaiveDate::parse_from_str(until, "%Y-%m-%d").ok()?;
+            if next_date > until {
+                return None;
+            }
+        }
+
+        Some(match time_of_day {
+            Some(time) => Local
+                .from_local_datetime(&next_date.and_time(time))
+                .earliest()?
+                .to_rfc3339(),
+            None => next_date.format("%Y-%m-%d").to_string(),
+        })
+    }
+
+    /// The rule to attach to the occurrence following this one
+    pub fn advanced(&self) -> Self {
+        Self {
+            occurrence: self.occurrence + 1,
+            ..self.clone()
+        }
+    }
+}
+
+/// Local date weekly and monthly rules are anchored on: the due date, or
+/// today without one
+fn anchor_date(due: Option<DateTime<Utc>>) -> NaiveDate {
+    due.map(|d| d.with_timezone(&Local).date_naive())
+        .unwrap_or_else(|| Local::now().date_naive())
+}
+
+/// Single step of a calendar-based frequency starting after `from`
+fn step(frequency: &Frequency, from: NaiveDate) -> Option<NaiveDate> {
+    match frequency {
+        Frequency::Daily { interval } => Some(from + Duration::days(i64::from((*interval).max(1)))),
+        Frequency::Weekly { interval, weekdays } => {
+            let interval = i64::from((*interval).max(1));
+            let week_start =
+                |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+            let base_week = week_start(from);
+
+            (1..=7 * interval + 7)
+                .map(|offset| from + Duration::days(offset))
+                .find(|candidate| {
+                    let weeks_apart = (week_start(*candidate) - base_week).num_days() / 7;
+                    let on_day = if weekdays.is_empty() {
+                        candidate.weekday() == from.weekday()
+                    } else {
+                        weekdays.contains(&candidate.weekday())
+                    };
+                    on_day && weeks_apart % interval == 0
+                })
+        }
+        Frequency::Monthly {
+            interval,
+            day_of_month,
+        } => {
+            let months = from.year() * 12 + from.month0() as i32 + (*interval).max(1) as i32;
+            let (year, month) = (months / 12, months as u32 % 12 + 1);
+            let last_day = last_day_of_month(year, month)?;
+            NaiveDate::from_ymd_opt(year, month, (*day_of_month).clamp(1, last_day))
+        }
+        Frequency::AfterCompletion { .. } => None,
+    }
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<u32> {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1).map(|d| d.pred_opt().map_or(28, |d| d.day()))
+}
+
+/// Parse a `due_at` value into local time, flagging date-only values
+fn parse_local_due(value: &str) -> Option<(NaiveDateTime, bool)> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some((dt.with_timezone(&Local).naive_local(), false));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| (dt, true))
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} must be a positive number", key.trim())),
+    }
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid weekday: {}", other)),
+    }
+}
+
+fn parse_until(value: &str) -> Result<String, String> {
+    let date_part = value.split('T').next().unwrap_or(value);
+    NaiveDate::parse_from_str(date_part, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(date_part, "%Y-%m-%d"))
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .map_err(|_| format!("Invalid UNTIL date: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completed_on(date: &str) -> DateTime<Utc> {
+        let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        Local
+            .from_local_datetime(&day.and_hms_opt(12, 0, 0).unwrap())
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_rrule() {
+        let rule =
+            RecurrenceRule::parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;COUNT=4", None).unwrap();
+        assert_eq!(
+            rule.frequency,
+            Frequency::Weekly {
+                interval: 2,
+                weekdays: vec![Weekday::Mon, Weekday::Fri]
+            }
+        );
+        assert_eq!(rule.count, Some(4));
+
+        let rule =
+            RecurrenceRule::parse("RRULE:FREQ=MONTHLY;BYMONTHDAY=31;UNTIL=20251231", None).unwrap();
+        assert_eq!(rule.until.as_deref(), Some("2025-12-31"));
+
+        assert!(RecurrenceRule::parse("FREQ=HOURLY", None).is_err());
+        assert!(RecurrenceRule::parse("INTERVAL=2", None).is_err());
+    }
+
+    #[test]
+    fn test_daily_skips_past_occurrences() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY", None).unwrap();
+        // Due on the 1st but completed on the 3rd: next one is the 4th
+        let next = rule.next_due(Some("2024-05-01"), completed_on("2024-05-03"));
+        assert_eq!(next.as_deref(), Some("2024-05-04"));
+    }
+
+    #[test]
+    fn test_weekly_on_weekdays() {
+        let rule = RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=MO,WE", None).unwrap();
+        // 2024-05-06 is a Monday
+        let next = rule.next_due(Some("2024-05-06"), completed_on("2024-05-06"));
+        assert_eq!(next.as_deref(), Some("2024-05-08"));
+        let next = rule.next_due(Some("2024-05-08"), completed_on("2024-05-08"));
+        assert_eq!(next.as_deref(), Some("2024-05-13"));
+    }
+
+    #[test]
+    fn test_monthly_clamps_to_month_end() {
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY;BYMONTHDAY=31", None).unwrap();
+        let next = rule.next_due(Some("2024-01-31"), completed_on("2024-01-31"));
+        assert_eq!(next.as_deref(), Some("2024-02-29"));
+    }
+
+    #[test]
+    fn test_monthly_defaults_to_due_day() {
+        let due = completed_on("2024-01-20");
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY", Some(due)).unwrap();
+        assert_eq!(
+            rule.frequency,
+            Frequency::Monthly {
+                interval: 1,
+                day_of_month: 20
+            }
+        );
+        let next = rule.next_due(Some("2024-01-20"), completed_on("2024-01-20"));
+        assert_eq!(next.as_deref(), Some("2024-02-20"));
+    }
+
+    #[test]
+    fn test_after_completion() {
+        let rule = RecurrenceRule::parse("FREQ=AFTER_COMPLETION;INTERVAL=3", None).unwrap();
+        let next = rule.next_due(Some("2024-05-01"), completed_on("2024-05-10"));
+        assert_eq!(next.as_deref(), Some("2024-05-13"));
+    }
+
+    #[test]
+    fn test_series_ends_by_count_and_until() {
+        let mut rule = RecurrenceRule::parse("FREQ=DAILY;COUNT=2", None).unwrap();
+        assert!(rule
+            .next_due(Some("2024-05-01"), completed_on("2024-05-01"))
+            .is_some());
+        rule = rule.advanced();
+        assert!(rule
+            .next_due(Some("2024-05-02"), completed_on("2024-05-02"))
+            .is_none());
+
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=2024-05-02", None).unwrap();
+        assert!(rule
+            .next_due(Some("2024-05-02"), completed_on("2024-05-02"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_from_legacy_values() {
+        assert!(RecurrenceRule::from_legacy("", None).is_none());
+        assert!(RecurrenceRule::from_legacy("none", None).is_none());
+        assert_eq!(
+            RecurrenceRule::from_legacy("daily", None)
+                .unwrap()
+                .frequency,
+            Frequency::Daily { interval: 1 }
+        );
+        let due = completed_on("2024-05-08"); // Wednesday
+        assert_eq!(
+            RecurrenceRule::from_legacy("weekly", Some(due))
+                .unwrap()
+                .frequency,
+            Frequency::Weekly {
+                interval: 1,
+                weekdays: vec![Weekday::Wed]
+            }
+        );
+    }
+}
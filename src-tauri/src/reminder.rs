@@ -1,7 +1,85 @@
-use chrono::Utc;
-use serde::{Deserialize, Serialize};
+use crate::clock;
+use crate::recurrence::RecurrenceRule;
+use crate::sort_key;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Globally unique reminder identifier (UUIDv7 for new reminders)
+pub type ReminderId = String;
+
+/// Generate a new collision-free reminder id. UUIDv7 is time-ordered, so ids
+/// still sort roughly by creation time.
+pub fn new_reminder_id() -> ReminderId {
+    Uuid::now_v7().to_string()
+}
+
+/// A replacement for an integer id handed out by the old max+1 scheme, unique
+/// across devices; None if `id` isn't one. Two devices may have used the same
+/// integer for different reminders, so the creation time is folded in; every
+/// device derives the same new id for the same reminder.
+pub fn unique_legacy_id(id: &str, created_at: &str) -> Option<ReminderId> {
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let created = DateTime::parse_from_rfc3339(created_at)
+        .map(|dt| dt.timestamp_millis().to_string())
+        .unwrap_or_else(|_| {
+            created_at
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect()
+        });
+    Some(format!("{}-{}", id, created))
+}
+
+/// Accept both the current string ids and the integer ids written by older
+/// versions. Integer ids are normalized by the schema migrations.
+fn deserialize_id<'de, D>(deserializer: D) -> Result<ReminderId, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawId {
+        Int(i64),
+        Str(String),
+    }
+
+    Ok(match RawId::deserialize(deserializer)? {
+        RawId::Int(n) => n.to_string(),
+        RawId::Str(s) => s,
+    })
+}
+
+/// Accept both sort keys and the integer positions written by older versions,
+/// which may still turn up in operation logs and database rows. Integers
+/// become the keys that sort the same way.
+fn deserialize_sort_key<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawSortKey {
+        Int(i64),
+        Str(String),
+    }
+
+    Ok(match RawSortKey::deserialize(deserializer)? {
+        RawSortKey::Int(n) => sort_key::from_integer(n),
+        RawSortKey::Str(s) => s,
+    })
+}
+
+fn default_sort_key() -> String {
+    sort_key::FIRST.to_string()
+}
+
+/// Variants are declared most urgent first, so `Urgency::Now < Urgency::Whenever`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum Urgency {
     Now,
@@ -16,11 +94,31 @@ impl Default for Urgency {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
+impl Urgency {
+    /// Urgency bucket for the time remaining until a deadline
+    pub fn from_time_left(time_left: Duration) -> Self {
+        let hours_until = time_left.num_hours();
+
+        if hours_until <= 1 {
+            Urgency::Now
+        } else if hours_until <= 24 {
+            Urgency::Today
+        } else if hours_until <= 168 {
+            // 7 days
+            Urgency::Soon
+        } else {
+            Urgency::Whenever
+        }
+    }
+}
+
+/// The list a reminder lives in. Actual and Backlog are built in; `Custom`
+/// holds the id of a user-created list. Serialized as a plain string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ListType {
     Actual,
     Backlog,
+    Custom(String),
 }
 
 impl Default for ListType {
@@ -29,9 +127,40 @@ impl Default for ListType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ListType {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "actual" => ListType::Actual,
+            "backlog" => ListType::Backlog,
+            other => ListType::Custom(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ListType::Actual => "actual",
+            ListType::Backlog => "backlog",
+            ListType::Custom(id) => id,
+        }
+    }
+}
+
+impl Serialize for ListType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ListType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|value| ListType::parse(&value))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Reminder {
-    pub id: i64,
+    #[serde(deserialize_with = "deserialize_id")]
+    pub id: ReminderId,
     pub message: String,
     pub urgency: Urgency,
     pub list_type: ListType,
@@ -39,20 +168,332 @@ pub struct Reminder {
     pub is_completed: bool,
     pub completed_at: Option<String>,
     #[serde(default)]
-    pub sort_order: i64, // Lower = higher priority (shown first in list / leftmost on bar)
+    pub next_occurrence: Option<ReminderId>, // Spawned by completing this recurring reminder
+    #[serde(
+        default = "default_sort_key",
+        deserialize_with = "deserialize_sort_key"
+    )]
+    pub sort_order: String, // Key from `sort_key`; lower = higher priority (shown first in list / leftmost on bar)
+    #[serde(default)]
+    pub due_at: Option<String>, // "YYYY-MM-DD" (due by end of day) or RFC3339 date+time
+    #[serde(default)]
+    pub recurrence: Option<RecurrenceRule>,
+    #[serde(default)]
+    pub hidden_until: Option<String>, // Snoozed: hidden from Actual/Backlog until this RFC3339 time
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: String, // Markdown body; `message` stays the title
+    #[serde(default)]
+    pub checklist: Vec<ChecklistItem>, // Ordered subtasks
+    #[serde(default)]
+    pub updated_at: Option<String>, // Last change to any field
+    #[serde(default)]
+    pub modified: BTreeMap<String, String>, // Field name (see `field`) -> clock stamp of last change
+}
+
+/// Names of the fields tracked in `Reminder::modified`
+pub mod field {
+    pub const MESSAGE: &str = "message";
+    pub const URGENCY: &str = "urgency";
+    pub const LIST_TYPE: &str = "list_type";
+    pub const SORT_ORDER: &str = "sort_order";
+    pub const COMPLETION: &str = "completion"; // is_completed + completed_at + next_occurrence
+    pub const DUE_AT: &str = "due_at";
+    pub const RECURRENCE: &str = "recurrence";
+    pub const HIDDEN_UNTIL: &str = "hidden_until";
+    pub const TAGS: &str = "tags";
+    pub const NOTES: &str = "notes";
+    pub const CHECKLIST: &str = "checklist";
+
+    pub const ALL: [&str; 11] = [
+        MESSAGE,
+        URGENCY,
+        LIST_TYPE,
+        SORT_ORDER,
+        COMPLETION,
+        DUE_AT,
+        RECURRENCE,
+        HIDDEN_UNTIL,
+        TAGS,
+        NOTES,
+        CHECKLIST,
+    ];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChecklistItem {
+    pub id: u32, // Unique within its reminder
+    pub text: String,
+    pub done: bool,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl ChecklistItem {
+    pub fn new(id: u32, text: String) -> Self {
+        Self {
+            id,
+            text,
+            done: false,
+            created_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+        }
+    }
+
+    pub fn set_done(&mut self, done: bool) {
+        self.done = done;
+        self.completed_at = done.then(|| Utc::now().to_rfc3339());
+    }
 }
 
 impl Reminder {
     pub fn new(message: String, urgency: Urgency, list_type: ListType) -> Self {
         Self {
-            id: 0, // Will be set by storage
+            id: new_reminder_id(),
             message,
             urgency,
             list_type,
             created_at: Utc::now().to_rfc3339(),
             is_completed: false,
             completed_at: None,
-            sort_order: 0, // New reminders go to top (most important)
+            next_occurrence: None,
+            sort_order: default_sort_key(), // Placed by storage when added to a list
+            due_at: None,
+            recurrence: None,
+            hidden_until: None,
+            tags: Vec::new(),
+            notes: String::new(),
+            checklist: Vec::new(),
+            updated_at: None,
+            modified: BTreeMap::new(),
+        }
+    }
+
+    /// Record that the given fields changed now, stamped by this device's
+    /// clock (see `clock`)
+    pub fn touch(&mut self, fields: &[&str]) {
+        let stamp = clock::now();
+        let value = stamp.to_string();
+        for field in fields {
+            self.modified.insert(field.to_string(), value.clone());
+        }
+        self.updated_at = Some(stamp.time_string());
+    }
+
+    /// Position within a list: by sort key, ties broken by id so every device
+    /// shows the same order
+    pub fn cmp_position(&self, other: &Reminder) -> Ordering {
+        (&self.sort_order, &self.id).cmp(&(&other.sort_order, &other.id))
+    }
+
+    /// Tracked fields (see `field`) whose values differ from `other`
+    pub fn changed_fields(&self, other: &Reminder) -> Vec<&'static str> {
+        [
+            (field::MESSAGE, self.message != other.message),
+            (field::URGENCY, self.urgency != other.urgency),
+            (field::LIST_TYPE, self.list_type != other.list_type),
+            (field::SORT_ORDER, self.sort_order != other.sort_order),
+            (
+                field::COMPLETION,
+                self.is_completed != other.is_completed
+                    || self.completed_at != other.completed_at
+                    || self.next_occurrence != other.next_occurrence,
+            ),
+            (field::DUE_AT, self.due_at != other.due_at),
+            (field::RECURRENCE, self.recurrence != other.recurrence),
+            (field::HIDDEN_UNTIL, self.hidden_until != other.hidden_until),
+            (field::TAGS, self.tags != other.tags),
+            (field::NOTES, self.notes != other.notes),
+            (field::CHECKLIST, self.checklist != other.checklist),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Take one tracked field's value (see `field`) from `other`
+    pub fn copy_field(&mut self, other: &Reminder, name: &str) {
+        match name {
+            field::MESSAGE => self.message = other.message.clone(),
+            field::URGENCY => self.urgency = other.urgency.clone(),
+            field::LIST_TYPE => self.list_type = other.list_type.clone(),
+            field::SORT_ORDER => self.sort_order = other.sort_order.clone(),
+            field::COMPLETION => {
+                self.is_completed = other.is_completed;
+                self.completed_at = other.completed_at.clone();
+                self.next_occurrence = other.next_occurrence.clone();
+            }
+            field::DUE_AT => self.due_at = other.due_at.clone(),
+            field::RECURRENCE => self.recurrence = other.recurrence.clone(),
+            field::HIDDEN_UNTIL => self.hidden_until = other.hidden_until.clone(),
+            field::TAGS => self.tags = other.tags.clone(),
+            field::NOTES => self.notes = other.notes.clone(),
+            field::CHECKLIST => self.checklist = other.checklist.clone(),
+            _ => {}
+        }
+    }
+
+    /// Time of the last change to the reminder. Reminders saved before change
+    /// tracking fall back to when they were completed or created.
+    pub fn last_modified(&self) -> &str {
+        self.updated_at
+            .as_deref()
+            .or(self.completed_at.as_deref())
+            .unwrap_or(&self.created_at)
+    }
+
+    /// Clock stamp of the newest field change, to order the reminder against
+    /// deletions. Reminders saved before stamps fall back to `last_modified`.
+    pub fn last_stamp(&self) -> &str {
+        self.modified
+            .values()
+            .map(String::as_str)
+            .reduce(|latest, stamp| {
+                if clock::is_newer(latest, stamp) {
+                    stamp
+                } else {
+                    latest
+                }
+            })
+            .unwrap_or_else(|| self.last_modified())
+    }
+
+    /// Clock stamp of the last change to one field. Fields never changed since
+    /// tracking began date from creation; reminders saved before tracking
+    /// fall back to `last_modified`.
+    pub fn field_modified(&self, field: &str) -> &str {
+        match self.modified.get(field) {
+            Some(time) => time,
+            None if self.updated_at.is_some() => &self.created_at,
+            None => self.last_modified(),
         }
     }
+
+    /// Checklist progress as (done, total)
+    pub fn checklist_progress(&self) -> (usize, usize) {
+        let done = self.checklist.iter().filter(|item| item.done).count();
+        (done, self.checklist.len())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Whether the reminder is snoozed and should stay hidden at `now`
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.hidden_until
+            .as_deref()
+            .and_then(|until| DateTime::parse_from_rfc3339(until).ok())
+            .is_some_and(|until| until > now)
+    }
+
+    /// Deadline as an instant, if a valid due date is set
+    pub fn due_datetime(&self) -> Option<DateTime<Utc>> {
+        self.due_at.as_deref().and_then(parse_due_at)
+    }
+
+    /// Urgency implied by the time left until the deadline, if there is one
+    pub fn suggested_urgency(&self, now: DateTime<Utc>) -> Option<Urgency> {
+        self.due_datetime()
+            .map(|due| Urgency::from_time_left(due - now))
+    }
+}
+
+/// Parse a due date given either as a plain date or as an RFC3339 timestamp.
+/// Date-only values are due at the end of that day in local time.
+pub fn parse_due_at(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let end_of_day = date.and_hms_opt(23, 59, 59)?;
+    Local
+        .from_local_datetime(&end_of_day)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urgency_from_time_left() {
+        assert_eq!(Urgency::from_time_left(Duration::minutes(30)), Urgency::Now);
+        assert_eq!(Urgency::from_time_left(Duration::hours(-5)), Urgency::Now);
+        assert_eq!(Urgency::from_time_left(Duration::hours(12)), Urgency::Today);
+        assert_eq!(Urgency::from_time_left(Duration::days(3)), Urgency::Soon);
+        assert_eq!(
+            Urgency::from_time_left(Duration::days(30)),
+            Urgency::Whenever
+        );
+    }
+
+    #[test]
+    fn test_parse_due_at_accepts_date_and_datetime() {
+        let dt = parse_due_at("2024-03-01T10:00:00Z").unwrap();
+        assert_eq!(dt.to_rfc3339(), "2024-03-01T10:00:00+00:00");
+
+        let date_only = parse_due_at("2024-03-01").unwrap();
+        let local = date_only.with_timezone(&Local);
+        assert_eq!(
+            local.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()
+        );
+
+        assert!(parse_due_at("next tuesday").is_none());
+    }
+
+    #[test]
+    fn test_suggested_urgency_without_due_date() {
+        let reminder = Reminder::new("Task".to_string(), Urgency::Soon, ListType::Actual);
+        assert_eq!(reminder.suggested_urgency(Utc::now()), None);
+    }
+
+    #[test]
+    fn test_deserialize_integer_and_string_ids() {
+        let json = r#"{"id":5,"message":"Old","urgency":"today","list_type":"actual",
+            "created_at":"2024-01-01T00:00:00Z","is_completed":false,"completed_at":null}"#;
+        let reminder: Reminder = serde_json::from_str(json).unwrap();
+        assert_eq!(reminder.id, "5");
+
+        let unique = unique_legacy_id(&reminder.id, &reminder.created_at);
+        assert_eq!(unique.as_deref(), Some("5-1704067200000"));
+        assert_eq!(
+            unique_legacy_id("5-1704067200000", &reminder.created_at),
+            None
+        );
+
+        let fresh = Reminder::new("New".to_string(), Urgency::Today, ListType::Actual);
+        let json = serde_json::to_string(&fresh).unwrap();
+        let parsed: Reminder = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.id, fresh.id);
+        assert_eq!(unique_legacy_id(&parsed.id, &parsed.created_at), None);
+    }
+
+    #[test]
+    fn test_new_reminder_ids_are_unique() {
+        let a = Reminder::new("A".to_string(), Urgency::Today, ListType::Actual);
+        let b = Reminder::new("B".to_string(), Urgency::Today, ListType::Actual);
+        assert_ne!(a.id, b.id);
+    }
+
+    #[test]
+    fn test_list_type_serializes_as_string() {
+        assert_eq!(
+            serde_json::to_string(&ListType::Backlog).unwrap(),
+            "\"backlog\""
+        );
+        let custom = ListType::Custom("errands".to_string());
+        let json = serde_json::to_string(&custom).unwrap();
+        assert_eq!(json, "\"errands\"");
+        assert_eq!(serde_json::from_str::<ListType>(&json).unwrap(), custom);
+        assert_eq!(
+            serde_json::from_str::<ListType>("\"actual\"").unwrap(),
+            ListType::Actual
+        );
+    }
 }
@@ -0,0 +1,429 @@
+//! Fractional keys for ordering reminders within a list
+//!
+//! Keys sort as plain text. There is always room for a key between two
+//! others, so putting a reminder somewhere only changes that reminder's key,
+//! and reorders made on different devices merge without renumbering.
+//!
+//! A key is an integer part followed by an optional fraction. The first
+//! character of the integer part gives its length, so integers sort
+//! correctly as text: `a0`..`az` are 0..61, `b00` is 62, `Zz` is -1. The
+//! fraction is base 62 and never ends in `0`, so every key has exactly one
+//! spelling.
+
+const DIGITS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = DIGITS.len();
+
+/// The lowest integer part. It is never a key by itself, so there is always
+/// room for a key before any other.
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+/// Key of the first reminder in an empty list
+pub const FIRST: &str = "a0";
+
+fn digit_value(digit: u8) -> Option<usize> {
+    DIGITS.iter().position(|d| *d == digit)
+}
+
+/// Length of the integer part starting with `head`
+fn integer_length(head: u8) -> Option<usize> {
+    match head {
+        b'a'..=b'z' => Some((head - b'a') as usize + 2),
+        b'A'..=b'Z' => Some((b'Z' - head) as usize + 2),
+        _ => None,
+    }
+}
+
+/// True if `key` is a well-formed key
+pub fn is_valid(key: &str) -> bool {
+    let bytes = key.as_bytes();
+    let Some(length) = bytes.first().and_then(|head| integer_length(*head)) else {
+        return false;
+    };
+    bytes.len() >= length
+        && bytes[1..].iter().all(|b| digit_value(*b).is_some())
+        && &key[..length] != SMALLEST_INTEGER
+        && !key[length..].ends_with('0')
+}
+
+/// Split a valid key into its integer part and fraction
+fn split(key: &str) -> (&str, &str) {
+    let length = integer_length(key.as_bytes()[0]).unwrap_or(key.len());
+    key.split_at(length)
+}
+
+/// The key for integer `n`. Keeps the order of the integer positions used
+/// before keys, so old data converts without looking at its neighbours.
+pub fn from_integer(n: i64) -> String {
+    // Non-negative integers count up from `a0`, negative ones down from `Zz`
+    let (mut rest, upwards) = if n >= 0 {
+        (n.unsigned_abs() as u128, true)
+    } else {
+        ((n.unsigned_abs() - 1) as u128, false)
+    };
+    let mut width = 1u32;
+    while rest >= (BASE as u128).pow(width) {
+        rest -= (BASE as u128).pow(width);
+        width += 1;
+    }
+    let (head, value) = if upwards {
+        (b'a' + (width - 1) as u8, rest)
+    } else {
+        (
+            b'Z' - (width - 1) as u8,
+            (BASE as u128).pow(width) - 1 - rest,
+        )
+    };
+
+    let mut digits = vec![b'0'; width as usize];
+    let mut value = value;
+    for digit in digits.iter_mut().rev() {
+        *digit = DIGITS[(value % BASE as u128) as usize];
+        value /= BASE as u128;
+    }
+    join(head, &digits)
+}
+
+/// `head` followed by `digits`, as text
+fn join(head: u8, digits: &[u8]) -> String {
+    std::iter::once(head)
+        .chain(digits.iter().copied())
+        .map(char::from)
+        .collect()
+}
+
+/// The next integer part, or None after the largest
+fn increment_integer(integer: &str) -> Option<String> {
+    let (head, digits) = integer.as_bytes().split_first()?;
+    let mut digits = digits.to_vec();
+    for digit in digits.iter_mut().rev() {
+        let value = digit_value(*digit)? + 1;
+        if value < BASE {
+            *digit = DIGITS[value];
+            return Some(join(*head, &digits));
+        }
+        *digit = DIGITS[0];
+    }
+    // Every digit carried over: the integer part gets longer or shorter
+    let head = match *head {
+        b'Z' => return Some(FIRST.to_string()),
+        b'z' => return None,
+        head => head + 1,
+    };
+    if head > b'a' {
+        digits.push(DIGITS[0]);
+    } else {
+        digits.pop();
+    }
+    Some(join(head, &digits))
+}
+
+/// The previous integer part, or None before the smallest
+fn decrement_integer(integer: &str) -> Option<String> {
+    let (head, digits) = integer.as_bytes().split_first()?;
+    let mut digits = digits.to_vec();
+    for digit in digits.iter_mut().rev() {
+        let value = digit_value(*digit)?;
+        if value > 0 {
+            *digit = DIGITS[value - 1];
+            return Some(join(*head, &digits));
+        }
+        *digit = DIGITS[BASE - 1];
+    }
+    let head = match *head {
+        b'a' => return Some(join(b'Z', &[DIGITS[BASE - 1]])),
+        b'A' => return None,
+        head => head - 1,
+    };
+    if head < b'Z' {
+        digits.push(DIGITS[BASE - 1]);
+    } else {
+        digits.pop();
+    }
+    Some(join(head, &digits))
+}
+
+/// A fraction between fractions `a` and `b` (None: no upper bound). `a` must
+/// sort before `b`, and neither may end in `0`.
+fn midpoint(a: &str, b: Option<&str>) -> String {
+    if let Some(b) = b {
+        // Skip the common prefix, reading missing digits of `a` as 0
+        let digit_of_a = |i: usize| a.as_bytes().get(i).copied().unwrap_or(DIGITS[0]);
+        let prefix = b
+            .bytes()
+            .enumerate()
+            .take_while(|(i, digit)| digit_of_a(*i) == *digit)
+            .count();
+        if prefix > 0 {
+            return format!(
+                "{}{}",
+                &b[..prefix],
+                midpoint(a.get(prefix..).unwrap_or_default(), Some(&b[prefix..]))
+            );
+        }
+    }
+
+    let digit_a = a.bytes().next().and_then(digit_value).unwrap_or(0);
+    let digit_b = b
+        .and_then(|b| b.bytes().next())
+        .and_then(digit_value)
+        .unwrap_or(BASE);
+    if digit_b - digit_a > 1 {
+        return (DIGITS[(digit_a + digit_b).div_ceil(2)] as char).to_string();
+    }
+    // Adjacent digits: a longer `b` leaves room after its first digit
+    match b {
+        Some(b) if b.len() > 1 => b[..1].to_string(),
+        _ => format!(
+            "{}{}",
+            DIGITS[digit_a] as char,
+            midpoint(a.get(1..).unwrap_or_default(), None)
+        ),
+    }
+}
+
+/// Invalid bounds are ignored, and so is an `after` that doesn't come after
+/// `before`: the key then only needs to follow `before`.
+fn usable_bounds<'a>(
+    before: Option<&'a str>,
+    after: Option<&'a str>,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let before = before.filter(|key| is_valid(key));
+    let after = after
+        .filter(|key| is_valid(key))
+        .filter(|after| before.is_none_or(|before| before < *after));
+    (before, after)
+}
+
+/// A key between `before` and `after`, where None is the start or end of the
+/// list
+pub fn between(before: Option<&str>, after: Option<&str>) -> String {
+    match usable_bounds(before, after) {
+        (None, None) => FIRST.to_string(),
+        (None, Some(after)) => {
+            let (integer, fraction) = split(after);
+            if integer == SMALLEST_INTEGER {
+                return format!("{}{}", integer, midpoint("", Some(fraction)));
+            }
+            if !fraction.is_empty() {
+                return integer.to_string();
+            }
+            // The smallest integer part is never a key, so this can't fail
+            decrement_integer(integer).unwrap_or_default()
+        }
+        (Some(before), None) => {
+            let (integer, fraction) = split(before);
+            increment_integer(integer)
+                .unwrap_or_else(|| format!("{}{}", integer, midpoint(fraction, None)))
+        }
+        (Some(before), Some(after)) => {
+            let (integer_a, fraction_a) = split(before);
+            let (integer_b, fraction_b) = split(after);
+            if integer_a == integer_b {
+                return format!("{}{}", integer_a, midpoint(fraction_a, Some(fraction_b)));
+            }
+            match increment_integer(integer_a) {
+                Some(next) if next.as_str() < after => next,
+                _ => format!("{}{}", integer_a, midpoint(fraction_a, None)),
+            }
+        }
+    }
+}
+
+/// `n` ascending keys between `before` and `after`, spread out so none of
+/// them grows much longer than the bounds
+pub fn n_between(before: Option<&str>, after: Option<&str>, n: usize) -> Vec<String> {
+    let (before, after) = usable_bounds(before, after);
+    match (n, before, after) {
+        (0, _, _) => Vec::new(),
+        (1, _, _) => vec![between(before, after)],
+        (_, _, None) => {
+            let mut keys = vec![between(before, None)];
+            while keys.len() < n {
+                let key = between(keys.last().map(String::as_str), None);
+                keys.push(key);
+            }
+            keys
+        }
+        (_, None, Some(_)) => {
+            let mut keys = vec![between(None, after)];
+            while keys.len() < n {
+                let key = between(None, keys.last().map(String::as_str));
+                keys.push(key);
+            }
+            keys.reverse();
+            keys
+        }
+        (_, Some(_), Some(_)) => {
+            let middle = between(before, after);
+            let mut keys = n_between(before, Some(&middle), n / 2);
+            let later = n_between(Some(&middle), after, n - n / 2 - 1);
+            keys.push(middle);
+            keys.extend(later);
+            keys
+        }
+    }
+}
+
+/// New keys that put items in the given order, changing as few as possible.
+/// `keys` are the items' current keys in the wanted order; the result has the
+/// new key of each item that has to move, and None for the rest.
+pub fn reorder(keys: &[&str]) -> Vec<Option<String>> {
+    // Keep the longest run of items whose keys already ascend in this order
+    let mut tails: Vec<usize> = Vec::new(); // Last item of the best run of each length
+    let mut previous: Vec<Option<usize>> = vec![None; keys.len()];
+    for (i, key) in keys.iter().enumerate() {
+        if !is_valid(key) {
+            continue;
+        }
+        let length = tails.partition_point(|tail| keys[*tail] < *key);
+        previous[i] = length.checked_sub(1).map(|before| tails[before]);
+        if length == tails.len() {
+            tails.push(i);
+        } else {
+            tails[length] = i;
+        }
+    }
+    let mut kept = vec![false; keys.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        kept[i] = true;
+        next = previous[i];
+    }
+
+    // Give the other items keys between the kept ones around them
+    let mut new_keys: Vec<Option<String>> = vec![None; keys.len()];
+    let mut before: Option<String> = None;
+    let mut i = 0;
+    while i < keys.len() {
+        if kept[i] {
+            before = Some(keys[i].to_string());
+            i += 1;
+            continue;
+        }
+        let end = (i..keys.len()).find(|j| kept[*j]).unwrap_or(keys.len());
+        let after = keys.get(end).copied();
+        for (j, key) in (i..end).zip(n_between(before.as_deref(), after, end - i)) {
+            new_keys[j] = Some(key);
+        }
+        before = new_keys[end - 1].clone();
+        i = end;
+    }
+    new_keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_keys_keep_their_order() {
+        assert_eq!(from_integer(0), FIRST);
+        assert_eq!(from_integer(61), "az");
+        assert_eq!(from_integer(62), "b00");
+        assert_eq!(from_integer(-1), "Zz");
+        assert_eq!(from_integer(-62), "Z0");
+        assert_eq!(from_integer(-63), "Yzz");
+
+        let samples = [
+            i64::MIN,
+            -3845,
+            -3844,
+            -63,
+            -62,
+            -1,
+            0,
+            1,
+            61,
+            62,
+            3905,
+            i64::MAX,
+        ];
+        for pair in samples.windows(2) {
+            let (a, b) = (from_integer(pair[0]), from_integer(pair[1]));
+            assert!(is_valid(&a) && is_valid(&b), "{} {}", a, b);
+            assert!(a < b, "{} should sort before {}", a, b);
+        }
+        for n in -200..200 {
+            assert_eq!(
+                increment_integer(&from_integer(n)),
+                Some(from_integer(n + 1))
+            );
+            assert_eq!(
+                decrement_integer(&from_integer(n)),
+                Some(from_integer(n - 1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_keys_between() {
+        assert_eq!(between(None, None), "a0");
+        assert_eq!(between(Some("a0"), None), "a1");
+        assert_eq!(between(None, Some("a0")), "Zz");
+        assert_eq!(between(Some("a0"), Some("a1")), "a0V");
+        assert_eq!(between(Some("a0V"), Some("a1")), "a0l");
+        assert_eq!(between(Some("az"), None), "b00");
+        assert_eq!(between(Some("a0"), Some("a0V")), "a0G");
+        assert_eq!(between(Some("a1"), Some("a3")), "a2");
+        // Bad bounds are ignored rather than producing a misplaced key
+        assert_eq!(between(Some("a1"), Some("a1")), "a2");
+        assert_eq!(between(Some("7"), Some("a0")), "Zz");
+
+        // Repeatedly inserting at the same spot keeps working
+        let (mut low, high) = (FIRST.to_string(), "a1".to_string());
+        for _ in 0..200 {
+            let key = between(Some(&low), Some(&high));
+            assert!(is_valid(&key));
+            assert!(low < key && key < high);
+            low = key;
+        }
+    }
+
+    #[test]
+    fn test_n_keys_between() {
+        for (before, after) in [
+            (None, None),
+            (Some("a0"), None),
+            (None, Some("a0")),
+            (Some("a0"), Some("a1")),
+        ] {
+            let keys = n_between(before, after, 10);
+            assert_eq!(keys.len(), 10);
+            assert!(keys.iter().all(|key| is_valid(key)));
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(before.is_none_or(|before| before < keys[0].as_str()));
+            assert!(after.is_none_or(|after| keys[9].as_str() < after));
+        }
+    }
+
+    #[test]
+    fn test_reorder_moves_only_what_it_must() {
+        let keys: Vec<String> = (0..6).map(from_integer).collect();
+        let key = |i: usize| keys[i].as_str();
+
+        // Moving the last item to the front changes only that item
+        let new_keys = reorder(&[key(5), key(0), key(1), key(2), key(3), key(4)]);
+        assert!(new_keys[0]
+            .as_deref()
+            .is_some_and(|new_key| new_key < key(0)));
+        assert!(new_keys[1..].iter().all(Option::is_none));
+
+        // Swapping two neighbours changes one of them
+        let new_keys = reorder(&[key(0), key(2), key(1), key(3)]);
+        assert_eq!(new_keys.iter().filter(|k| k.is_some()).count(), 1);
+
+        // Duplicate and invalid keys get new ones in place
+        let new_keys = reorder(&[key(0), key(0), "", key(1)]);
+        let result: Vec<String> = new_keys
+            .iter()
+            .zip([key(0), key(0), "", key(1)])
+            .map(|(new_key, old)| new_key.clone().unwrap_or_else(|| old.to_string()))
+            .collect();
+        assert!(
+            result.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            result
+        );
+        assert!(result.iter().all(|key| is_valid(key)));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::error::AppError;
+use crate::reminder::Reminder;
+use crate::storage::backup::write_atomic;
+use crate::storage::crypto::Cipher;
+use crate::storage::migrations::{NEWER_VERSION_ERROR, SCHEMA_VERSION};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_DIR: &str = "archive";
+const ARCHIVE_PREFIX: &str = "reminders-";
+
+/// One year of archived reminders, as stored locally and on Drive
+#[derive(Serialize, Deserialize)]
+struct ArchiveFile {
+    schema_version: u32,
+    year: i32,
+    reminders: Vec<Reminder>,
+}
+
+fn archive_dir(app_data_path: &Path) -> PathBuf {
+    app_data_path.join(ARCHIVE_DIR)
+}
+
+fn archive_path(app_data_path: &Path, year: i32) -> PathBuf {
+    archive_dir(app_data_path).join(format!("{}{}.json", ARCHIVE_PREFIX, year))
+}
+
+/// The year a reminder is archived under: the year it was completed (UTC).
+/// None if it has no valid completion time.
+pub fn archive_year(reminder: &Reminder) -> Option<i32> {
+    let completed_at = reminder.completed_at.as_deref()?;
+    DateTime::parse_from_rfc3339(completed_at)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc).year())
+}
+
+/// Completed reminders finished before `cutoff`, grouped by archive year
+pub fn due_for_archive(
+    completed: &[Reminder],
+    cutoff: DateTime<Utc>,
+) -> BTreeMap<i32, Vec<Reminder>> {
+    let mut due: BTreeMap<i32, Vec<Reminder>> = BTreeMap::new();
+    for reminder in completed {
+        let completed_at = reminder
+            .completed_at
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+        if let (Some(completed_at), Some(year)) = (completed_at, archive_year(reminder)) {
+            if completed_at < cutoff {
+                due.entry(year).or_default().push(reminder.clone());
+            }
+        }
+    }
+    due
+}
+
+/// Add `reminders` to `archive`, replacing ones with the same id. Returns
+/// true if anything changed.
+pub fn merge_into(archive: &mut Vec<Reminder>, reminders: Vec<Reminder>) -> bool {
+    let mut changed = false;
+    for reminder in reminders {
+        match archive.iter_mut().find(|r| r.id == reminder.id) {
+            Some(existing) if *existing == reminder => {}
+            Some(existing) => {
+                *existing = reminder;
+                changed = true;
+            }
+            None => {
+                archive.push(reminder);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Whether `query` appears in a reminder's message, notes or tags
+/// (case-insensitive)
+pub fn matches(reminder: &Reminder, query: &str) -> bool {
+    let query = query.to_lowercase();
+    reminder.message.to_lowercase().contains(&query)
+        || reminder.notes.to_lowercase().contains(&query)
+        || reminder
+            .tags
+            .iter()
+            .any(|tag| tag.to_lowercase().contains(&query))
+}
+
+/// An archive year as stored
+pub fn format_archive(
+    year: i32,
+    reminders: &[Reminder],
+    cipher: &Cipher,
+) -> Result<String, String> {
+    let file = ArchiveFile {
+        schema_version: SCHEMA_VERSION,
+        year,
+        reminders: reminders.to_vec(),
+    };
+    let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    cipher.seal(content)
+}
+
+/// Reminders of a stored archive year. Empty content is an empty archive.
+pub fn parse_archive(content: String, cipher: &Cipher) -> Result<Vec<Reminder>, String> {
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let content = cipher.open(content)?;
+    let file: ArchiveFile = serde_json::from_str(&content)
+        .map_err(|e| AppError::storage(format!("Archive could not be read: {}", e)))?;
+    // Archives start at the current format; older ones will need migrating
+    // here once it changes
+    if file.schema_version > SCHEMA_VERSION {
+        return Err(AppError::storage(format!(
+            "Archive {} was {}. Please update the app.",
+            file.year, NEWER_VERSION_ERROR
+        ))
+        .into());
+    }
+    Ok(file.reminders)
+}
+
+/// Years with a local archive file, oldest first
+pub fn list_years(app_data_path: &Path) -> Vec<i32> {
+    let Ok(entries) = fs::read_dir(archive_dir(app_data_path)) else {
+        return Vec::new();
+    };
+    let mut years: Vec<i32> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_prefix(ARCHIVE_PREFIX)?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    years.sort_unstable();
+    years
+}
+
+/// The local archive of `year`, empty if there is none
+pub fn load_year(
+    app_data_path: &Path,
+    year: i32,
+    cipher: &Cipher,
+) -> Result<Vec<Reminder>, String> {
+    match fs::read_to_string(archive_path(app_data_path, year)) {
+        Ok(content) => parse_archive(content, cipher),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(AppError::storage(format!("Failed to read archive {}: {}", year, e)).into()),
+    }
+}
+
+/// Replace the local archive of `year`
+pub fn save_year(
+    app_data_path: &Path,
+    year: i32,
+    reminders: &[Reminder],
+    cipher: &Cipher,
+) -> Result<(), String> {
+    fs::create_dir_all(archive_dir(app_data_path)).map_err(|e| e.to_string())?;
+    let content = format_archive(year, reminders, cipher)?;
+    write_atomic(&archive_path(app_data_path, year), content.as_bytes())
+}
+
+/// Rewrite every local archive with `cipher`'s current key
+pub fn reseal_archives(app_data_path: &Path, cipher: &Cipher) -> Result<(), String> {
+    for year in list_years(app_data_path) {
+        let reminders = load_year(app_data_path, year, cipher)?;
+        save_year(app_data_path, year, &reminders, cipher)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+
+    fn completed(id: &str, completed_at: &str) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            is_completed: true,
+            completed_at: Some(completed_at.to_string()),
+            ..Reminder::new(format!("Task {}", id), Urgency::Today, ListType::Actual)
+        }
+    }
+
+    #[test]
+    fn test_due_reminders_are_grouped_by_year() {
+        let reminders = vec![
+            completed("1", "2023-12-31T23:00:00Z"),
+            completed("2", "2024-01-01T01:00:00+02:00"), // 2023 in UTC
+            completed("3", "2024-03-01T00:00:00Z"),
+            completed("4", "2024-06-01T00:00:00Z"),
+            Reminder {
+                completed_at: None,
+                ..completed("5", "")
+            },
+        ];
+        let cutoff = DateTime::parse_from_rfc3339("2024-05-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let due = due_for_archive(&reminders, cutoff);
+        let ids = |year: i32| -> Vec<&str> { due[&year].iter().map(|r| r.id.as_str()).collect() };
+        assert_eq!(due.len(), 2);
+        assert_eq!(ids(2023), vec!["1", "2"]);
+        assert_eq!(ids(2024), vec!["3"]);
+    }
+
+    #[test]
+    fn test_archive_year_roundtrip() {
+        let dir = std::env::temp_dir().join("test_archive_roundtrip");
+        let _ = fs::remove_dir_all(&dir);
+        let cipher = Cipher::default();
+        assert!(load_year(&dir, 2023, &cipher).unwrap().is_empty());
+
+        let first = completed("1", "2023-02-01T00:00:00Z");
+        let second = completed("2", "2023-03-01T00:00:00Z");
+        let mut archive = vec![first.clone()];
+        assert!(merge_into(&mut archive, vec![first, second.clone()]));
+        assert!(!merge_into(&mut archive, vec![second]));
+        assert_eq!(archive.len(), 2);
+        save_year(&dir, 2023, &archive, &cipher).unwrap();
+
+        assert_eq!(list_years(&dir), vec![2023]);
+        assert_eq!(load_year(&dir, 2023, &cipher).unwrap(), archive);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_matches_message_notes_and_tags() {
+        let mut reminder = completed("1", "2023-02-01T00:00:00Z");
+        reminder.message = "Call the plumber".to_string();
+        reminder.notes = "About the **kitchen** sink".to_string();
+        reminder.tags = vec!["house".to_string()];
+
+        assert!(matches(&reminder, "PLUMBER"));
+        assert!(matches(&reminder, "kitchen"));
+        assert!(matches(&reminder, "hous"));
+        assert!(!matches(&reminder, "garden"));
+    }
+}
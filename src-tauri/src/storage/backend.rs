@@ -0,0 +1,147 @@
+use crate::reminder::Reminder;
+use crate::storage::crypto::Cipher;
+use crate::storage::merge::ReminderStore;
+use crate::storage::oplog::Operation;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Opaque marker of one stored version of the data (file timestamp, Drive
+/// revision id, ...). Only compared for equality.
+pub type Revision = String;
+
+/// Data read from a backend together with the revision it was read at
+pub struct Snapshot {
+    pub data: ReminderStore,
+    pub revision: Option<Revision>,
+}
+
+/// One year of archived reminders together with the revision it was read at,
+/// None if the year has no archive yet
+pub struct ArchiveSnapshot {
+    pub reminders: Vec<Reminder>,
+    pub revision: Option<Revision>,
+}
+
+/// Result of a save that only goes through if nobody else saved in between
+#[derive(Debug, PartialEq)]
+pub enum ConditionalSave {
+    Saved(Option<Revision>),
+    Conflict,
+}
+
+/// How the last upload to the remote went
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UploadOutcome {
+    /// Conditional uploads tried. More than one means the remote changed in
+    /// the meantime and its changes were merged in before trying again.
+    pub attempts: u32,
+    pub saved: bool, // False if the remote kept changing and the upload was given up
+    pub at: String,  // ISO string
+}
+
+/// Where this device keeps its local data
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalBackendKind {
+    /// reminders.json
+    Json,
+    /// reminders.db
+    Sqlite,
+}
+
+/// The local backend in use. `error` is set when the chosen backend couldn't
+/// be opened and reminders.json is used instead.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LocalBackendStatus {
+    pub kind: LocalBackendKind,
+    pub error: Option<String>,
+}
+
+/// A place reminders are persisted to or synced with
+pub trait StorageBackend: Send {
+    /// Where the data lives, for logs and sync status
+    fn identity(&self) -> String;
+
+    /// Read the stored data. An empty store is returned if nothing has been
+    /// saved yet.
+    fn load(&mut self) -> Result<Snapshot, String>;
+
+    /// Overwrite the stored data, returning the new revision
+    fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String>;
+
+    /// Current revision of the stored data, None if nothing is stored
+    fn revision(&mut self) -> Result<Option<Revision>, String>;
+
+    /// Save only if the stored revision is still `expected`
+    fn save_if_unchanged(
+        &mut self,
+        data: &ReminderStore,
+        expected: Option<&str>,
+    ) -> Result<ConditionalSave, String> {
+        if self.revision()?.as_deref() != expected {
+            return Ok(ConditionalSave::Conflict);
+        }
+        self.save(data).map(ConditionalSave::Saved)
+    }
+
+    /// Completed reminders, most recently completed first. Backends with an
+    /// index for this return Some; otherwise callers use the loaded data.
+    fn query_completed(&self) -> Option<Result<Vec<Reminder>, String>> {
+        None
+    }
+
+    /// Completion times at or after `since` (all if None), for statistics.
+    /// Same fallback as `query_completed`.
+    fn query_completion_times(
+        &self,
+        _since: Option<DateTime<Utc>>,
+    ) -> Option<Result<Vec<DateTime<Utc>>, String>> {
+        None
+    }
+
+    /// The operation log kept next to the data, for exchanging operations
+    /// with other replicas. None if the backend keeps no log.
+    fn load_ops(&mut self) -> Option<Result<Vec<Operation>, String>> {
+        None
+    }
+
+    /// Add operations to the backend's log, ignoring ones it already has,
+    /// and drop the ones up to the `compacted` id, which every device has
+    /// read. None if the backend keeps no log.
+    fn push_ops(
+        &mut self,
+        _ops: &[Operation],
+        _compacted: Option<&str>,
+    ) -> Option<Result<(), String>> {
+        None
+    }
+
+    /// Years with archived reminders. None if the backend keeps no archive.
+    fn archive_years(&mut self) -> Option<Result<Vec<i32>, String>> {
+        None
+    }
+
+    /// Archived reminders completed in `year`, empty if there are none.
+    /// None if the backend keeps no archive.
+    fn load_archive(&mut self, _year: i32) -> Option<Result<ArchiveSnapshot, String>> {
+        None
+    }
+
+    /// Replace the archive of `year` only if its revision is still
+    /// `expected`; with None, only if the year has no archive yet. None if
+    /// the backend keeps no archive.
+    fn save_archive_if_unchanged(
+        &mut self,
+        _year: i32,
+        _reminders: &[Reminder],
+        _expected: Option<&str>,
+    ) -> Option<Result<ConditionalSave, String>> {
+        None
+    }
+
+    /// Encrypt what is written from now on with `cipher` and decrypt what is
+    /// read with it. Data already stored is rewritten by the next full save.
+    fn set_cipher(&mut self, _cipher: Cipher) -> Result<(), String> {
+        Err(format!("{} does not support encryption", self.identity()))
+    }
+}
@@ -0,0 +1,279 @@
+use crate::error::AppError;
+use crate::storage::crypto::Cipher;
+use crate::storage::merge::ReminderStore;
+use crate::storage::migrations;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "reminders-";
+const BACKUP_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// A backup file in the app data directory
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BackupInfo {
+    pub name: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+/// Replace `path` with `content` so that a crash leaves either the old or the
+/// new file, never a truncated one: write a temp file next to it, fsync it
+/// and rename it over the original.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid path: {}", path.display()))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let write_temp = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()
+    };
+    if let Err(e) = write_temp() {
+        let _ = fs::remove_file(&temp_path);
+        return Err(AppError::storage(format!("Failed to write {}: {}", path.display(), e)).into());
+    }
+
+    fs::rename(&temp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        AppError::storage(format!("Failed to replace {}: {}", path.display(), e)).to_string()
+    })?;
+
+    // Persist the rename itself; directories can't be opened for this on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+fn backup_dir(app_data_path: &Path) -> PathBuf {
+    app_data_path.join(BACKUP_DIR)
+}
+
+/// When a backup was taken, from its file name
+fn backup_time(name: &str) -> Option<DateTime<Utc>> {
+    let stamp = name.strip_prefix(BACKUP_PREFIX)?.strip_suffix(".json")?;
+    NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Backups in the app data directory, newest first
+pub fn list_backups(app_data_path: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = fs::read_dir(backup_dir(app_data_path)) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<(DateTime<Utc>, BackupInfo)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let created_at = backup_time(&name)?;
+            let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            Some((
+                created_at,
+                BackupInfo {
+                    name,
+                    created_at: created_at.to_rfc3339(),
+                    size_bytes,
+                },
+            ))
+        })
+        .collect();
+    backups.sort_by_key(|(created_at, _)| std::cmp::Reverse(*created_at));
+    backups.into_iter().map(|(_, info)| info).collect()
+}
+
+/// Write `data` to a new timestamped backup and delete all but the newest
+/// `keep` backups. Returns the new backup's name.
+pub fn create_backup(
+    app_data_path: &Path,
+    data: &ReminderStore,
+    cipher: &Cipher,
+    now: DateTime<Utc>,
+    keep: usize,
+) -> Result<String, String> {
+    let dir = backup_dir(app_data_path);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let name = format!("{}{}.json", BACKUP_PREFIX, now.format(BACKUP_TIME_FORMAT));
+    let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    let content = cipher.seal(content)?;
+    write_atomic(&dir.join(&name), content.as_bytes())?;
+
+    for old in list_backups(app_data_path).iter().skip(keep) {
+        if let Err(e) = fs::remove_file(dir.join(&old.name)) {
+            eprintln!("Failed to remove old backup {}: {}", old.name, e);
+        }
+    }
+    Ok(name)
+}
+
+/// Take a backup unless the newest one is less than `interval_minutes` old
+pub fn backup_if_due(
+    app_data_path: &Path,
+    data: &ReminderStore,
+    cipher: &Cipher,
+    now: DateTime<Utc>,
+    interval_minutes: i64,
+    keep: usize,
+) -> Result<Option<String>, String> {
+    let newest = list_backups(app_data_path)
+        .first()
+        .and_then(|b| backup_time(&b.name));
+    if newest.is_some_and(|t| now.signed_duration_since(t).num_minutes() < interval_minutes) {
+        return Ok(None);
+    }
+    create_backup(app_data_path, data, cipher, now, keep).map(Some)
+}
+
+/// Read a backup by the name `list_backups` reported
+pub fn load_backup(
+    app_data_path: &Path,
+    name: &str,
+    cipher: &Cipher,
+) -> Result<ReminderStore, String> {
+    // Only bare backup file names, so callers can't read other files
+    if backup_time(name).is_none() || name.contains(['/', '\\']) {
+        return Err(AppError::validation(format!("Unknown backup: {}", name)).into());
+    }
+
+    let path = backup_dir(app_data_path).join(name);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| AppError::storage(format!("Failed to read backup {}: {}", name, e)))?;
+    let content = cipher.open(content)?;
+    migrations::parse_store(&content)
+        .map(|migrated| migrated.store)
+        .map_err(|e| format!("Backup {} could not be read: {}", name, e))
+}
+
+/// The newest backup that can still be read, for recovering from a corrupt
+/// data file
+pub fn load_latest_backup(
+    app_data_path: &Path,
+    cipher: &Cipher,
+) -> Option<(String, ReminderStore)> {
+    list_backups(app_data_path).into_iter().find_map(|backup| {
+        match load_backup(app_data_path, &backup.name, cipher) {
+            Ok(data) => Some((backup.name, data)),
+            Err(e) => {
+                eprintln!("Skipping backup: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// Rewrite every backup with `cipher`'s current key
+pub fn reseal_backups(app_data_path: &Path, cipher: &Cipher) -> Result<(), String> {
+    let dir = backup_dir(app_data_path);
+    for backup in list_backups(app_data_path) {
+        let path = dir.join(&backup.name);
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let content = cipher.seal(cipher.open(content)?)?;
+        write_atomic(&path, content.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Reminder, Urgency};
+    use chrono::Duration;
+    use std::env;
+
+    fn store_with(message: &str) -> ReminderStore {
+        ReminderStore {
+            pending: vec![Reminder::new(
+                message.to_string(),
+                Urgency::Today,
+                ListType::Actual,
+            )],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let temp_dir = env::temp_dir().join("test_write_atomic");
+        let _ = fs::create_dir_all(&temp_dir);
+        let path = temp_dir.join("data.json");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert!(!temp_dir.join("data.json.tmp").exists());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let temp_dir = env::temp_dir().join("test_backups_rotate");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let now = Utc::now();
+
+        for i in 0..4 {
+            let data = store_with(&format!("Version {}", i));
+            create_backup(
+                &temp_dir,
+                &data,
+                &Cipher::default(),
+                now + Duration::minutes(i),
+                3,
+            )
+            .unwrap();
+        }
+
+        let backups = list_backups(&temp_dir);
+        assert_eq!(backups.len(), 3);
+        let newest = load_backup(&temp_dir, &backups[0].name, &Cipher::default()).unwrap();
+        assert_eq!(newest.pending[0].message, "Version 3");
+        let oldest = load_backup(&temp_dir, &backups[2].name, &Cipher::default()).unwrap();
+        assert_eq!(oldest.pending[0].message, "Version 1");
+
+        assert!(load_backup(&temp_dir, "../reminders.json", &Cipher::default()).is_err());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_backup_if_due_respects_interval() {
+        let temp_dir = env::temp_dir().join("test_backup_interval");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let now = Utc::now();
+        let data = store_with("Task");
+
+        assert!(
+            backup_if_due(&temp_dir, &data, &Cipher::default(), now, 60, 5)
+                .unwrap()
+                .is_some()
+        );
+        let soon = now + Duration::minutes(10);
+        assert!(
+            backup_if_due(&temp_dir, &data, &Cipher::default(), soon, 60, 5)
+                .unwrap()
+                .is_none()
+        );
+        let later = now + Duration::minutes(61);
+        assert!(
+            backup_if_due(&temp_dir, &data, &Cipher::default(), later, 60, 5)
+                .unwrap()
+                .is_some()
+        );
+        assert_eq!(list_backups(&temp_dir).len(), 2);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
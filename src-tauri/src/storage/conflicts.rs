@@ -0,0 +1,233 @@
+use crate::error::AppError;
+use crate::reminder::{field, Reminder, ReminderId};
+use crate::storage::merge::ReminderStore;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Fields reported as conflicts. Positions only place the one reminder and
+/// tags from both sides are kept, so neither loses a change worth showing.
+const REPORTED_FIELDS: [&str; 9] = [
+    field::MESSAGE,
+    field::URGENCY,
+    field::LIST_TYPE,
+    field::COMPLETION,
+    field::DUE_AT,
+    field::RECURRENCE,
+    field::HIDDEN_UNTIL,
+    field::NOTES,
+    field::CHECKLIST,
+];
+
+/// Which side's version of a field to keep
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictChoice {
+    Local,
+    Cloud,
+}
+
+/// A field both sides changed to different values, and the one a merge kept
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SyncConflict {
+    pub id: String,
+    pub reminder_id: ReminderId,
+    pub message: String, // The reminder's message after the merge
+    pub field: String,   // See `field`
+    pub local: Value,
+    pub cloud: Value,
+    pub resolution: ConflictChoice,
+    pub detected_at: String, // ISO string
+}
+
+impl SyncConflict {
+    /// The value of `choice`'s side
+    pub fn value(&self, choice: ConflictChoice) -> &Value {
+        match choice {
+            ConflictChoice::Local => &self.local,
+            ConflictChoice::Cloud => &self.cloud,
+        }
+    }
+}
+
+/// Fields of reminders in `merged` that `local` and `cloud` both changed
+/// since `base`. Without a base every differing field counts, as there is no
+/// telling which side changed it.
+pub fn find(
+    base: Option<&ReminderStore>,
+    local: &ReminderStore,
+    cloud: &ReminderStore,
+    merged: &ReminderStore,
+) -> Vec<SyncConflict> {
+    let by_id = |store: &ReminderStore| -> HashMap<String, Reminder> {
+        store
+            .pending
+            .iter()
+            .chain(store.completed.iter())
+            .map(|r| (r.id.clone(), r.clone()))
+            .collect()
+    };
+    let base_by_id = base.map(by_id).unwrap_or_default();
+    let local_by_id = by_id(local);
+    let cloud_by_id = by_id(cloud);
+    let detected_at = Utc::now().to_rfc3339();
+
+    let mut conflicts = Vec::new();
+    for kept in merged.pending.iter().chain(merged.completed.iter()) {
+        let (Some(local_version), Some(cloud_version)) =
+            (local_by_id.get(&kept.id), cloud_by_id.get(&kept.id))
+        else {
+            continue;
+        };
+        let base_version = base_by_id.get(&kept.id);
+        for name in REPORTED_FIELDS {
+            let local_value = field_value(local_version, name);
+            let cloud_value = field_value(cloud_version, name);
+            if local_value == cloud_value {
+                continue;
+            }
+            if let Some(base_version) = base_version {
+                let base_value = field_value(base_version, name);
+                if local_value == base_value || cloud_value == base_value {
+                    continue;
+                }
+            }
+            let resolution = if field_value(kept, name) == local_value {
+                ConflictChoice::Local
+            } else {
+                ConflictChoice::Cloud
+            };
+            conflicts.push(SyncConflict {
+                id: uuid::Uuid::now_v7().to_string(),
+                reminder_id: kept.id.clone(),
+                message: kept.message.clone(),
+                field: name.to_string(),
+                local: local_value,
+                cloud: cloud_value,
+                resolution,
+                detected_at: detected_at.clone(),
+            });
+        }
+    }
+    conflicts
+}
+
+/// A tracked field as JSON. Completion is `is_completed`, `completed_at`
+/// and `next_occurrence` together.
+pub fn field_value(reminder: &Reminder, name: &str) -> Value {
+    let object = to_object(reminder);
+    match name {
+        field::COMPLETION => json!({
+            "is_completed": object.get("is_completed"),
+            "completed_at": object.get("completed_at"),
+            "next_occurrence": object.get("next_occurrence"),
+        }),
+        _ => object.get(name).cloned().unwrap_or(Value::Null),
+    }
+}
+
+/// `reminder` with a tracked field set to a value from `field_value`
+pub fn with_field_value(
+    reminder: &Reminder,
+    name: &str,
+    value: &Value,
+) -> Result<Reminder, String> {
+    let mut object = to_object(reminder);
+    match (name, value) {
+        (field::COMPLETION, Value::Object(completion)) => {
+            for (key, value) in completion {
+                object.insert(key.clone(), value.clone());
+            }
+        }
+        _ => {
+            object.insert(name.to_string(), value.clone());
+        }
+    }
+    serde_json::from_value(Value::Object(object))
+        .map_err(|e| AppError::storage(format!("Invalid value for {}: {}", name, e)).into())
+}
+
+fn to_object(reminder: &Reminder) -> Map<String, Value> {
+    match serde_json::to_value(reminder) {
+        Ok(Value::Object(object)) => object,
+        _ => Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+    use crate::storage::merge::{merge_stores, merge_three_way};
+
+    fn store(reminder: Reminder) -> ReminderStore {
+        ReminderStore {
+            pending: vec![reminder],
+            ..Default::default()
+        }
+    }
+
+    fn stamped(reminder: &Reminder, name: &str, at: &str) -> Reminder {
+        let mut reminder = reminder.clone();
+        reminder.modified.insert(name.to_string(), at.to_string());
+        reminder.updated_at = Some(at.to_string());
+        reminder
+    }
+
+    #[test]
+    fn test_conflicts_report_fields_changed_on_both_sides() {
+        let original = Reminder::new("Call".to_string(), Urgency::Today, ListType::Actual);
+        let base = store(original.clone());
+
+        let mut local_version = stamped(&original, field::MESSAGE, "2024-01-02T00:00:00Z");
+        local_version.message = "Call mum".to_string();
+        local_version.urgency = Urgency::Now; // Only changed here
+        let mut cloud_version = stamped(&original, field::MESSAGE, "2024-01-03T00:00:00Z");
+        cloud_version.message = "Call dad".to_string();
+        let (local, cloud) = (store(local_version), store(cloud_version));
+
+        let merged = merge_three_way(&base, &local, &cloud);
+        let conflicts = find(Some(&base), &local, &cloud, &merged);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].field, field::MESSAGE);
+        assert_eq!(conflicts[0].local, json!("Call mum"));
+        assert_eq!(conflicts[0].cloud, json!("Call dad"));
+        assert_eq!(conflicts[0].resolution, ConflictChoice::Cloud);
+        assert_eq!(conflicts[0].message, "Call dad");
+
+        // Without a base, the one-sided change can't be told apart
+        let merged = merge_stores(&local, &cloud);
+        let fields: Vec<String> = find(None, &local, &cloud, &merged)
+            .into_iter()
+            .map(|c| c.field)
+            .collect();
+        assert_eq!(fields, vec![field::MESSAGE, field::URGENCY]);
+    }
+
+    #[test]
+    fn test_field_value_roundtrip() {
+        let mut reminder = Reminder::new("Call".to_string(), Urgency::Today, ListType::Actual);
+        let mut done = reminder.clone();
+        done.is_completed = true;
+        done.completed_at = Some("2024-01-02T00:00:00Z".to_string());
+        done.next_occurrence = Some("next".to_string());
+
+        let completion = field_value(&done, field::COMPLETION);
+        reminder = with_field_value(&reminder, field::COMPLETION, &completion).unwrap();
+        assert!(reminder.is_completed);
+        assert_eq!(reminder.completed_at, done.completed_at);
+        assert_eq!(reminder.next_occurrence, done.next_occurrence);
+
+        let list = field_value(
+            &Reminder {
+                list_type: ListType::Backlog,
+                ..done
+            },
+            field::LIST_TYPE,
+        );
+        reminder = with_field_value(&reminder, field::LIST_TYPE, &list).unwrap();
+        assert_eq!(reminder.list_type, ListType::Backlog);
+        assert!(with_field_value(&reminder, field::URGENCY, &json!(42)).is_err());
+    }
+}
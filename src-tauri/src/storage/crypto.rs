@@ -0,0 +1,395 @@
+use crate::error::AppError;
+use crate::storage::backup::write_atomic;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const CONFIG_FILE_NAME: &str = "encryption.json";
+const ENVELOPE_FORMAT: &str = "reminder-app-encrypted-v1";
+const KDF_ALGORITHM: &str = "argon2id";
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+// OWASP's recommended Argon2id parameters
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+// Most that stored parameters may ask for. A tampered or corrupt envelope
+// could otherwise make unlocking allocate gigabytes or run for hours.
+const KDF_MAX_MEMORY_KIB: u32 = KDF_MEMORY_KIB * 8;
+const KDF_MAX_ITERATIONS: u32 = KDF_ITERATIONS * 8;
+const KDF_MAX_PARALLELISM: u32 = KDF_PARALLELISM * 8;
+/// Plain text sealed with the key when encryption is enabled, to tell a wrong
+/// passphrase from damaged data
+const CHECK_TEXT: &str = "reminder-app";
+
+/// Part of the error for encrypted data read without a key
+pub const LOCKED_ERROR: &str = "encrypted and locked";
+
+/// Whether data is encrypted, and whether the passphrase is still needed
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+/// How a key was derived from the passphrase. Stored with everything
+/// encrypted, so any device that knows the passphrase can derive it again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub salt: String, // Base64
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+/// What encrypted data is stored as in place of the plain JSON
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    format: String,
+    kdf: KdfParams,
+    nonce: String,      // Base64
+    ciphertext: String, // Base64, XChaCha20-Poly1305 with the tag appended
+}
+
+/// Just enough of a document to tell an envelope from plain data
+#[derive(Deserialize)]
+struct FormatTag {
+    format: String,
+}
+
+/// A passphrase and the key derived from it. Keys for other salts, as used
+/// by other devices with the same passphrase, are derived when first needed.
+#[derive(Clone)]
+pub struct DataKey {
+    passphrase: Arc<str>,
+    kdf: KdfParams,
+    derived: Arc<Mutex<HashMap<String, [u8; KEY_LEN]>>>, // By salt
+}
+
+impl DataKey {
+    /// A key with a fresh random salt
+    pub fn generate(passphrase: &str) -> Result<Self, String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(
+            passphrase,
+            KdfParams {
+                algorithm: KDF_ALGORITHM.to_string(),
+                salt: BASE64.encode(salt),
+                memory_kib: KDF_MEMORY_KIB,
+                iterations: KDF_ITERATIONS,
+                parallelism: KDF_PARALLELISM,
+            },
+        )
+    }
+
+    /// The key `passphrase` gives with `kdf`
+    pub fn derive(passphrase: &str, kdf: KdfParams) -> Result<Self, String> {
+        let key = Self {
+            passphrase: Arc::from(passphrase),
+            kdf,
+            derived: Arc::default(),
+        };
+        key.key_for(&key.kdf)?;
+        Ok(key)
+    }
+
+    fn key_for(&self, kdf: &KdfParams) -> Result<[u8; KEY_LEN], String> {
+        let mut derived = self.derived.lock().map_err(|e| e.to_string())?;
+        if let Some(key) = derived.get(&kdf.salt) {
+            return Ok(*key);
+        }
+
+        if kdf.algorithm != KDF_ALGORITHM {
+            return Err(AppError::storage(format!(
+                "Unsupported key derivation: {}",
+                kdf.algorithm
+            ))
+            .into());
+        }
+        if kdf.memory_kib > KDF_MAX_MEMORY_KIB
+            || kdf.iterations > KDF_MAX_ITERATIONS
+            || kdf.parallelism > KDF_MAX_PARALLELISM
+        {
+            return Err(AppError::storage(format!(
+                "Key derivation parameters are too costly (memory {} KiB, {} iterations, parallelism {})",
+                kdf.memory_kib, kdf.iterations, kdf.parallelism
+            ))
+            .into());
+        }
+        let salt = BASE64.decode(&kdf.salt).map_err(|e| e.to_string())?;
+        let params = Params::new(
+            kdf.memory_kib,
+            kdf.iterations,
+            kdf.parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(self.passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| AppError::storage(format!("Key derivation failed: {}", e)))?;
+
+        derived.insert(kdf.salt.clone(), key);
+        Ok(key)
+    }
+
+    fn seal(&self, plaintext: &str) -> Result<String, String> {
+        let key = self.key_for(&self.kdf)?;
+        let aead = XChaCha20Poly1305::new(&key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = aead
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| AppError::storage("Encryption failed"))?;
+
+        let envelope = Envelope {
+            format: ENVELOPE_FORMAT.to_string(),
+            kdf: self.kdf.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        serde_json::to_string(&envelope).map_err(|e| e.to_string())
+    }
+
+    /// None if the envelope wasn't sealed with this passphrase
+    fn open(&self, envelope: &Envelope) -> Result<Option<String>, String> {
+        let key = self.key_for(&envelope.kdf)?;
+        let nonce = BASE64.decode(&envelope.nonce).map_err(|e| e.to_string())?;
+        if nonce.len() != 24 {
+            return Err(AppError::storage("Encrypted data is damaged").into());
+        }
+        let ciphertext = BASE64
+            .decode(&envelope.ciphertext)
+            .map_err(|e| e.to_string())?;
+
+        let aead = XChaCha20Poly1305::new(&key.into());
+        match aead.decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice()) {
+            Ok(plaintext) => String::from_utf8(plaintext)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Encrypts what is written to storage and decrypts what is read. Without a
+/// key everything is written as plain text. Keys replaced during this session
+/// are still used for reading, so data not yet rewritten stays readable.
+/// With a key, plain content is refused, so nobody can slip unauthenticated
+/// data in, except while plain data is being encrypted (`accepting_plain`).
+#[derive(Clone, Default)]
+pub struct Cipher {
+    key: Option<DataKey>,
+    previous: Vec<DataKey>,
+    accept_plain: bool,
+}
+
+impl Cipher {
+    pub fn new(key: Option<DataKey>) -> Self {
+        Self {
+            key,
+            previous: Vec::new(),
+            accept_plain: false,
+        }
+    }
+
+    /// This cipher, reading plain content even with a key if `accept` is set.
+    /// Only for encrypting data that was stored as plain text.
+    pub fn accepting_plain(&self, accept: bool) -> Self {
+        Self {
+            accept_plain: accept,
+            ..self.clone()
+        }
+    }
+
+    /// Whether data is written encrypted
+    pub fn is_enabled(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Write with `key` from now on, still reading with this cipher's keys
+    pub fn replaced_by(&self, key: Option<DataKey>) -> Self {
+        Self {
+            key,
+            previous: self.key.iter().chain(&self.previous).cloned().collect(),
+            accept_plain: false,
+        }
+    }
+
+    /// `plaintext` as it should be stored
+    pub fn seal(&self, plaintext: String) -> Result<String, String> {
+        match &self.key {
+            Some(key) => key.seal(&plaintext),
+            None => Ok(plaintext),
+        }
+    }
+
+    /// Stored `content` as plain text. Plain content is returned unchanged
+    /// unless data is written encrypted.
+    pub fn open(&self, content: String) -> Result<String, String> {
+        if !is_sealed(&content) {
+            if self.key.is_some() && !self.accept_plain {
+                return Err(AppError::storage(
+                    "Found unencrypted data where encrypted data was expected; it was not read",
+                )
+                .into());
+            }
+            return Ok(content);
+        }
+        let envelope: Envelope = serde_json::from_str(&content)
+            .map_err(|e| AppError::storage(format!("Encrypted data is damaged: {}", e)))?;
+
+        let mut keys = self.key.iter().chain(&self.previous).peekable();
+        if keys.peek().is_none() {
+            return Err(AppError::storage(format!(
+                "Data is {}; enter the passphrase to unlock it",
+                LOCKED_ERROR
+            ))
+            .into());
+        }
+        for key in keys {
+            if let Some(plaintext) = key.open(&envelope)? {
+                return Ok(plaintext);
+            }
+        }
+        Err(
+            AppError::storage("Data could not be decrypted: wrong passphrase or damaged data")
+                .into(),
+        )
+    }
+}
+
+/// Whether stored content is encrypted
+pub fn is_sealed(content: &str) -> bool {
+    serde_json::from_str::<FormatTag>(content).is_ok_and(|tag| tag.format == ENVELOPE_FORMAT)
+}
+
+/// encryption.json in the app data directory. Its presence means data is
+/// encrypted; it holds what's needed to check a passphrase, never the key.
+#[derive(Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    kdf: KdfParams,
+    check: String, // CHECK_TEXT sealed with the key
+    /// Plain data is still being encrypted; set until enabling finishes
+    #[serde(default)]
+    pub migrating: bool,
+}
+
+impl EncryptionConfig {
+    pub fn new(key: &DataKey, migrating: bool) -> Result<Self, String> {
+        Ok(Self {
+            kdf: key.kdf.clone(),
+            check: key.seal(CHECK_TEXT)?,
+            migrating,
+        })
+    }
+
+    /// The key for `passphrase`, if it is the right one
+    pub fn unlock(&self, passphrase: &str) -> Result<DataKey, String> {
+        let key = DataKey::derive(passphrase, self.kdf.clone())?;
+        let check: Envelope = serde_json::from_str(&self.check).map_err(|e| e.to_string())?;
+        match key.open(&check)? {
+            Some(text) if text == CHECK_TEXT => Ok(key),
+            _ => Err(AppError::validation("Wrong passphrase").into()),
+        }
+    }
+}
+
+pub fn load_config(app_data_path: &Path) -> Result<Option<EncryptionConfig>, String> {
+    let path = app_data_path.join(CONFIG_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).map(Some).map_err(|e| {
+            AppError::storage(format!("{} is damaged: {}", CONFIG_FILE_NAME, e)).into()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub fn save_config(app_data_path: &Path, config: &EncryptionConfig) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    write_atomic(&app_data_path.join(CONFIG_FILE_NAME), content.as_bytes())
+}
+
+pub fn remove_config(app_data_path: &Path) -> Result<(), String> {
+    match fs::remove_file(app_data_path.join(CONFIG_FILE_NAME)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_roundtrip() {
+        let cipher = Cipher::new(Some(DataKey::generate("correct horse").unwrap()));
+        let sealed = cipher.seal("Call the client".to_string()).unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("client"));
+        assert_eq!(cipher.open(sealed).unwrap(), "Call the client");
+
+        // Plain text only passes through without a key, or while encrypting
+        // data that was plain
+        assert!(cipher.open("{}".to_string()).is_err());
+        assert_eq!(
+            cipher.accepting_plain(true).open("{}".to_string()).unwrap(),
+            "{}"
+        );
+        assert_eq!(Cipher::default().open("{}".to_string()).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_same_passphrase_opens_data_from_another_salt() {
+        let other_device = Cipher::new(Some(DataKey::generate("shared secret").unwrap()));
+        let sealed = other_device.seal("Task".to_string()).unwrap();
+
+        let this_device = Cipher::new(Some(DataKey::generate("shared secret").unwrap()));
+        assert_eq!(this_device.open(sealed.clone()).unwrap(), "Task");
+
+        let wrong = Cipher::new(Some(DataKey::generate("something else").unwrap()));
+        assert!(wrong.open(sealed.clone()).is_err());
+        let locked = Cipher::default().open(sealed).err().unwrap();
+        assert!(locked.contains(LOCKED_ERROR));
+    }
+
+    #[test]
+    fn test_costly_kdf_params_are_refused() {
+        let key = DataKey::generate("correct horse").unwrap();
+        let mut kdf = key.kdf.clone();
+        kdf.memory_kib = KDF_MAX_MEMORY_KIB + 1;
+        assert!(DataKey::derive("correct horse", kdf).is_err());
+
+        let mut kdf = key.kdf.clone();
+        kdf.iterations = u32::MAX;
+        assert!(DataKey::derive("correct horse", kdf).is_err());
+    }
+
+    #[test]
+    fn test_replaced_key_still_reads_old_data() {
+        let old = Cipher::new(Some(DataKey::generate("old passphrase").unwrap()));
+        let sealed = old.seal("Task".to_string()).unwrap();
+
+        let disabled = old.replaced_by(None);
+        assert_eq!(disabled.seal("Task".to_string()).unwrap(), "Task");
+        assert_eq!(disabled.open(sealed).unwrap(), "Task");
+    }
+
+    #[test]
+    fn test_config_checks_passphrase() {
+        let key = DataKey::generate("correct horse").unwrap();
+        let config = EncryptionConfig::new(&key, false).unwrap();
+        assert!(config.unlock("correct horse").is_ok());
+        assert!(config.unlock("wrong horse").is_err());
+    }
+}
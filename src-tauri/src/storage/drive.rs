@@ -1,24 +1,379 @@
-use crate::storage::legacy::try_migrate_legacy_data;
+use crate::config::MAX_UPLOAD_ATTEMPTS;
+use crate::reminder::Reminder;
+use crate::storage::archive;
+use crate::storage::backend::{
+    ArchiveSnapshot, ConditionalSave, Revision, Snapshot, StorageBackend,
+};
+use crate::storage::crypto::Cipher;
 use crate::storage::merge::ReminderStore;
+use crate::storage::migrations;
+use crate::storage::oauth;
+use crate::storage::oplog::{self, Operation};
 use crate::urlencoding;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-/// Find or create reminders.json file in Google Drive
+const DATA_FILE_NAME: &str = "reminders.json";
+const OPS_FILE_NAME: &str = "reminders-ops.jsonl";
+const ARCHIVE_FILE_PREFIX: &str = "reminders-archive-";
+
+/// reminders.json in a Google Drive folder, with the shared operation log and
+/// the yearly archives next to it. Refreshes the access token when it expires.
+pub struct DriveBackend {
+    app_data_path: PathBuf,
+    access_token: String,
+    refresh_token: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    folder_id: String,
+    file_id: String,
+    ops_file_id: Option<String>, // Found or created on first use
+    archive_file_ids: Option<HashMap<i32, String>>, // By year, listed on first use
+    cipher: Cipher,
+    reseal_ops: bool, // The operation log must be uploaded under a new key
+}
+
+impl DriveBackend {
+    /// Connect with the saved OAuth state, finding or creating reminders.json
+    /// (seeded with `initial_data`) in the configured folder
+    pub fn connect(
+        app_data_path: &PathBuf,
+        initial_data: &ReminderStore,
+        cipher: &Cipher,
+    ) -> Result<Self, String> {
+        let oauth_state = oauth::load_oauth_state(app_data_path)?;
+        let mut backend = Self {
+            app_data_path: app_data_path.clone(),
+            access_token: oauth_state.access_token,
+            refresh_token: oauth_state.refresh_token,
+            client_id: oauth_state.client_id,
+            client_secret: oauth_state.client_secret,
+            folder_id: oauth_state.folder_id,
+            file_id: String::new(),
+            ops_file_id: None,
+            archive_file_ids: None,
+            cipher: cipher.clone(),
+            reseal_ops: false,
+        };
+
+        let folder_id = backend.folder_id.clone();
+        let content = serde_json::to_string(initial_data).map_err(|e| e.to_string())?;
+        let content = cipher.seal(content)?;
+        backend.file_id = backend.with_token_refresh(|token, _| {
+            find_or_create_drive_file(
+                token,
+                &folder_id,
+                DATA_FILE_NAME,
+                "application/json",
+                &content,
+            )
+        })?;
+        Ok(backend)
+    }
+
+    fn ops_file_id(&mut self) -> Result<String, String> {
+        if let Some(id) = &self.ops_file_id {
+            return Ok(id.clone());
+        }
+        let folder_id = self.folder_id.clone();
+        let id = self.with_token_refresh(|token, _| {
+            find_or_create_drive_file(token, &folder_id, OPS_FILE_NAME, "application/x-ndjson", "")
+        })?;
+        self.ops_file_id = Some(id.clone());
+        Ok(id)
+    }
+
+    fn read_ops(&mut self) -> Result<Vec<Operation>, String> {
+        let ops_file_id = self.ops_file_id()?;
+        let content =
+            self.with_token_refresh(|token, _| download_drive_file(token, &ops_file_id))?;
+        Ok(oplog::parse_ops(&content, &self.cipher))
+    }
+
+    /// The operation log with the etag it was read at. The etag is read
+    /// first, so a write slipping in before the download fails the upload.
+    fn read_ops_with_etag(&mut self) -> Result<(Vec<Operation>, String), String> {
+        let ops_file_id = self.ops_file_id()?;
+        let (_, etag) =
+            self.with_token_refresh(|token, _| get_file_version(token, &ops_file_id))?;
+        let etag = etag.ok_or("Drive didn't report the operation log's etag")?;
+        Ok((self.read_ops()?, etag))
+    }
+
+    /// Ids of the archive files in the folder, by year
+    fn archive_file_ids(&mut self) -> Result<HashMap<i32, String>, String> {
+        if let Some(ids) = &self.archive_file_ids {
+            return Ok(ids.clone());
+        }
+        let folder_id = self.folder_id.clone();
+        let files = self.with_token_refresh(|token, _| {
+            find_drive_files(token, &folder_id, ARCHIVE_FILE_PREFIX)
+        })?;
+        let ids: HashMap<i32, String> = files
+            .into_iter()
+            .filter_map(|(name, id)| {
+                let year = name
+                    .strip_prefix(ARCHIVE_FILE_PREFIX)?
+                    .strip_suffix(".json")?
+                    .parse()
+                    .ok()?;
+                Some((year, id))
+            })
+            .collect();
+        self.archive_file_ids = Some(ids.clone());
+        Ok(ids)
+    }
+
+    /// The archive of `year` with the etag it was read at. The etag is read
+    /// first, as for the operation log.
+    fn read_archive(&mut self, year: i32) -> Result<ArchiveSnapshot, String> {
+        let Some(file_id) = self.archive_file_ids()?.remove(&year) else {
+            return Ok(ArchiveSnapshot {
+                reminders: Vec::new(),
+                revision: None,
+            });
+        };
+        let (_, etag) = self.with_token_refresh(|token, _| get_file_version(token, &file_id))?;
+        let etag = etag.ok_or("Drive didn't report the archive's etag")?;
+        let content = self.with_token_refresh(|token, _| download_drive_file(token, &file_id))?;
+        Ok(ArchiveSnapshot {
+            reminders: archive::parse_archive(content, &self.cipher)?,
+            revision: Some(etag),
+        })
+    }
+
+    /// Upload the archive of `year` if its etag is still `expected`. Without
+    /// an etag the year had no file when it was read; the folder is listed
+    /// again before creating one, so a file another device created since is
+    /// merged into rather than duplicated.
+    fn write_archive_if_unchanged(
+        &mut self,
+        year: i32,
+        reminders: &[Reminder],
+        expected: Option<&str>,
+    ) -> Result<ConditionalSave, String> {
+        let content = archive::format_archive(year, reminders, &self.cipher)?;
+        let Some(etag) = expected else {
+            self.archive_file_ids = None;
+            if self.archive_file_ids()?.contains_key(&year) {
+                return Ok(ConditionalSave::Conflict);
+            }
+            let folder_id = self.folder_id.clone();
+            let name = format!("{}{}.json", ARCHIVE_FILE_PREFIX, year);
+            let file_id = self.with_token_refresh(|token, _| {
+                create_drive_file(token, &folder_id, &name, "application/json", &content)
+            })?;
+            if let Some(ids) = self.archive_file_ids.as_mut() {
+                ids.insert(year, file_id);
+            }
+            return Ok(ConditionalSave::Saved(None));
+        };
+        // Removed since it was read: read (and create) it again
+        let Some(file_id) = self.archive_file_ids()?.remove(&year) else {
+            return Ok(ConditionalSave::Conflict);
+        };
+        let upload = self.with_token_refresh(|token, _| {
+            upload_drive_file_if_match(token, &file_id, "application/json", &content, etag)
+        })?;
+        // Archives are compared by etag, which the upload doesn't report
+        Ok(match upload {
+            ConditionalSave::Saved(_) => ConditionalSave::Saved(None),
+            ConditionalSave::Conflict => ConditionalSave::Conflict,
+        })
+    }
+
+    fn refresh_access_token(&mut self) -> Result<(), String> {
+        let refresh_token = self.refresh_token.as_ref().ok_or("No refresh token")?;
+        let client_id = self.client_id.as_ref().ok_or("No client ID")?;
+        let client_secret = self.client_secret.as_ref().ok_or("No client secret")?;
+
+        self.access_token = oauth::refresh_access_token(
+            &self.app_data_path,
+            refresh_token,
+            client_id,
+            client_secret,
+        )?;
+        Ok(())
+    }
+
+    /// Run a Drive request with (access token, file id), refreshing the token
+    /// and retrying once if it has expired
+    fn with_token_refresh<T>(
+        &mut self,
+        request: impl Fn(&str, &str) -> Result<T, String>,
+    ) -> Result<T, String> {
+        match request(&self.access_token, &self.file_id) {
+            Err(e) if e.contains("expired") => {
+                eprintln!("Drive request failed: {}, trying token refresh...", e);
+                self.refresh_access_token()?;
+                request(&self.access_token, &self.file_id)
+            }
+            result => result,
+        }
+    }
+
+    /// Drive can't append to a file, so the log is read, extended and
+    /// uploaded whole, on condition that nobody uploaded it in between. If
+    /// somebody did, their log is read again and extended instead, up to
+    /// `MAX_UPLOAD_ATTEMPTS` uploads in all. Operations up to `compacted`
+    /// are dropped on the way, so the file stays small.
+    fn append_ops(&mut self, ops: &[Operation], compacted: Option<&str>) -> Result<(), String> {
+        let ops_file_id = self.ops_file_id()?;
+        for _ in 0..MAX_UPLOAD_ATTEMPTS {
+            let (mut all, etag) = self.read_ops_with_etag()?;
+            let before = all.len();
+            if let Some(point) = compacted {
+                all.retain(|op| op.id.as_str() > point);
+            }
+            let mut changed = all.len() < before;
+            for op in ops {
+                let known = all.iter().any(|known| known.id == op.id);
+                let compacted = compacted.is_some_and(|point| op.id.as_str() <= point);
+                if !known && !compacted {
+                    all.push(op.clone());
+                    changed = true;
+                }
+            }
+            if !changed && !self.reseal_ops {
+                return Ok(());
+            }
+
+            let content = oplog::format_ops(&all, &self.cipher)?;
+            let upload = self.with_token_refresh(|token, _| {
+                upload_drive_file_if_match(
+                    token,
+                    &ops_file_id,
+                    "application/x-ndjson",
+                    &content,
+                    &etag,
+                )
+            })?;
+            if let ConditionalSave::Saved(_) = upload {
+                self.reseal_ops = false;
+                return Ok(());
+            }
+            eprintln!("Operation log changed on Drive during upload, reading it again");
+        }
+        Err(format!(
+            "Operation log kept changing on Drive, gave up after {} attempts",
+            MAX_UPLOAD_ATTEMPTS
+        ))
+    }
+}
+
+impl StorageBackend for DriveBackend {
+    fn identity(&self) -> String {
+        format!("drive:{}", self.file_id)
+    }
+
+    fn load(&mut self) -> Result<Snapshot, String> {
+        // Read the revision first: a change slipping in between makes the
+        // next conditional save conflict rather than overwrite it
+        let revision = self.revision()?;
+        let cipher = self.cipher.clone();
+        let data =
+            self.with_token_refresh(|token, file_id| load_from_drive(token, file_id, &cipher))?;
+        Ok(Snapshot { data, revision })
+    }
+
+    fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String> {
+        let cipher = self.cipher.clone();
+        self.with_token_refresh(|token, file_id| save_to_drive(token, file_id, data, &cipher))
+    }
+
+    fn revision(&mut self) -> Result<Option<Revision>, String> {
+        self.with_token_refresh(get_head_revision)
+    }
+
+    /// Revisions are headRevisionIds. The upload itself is conditional on
+    /// the etag read together with the head revision, so a write landing
+    /// between the check and the upload makes Drive refuse it rather than be
+    /// overwritten. The etag also changes with metadata, which only costs an
+    /// extra merge. The new revision comes back with the upload itself, so a
+    /// write right after ours can't be mistaken for it.
+    fn save_if_unchanged(
+        &mut self,
+        data: &ReminderStore,
+        expected: Option<&str>,
+    ) -> Result<ConditionalSave, String> {
+        let (head, etag) = self.with_token_refresh(get_file_version)?;
+        if head.as_deref() != expected {
+            return Ok(ConditionalSave::Conflict);
+        }
+        let etag = etag.ok_or("Drive didn't report the file's etag")?;
+        let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+        let content = self.cipher.seal(content)?;
+        let upload = self.with_token_refresh(|token, file_id| {
+            upload_drive_file_if_match(token, file_id, "application/json", &content, &etag)
+        })?;
+        match upload {
+            ConditionalSave::Saved(uploaded) => {
+                checked_upload(head.as_deref(), uploaded).map(ConditionalSave::Saved)
+            }
+            ConditionalSave::Conflict => Ok(ConditionalSave::Conflict),
+        }
+    }
+
+    fn load_ops(&mut self) -> Option<Result<Vec<Operation>, String>> {
+        Some(self.read_ops())
+    }
+
+    fn push_ops(
+        &mut self,
+        ops: &[Operation],
+        compacted: Option<&str>,
+    ) -> Option<Result<(), String>> {
+        Some(self.append_ops(ops, compacted))
+    }
+
+    fn archive_years(&mut self) -> Option<Result<Vec<i32>, String>> {
+        Some(self.archive_file_ids().map(|ids| {
+            let mut years: Vec<i32> = ids.into_keys().collect();
+            years.sort_unstable();
+            years
+        }))
+    }
+
+    fn load_archive(&mut self, year: i32) -> Option<Result<ArchiveSnapshot, String>> {
+        Some(self.read_archive(year))
+    }
+
+    fn save_archive_if_unchanged(
+        &mut self,
+        year: i32,
+        reminders: &[Reminder],
+        expected: Option<&str>,
+    ) -> Option<Result<ConditionalSave, String>> {
+        Some(self.write_archive_if_unchanged(year, reminders, expected))
+    }
+
+    fn set_cipher(&mut self, cipher: Cipher) -> Result<(), String> {
+        self.cipher = cipher;
+        self.reseal_ops = true;
+        Ok(())
+    }
+}
+
+/// Find a file by name in a Google Drive folder, creating it with
+/// `initial_content` if it doesn't exist
 pub fn find_or_create_drive_file(
     access_token: &str,
     folder_id: &str,
-    initial_data: &ReminderStore,
+    name: &str,
+    mime_type: &str,
+    initial_content: &str,
 ) -> Result<String, String> {
     // Search for existing file in the specific folder
     let query = format!(
-        "name='reminders.json' and '{}' in parents and trashed=false",
-        folder_id
+        "name='{}' and '{}' in parents and trashed=false",
+        name, folder_id
     );
     let url = format!(
         "https://www.googleapis.com/drive/v3/files?q={}&fields=files(id)",
         urlencoding::encode(&query)
     );
 
-    eprintln!("Searching for reminders.json in folder {}...", folder_id);
+    eprintln!("Searching for {} in folder {}...", name, folder_id);
 
     let response = ureq::get(&url)
         .set("Authorization", &format!("Bearer {}", access_token))
@@ -42,38 +397,82 @@ pub fn find_or_create_drive_file(
     }
 
     // Create new file if not found
-    create_drive_file(access_token, folder_id, initial_data)
+    create_drive_file(access_token, folder_id, name, mime_type, initial_content)
+}
+
+/// (name, id) of the files in a Google Drive folder whose name starts with
+/// `prefix`
+fn find_drive_files(
+    access_token: &str,
+    folder_id: &str,
+    prefix: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let query = format!(
+        "name contains '{}' and '{}' in parents and trashed=false",
+        prefix, folder_id
+    );
+    let url = format!(
+        "https://www.googleapis.com/drive/v3/files?q={}&fields=files(id,name)&pageSize=1000",
+        urlencoding::encode(&query)
+    );
+
+    let response = ureq::get(&url)
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .call();
+
+    let response = match response {
+        Ok(r) => r,
+        Err(ureq::Error::Status(401, _)) => return Err("Token expired".to_string()),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("Drive API error: {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let json: serde_json::Value = response.into_json().map_err(|e| e.to_string())?;
+    let Some(files) = json["files"].as_array() else {
+        return Ok(Vec::new());
+    };
+    Ok(files
+        .iter()
+        .filter_map(|file| {
+            let name = file["name"].as_str()?;
+            let id = file["id"].as_str()?;
+            // "contains" matches word prefixes anywhere in the name
+            name.starts_with(prefix)
+                .then(|| (name.to_string(), id.to_string()))
+        })
+        .collect())
 }
 
-/// Create a new reminders.json file in Google Drive
+/// Create a new file in a Google Drive folder
 fn create_drive_file(
     access_token: &str,
     folder_id: &str,
-    data: &ReminderStore,
+    name: &str,
+    mime_type: &str,
+    content: &str,
 ) -> Result<String, String> {
     let metadata = serde_json::json!({
-        "name": "reminders.json",
+        "name": name,
         "parents": [folder_id],
-        "mimeType": "application/json"
+        "mimeType": mime_type
     });
 
-    let content = serde_json::to_string(data).map_err(|e| e.to_string())?;
-
     // Use multipart upload
     let boundary = "reminder_app_boundary";
     let body = format!(
-        "--{}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{}\r\n--{}\r\nContent-Type: application/json\r\n\r\n{}\r\n--{}--",
-        boundary, metadata, boundary, content, boundary
+        "--{}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{}\r\n--{}\r\nContent-Type: {}\r\n\r\n{}\r\n--{}--",
+        boundary, metadata, boundary, mime_type, content, boundary
     );
 
-    let response =
-        ureq::post("https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart&fields=id")
-            .set("Authorization", &format!("Bearer {}", access_token))
-            .set(
-                "Content-Type",
-                &format!("multipart/related; boundary={}", boundary),
-            )
-            .send_string(&body);
+    let response = ureq::post(
+        "https://www.googleapis.com/upload/drive/v3/files?uploadType=multipart&fields=id",
+    )
+    .set("Authorization", &format!("Bearer {}", access_token))
+    .set(
+        "Content-Type",
+        &format!("multipart/related; boundary={}", boundary),
+    )
+    .send_string(&body);
 
     let response = match response {
         Ok(r) => r,
@@ -89,8 +488,8 @@ fn create_drive_file(
         .ok_or_else(|| "No file ID in response".to_string())
 }
 
-/// Load reminders from Google Drive
-pub fn load_from_drive(access_token: &str, file_id: &str) -> Result<ReminderStore, String> {
+/// Contents of a Google Drive file
+fn download_drive_file(access_token: &str, file_id: &str) -> Result<String, String> {
     let url = format!(
         "https://www.googleapis.com/drive/v3/files/{}?alt=media",
         file_id
@@ -107,51 +506,197 @@ pub fn load_from_drive(access_token: &str, file_id: &str) -> Result<ReminderStor
         Err(e) => return Err(e.to_string()),
     };
 
-    let content = response.into_string().map_err(|e| e.to_string())?;
+    response.into_string().map_err(|e| e.to_string())
+}
+
+/// Load reminders from Google Drive
+pub fn load_from_drive(
+    access_token: &str,
+    file_id: &str,
+    cipher: &Cipher,
+) -> Result<ReminderStore, String> {
+    let content = download_drive_file(access_token, file_id)?;
     eprintln!("Drive content received: {} bytes", content.len());
+    let content = cipher.open(content)?;
 
-    // Try to parse as new format first
-    if let Ok(data) = serde_json::from_str::<ReminderStore>(&content) {
+    // Older formats are upgraded here; the next save uploads the new format.
+    // Unreadable or newer data is an error so it never gets overwritten.
+    let migrated = migrations::parse_store(&content)?;
+    if migrated.was_migrated() {
         eprintln!(
-            "Parsed {} pending, {} completed reminders from Drive",
-            data.pending.len(),
-            data.completed.len()
+            "Migrated Drive data from schema version {}",
+            migrated.from_version
         );
-        return Ok(data);
     }
+    let data = migrated.store;
+    eprintln!(
+        "Parsed {} pending, {} completed reminders from Drive",
+        data.pending.len(),
+        data.completed.len()
+    );
+    Ok(data)
+}
 
-    // Try migration from legacy format
-    if let Some(migrated) = try_migrate_legacy_data(&content, None) {
-        eprintln!("Migrated legacy data from Drive");
-        return Ok(migrated);
-    }
+/// Current head revision id of a Drive file
+pub fn get_head_revision(access_token: &str, file_id: &str) -> Result<Option<String>, String> {
+    let url = format!(
+        "https://www.googleapis.com/drive/v3/files/{}?fields=headRevisionId",
+        file_id
+    );
+
+    let response = ureq::get(&url)
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .call();
+
+    let response = match response {
+        Ok(r) => r,
+        Err(ureq::Error::Status(401, _)) => return Err("Token expired".to_string()),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("Drive API error: {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
 
-    eprintln!("Failed to parse Drive content, using empty");
-    Ok(ReminderStore::default())
+    let json: serde_json::Value = response.into_json().map_err(|e| e.to_string())?;
+    Ok(json["headRevisionId"].as_str().map(String::from))
 }
 
-/// Save reminders to Google Drive
-pub fn save_to_drive(
+/// (headRevisionId, etag) of a Drive file. Only the v2 API reports the etag
+/// that uploads can be made conditional on.
+fn get_file_version(
     access_token: &str,
     file_id: &str,
-    data: &ReminderStore,
-) -> Result<(), String> {
+) -> Result<(Option<String>, Option<String>), String> {
     let url = format!(
-        "https://www.googleapis.com/upload/drive/v3/files/{}?uploadType=media",
+        "https://www.googleapis.com/drive/v2/files/{}?fields=headRevisionId,etag",
         file_id
     );
 
+    let response = ureq::get(&url)
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .call();
+
+    let response = match response {
+        Ok(r) => r,
+        Err(ureq::Error::Status(401, _)) => return Err("Token expired".to_string()),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("Drive API error: {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let json: serde_json::Value = response.into_json().map_err(|e| e.to_string())?;
+    Ok((
+        json["headRevisionId"].as_str().map(String::from),
+        json["etag"].as_str().map(String::from),
+    ))
+}
+
+/// Save reminders to Google Drive, returning the new head revision id
+pub fn save_to_drive(
+    access_token: &str,
+    file_id: &str,
+    data: &ReminderStore,
+    cipher: &Cipher,
+) -> Result<Option<String>, String> {
     let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    let content = cipher.seal(content)?;
+    upload_drive_file(access_token, file_id, "application/json", &content)
+}
+
+/// The revision created by an upload made once the head revision was found to
+/// be `checked`, as the upload response reports it. Later uploads are checked
+/// against it, so a response without a new revision is an error rather than
+/// a revision that would never match.
+fn checked_upload(
+    checked: Option<&str>,
+    uploaded: Option<String>,
+) -> Result<Option<Revision>, String> {
+    match uploaded {
+        Some(revision) if Some(revision.as_str()) != checked => Ok(Some(revision)),
+        Some(revision) => Err(format!(
+            "Drive upload didn't create a new revision (still {})",
+            revision
+        )),
+        None => Err("Drive upload didn't report the new revision".to_string()),
+    }
+}
+
+/// Replace the contents of a Google Drive file, returning the new head
+/// revision id
+fn upload_drive_file(
+    access_token: &str,
+    file_id: &str,
+    content_type: &str,
+    content: &str,
+) -> Result<Option<String>, String> {
+    let url = format!(
+        "https://www.googleapis.com/upload/drive/v3/files/{}?uploadType=media&fields=headRevisionId",
+        file_id
+    );
 
     let response = ureq::request("PATCH", &url)
         .set("Authorization", &format!("Bearer {}", access_token))
-        .set("Content-Type", "application/json")
-        .send_string(&content);
-
-    match response {
-        Ok(_) => Ok(()),
-        Err(ureq::Error::Status(401, _)) => Err("Token expired".to_string()),
-        Err(ureq::Error::Status(code, _)) => Err(format!("Drive API error: {}", code)),
-        Err(e) => Err(e.to_string()),
+        .set("Content-Type", content_type)
+        .send_string(content);
+
+    let response = match response {
+        Ok(r) => r,
+        Err(ureq::Error::Status(401, _)) => return Err("Token expired".to_string()),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("Drive API error: {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let json: serde_json::Value = response.into_json().map_err(|e| e.to_string())?;
+    Ok(json["headRevisionId"].as_str().map(String::from))
+}
+
+/// Replace the contents of a Google Drive file if its etag is still `etag`.
+/// Drive v2 honours If-Match on uploads and answers 412 if the file changed.
+fn upload_drive_file_if_match(
+    access_token: &str,
+    file_id: &str,
+    content_type: &str,
+    content: &str,
+    etag: &str,
+) -> Result<ConditionalSave, String> {
+    let url = format!(
+        "https://www.googleapis.com/upload/drive/v2/files/{}?uploadType=media&fields=headRevisionId",
+        file_id
+    );
+
+    let response = ureq::request("PUT", &url)
+        .set("Authorization", &format!("Bearer {}", access_token))
+        .set("Content-Type", content_type)
+        .set("If-Match", etag)
+        .send_string(content);
+
+    let response = match response {
+        Ok(r) => r,
+        Err(ureq::Error::Status(401, _)) => return Err("Token expired".to_string()),
+        Err(ureq::Error::Status(412, _)) => return Ok(ConditionalSave::Conflict),
+        Err(ureq::Error::Status(code, _)) => return Err(format!("Drive API error: {}", code)),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let json: serde_json::Value = response.into_json().map_err(|e| e.to_string())?;
+    Ok(ConditionalSave::Saved(
+        json["headRevisionId"].as_str().map(String::from),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_upload_needs_a_new_revision() {
+        assert_eq!(
+            checked_upload(Some("r1"), Some("r2".to_string())),
+            Ok(Some("r2".to_string()))
+        );
+        assert_eq!(
+            checked_upload(None, Some("r1".to_string())),
+            Ok(Some("r1".to_string()))
+        );
+        // Saving the reported revision would make every later upload conflict
+        assert!(checked_upload(Some("r1"), Some("r1".to_string())).is_err());
+        assert!(checked_upload(Some("r1"), None).is_err());
     }
 }
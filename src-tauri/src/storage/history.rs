@@ -0,0 +1,180 @@
+use crate::reminder::{Reminder, ReminderId};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// One reminder's state before and after a change. None means it didn't
+/// exist (added or deleted).
+#[derive(Debug, Clone)]
+pub struct ReminderChange {
+    pub id: ReminderId,
+    pub before: Option<Reminder>,
+    pub after: Option<Reminder>,
+}
+
+/// An undoable action: every reminder it touched, including reminders bumped
+/// or promoted as a side effect
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub label: String,
+    pub at: String,
+    pub changes: Vec<ReminderChange>,
+}
+
+/// What the frontend is shown of a history entry
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HistoryItem {
+    pub label: String,
+    pub at: String,
+    pub reminders: usize, // How many reminders the action changed
+}
+
+/// Undo and redo stacks, most recent first
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HistoryListing {
+    pub undo: Vec<HistoryItem>,
+    pub redo: Vec<HistoryItem>,
+}
+
+impl HistoryEntry {
+    fn item(&self) -> HistoryItem {
+        HistoryItem {
+            label: self.label.clone(),
+            at: self.at.clone(),
+            reminders: self.changes.len(),
+        }
+    }
+}
+
+/// Bounded undo/redo history. Kept in memory only.
+pub struct History {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Record a new action. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, entry: HistoryEntry) {
+        self.redo.clear();
+        self.push_undo(entry);
+    }
+
+    fn push_undo(&mut self, entry: HistoryEntry) {
+        self.undo.push_back(entry);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<HistoryEntry> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<HistoryEntry> {
+        self.redo.pop()
+    }
+
+    /// Move an entry that was just undone onto the redo stack
+    pub fn undone(&mut self, entry: HistoryEntry) {
+        self.redo.push(entry);
+    }
+
+    /// Move an entry that was just redone back onto the undo stack
+    pub fn redone(&mut self, entry: HistoryEntry) {
+        self.push_undo(entry);
+    }
+
+    pub fn listing(&self) -> HistoryListing {
+        HistoryListing {
+            undo: self.undo.iter().rev().map(HistoryEntry::item).collect(),
+            redo: self.redo.iter().rev().map(HistoryEntry::item).collect(),
+        }
+    }
+}
+
+/// The reminders that differ between two snapshots of all reminders
+pub fn diff(before: &[Reminder], after: &[Reminder]) -> Vec<ReminderChange> {
+    let before_by_id: HashMap<&str, &Reminder> =
+        before.iter().map(|r| (r.id.as_str(), r)).collect();
+    let after_by_id: HashMap<&str, &Reminder> = after.iter().map(|r| (r.id.as_str(), r)).collect();
+
+    let mut changes: Vec<ReminderChange> = before
+        .iter()
+        .filter(|r| after_by_id.get(r.id.as_str()) != Some(r))
+        .map(|r| ReminderChange {
+            id: r.id.clone(),
+            before: Some(r.clone()),
+            after: after_by_id.get(r.id.as_str()).map(|a| (*a).clone()),
+        })
+        .collect();
+    changes.extend(
+        after
+            .iter()
+            .filter(|r| !before_by_id.contains_key(r.id.as_str()))
+            .map(|r| ReminderChange {
+                id: r.id.clone(),
+                before: None,
+                after: Some(r.clone()),
+            }),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+
+    fn entry(label: &str) -> HistoryEntry {
+        HistoryEntry {
+            label: label.to_string(),
+            at: String::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_finds_added_changed_and_removed() {
+        let kept = Reminder::new("Kept".to_string(), Urgency::Today, ListType::Actual);
+        let removed = Reminder::new("Removed".to_string(), Urgency::Today, ListType::Actual);
+        let mut changed = Reminder::new("Old".to_string(), Urgency::Today, ListType::Actual);
+        let before = vec![kept.clone(), removed.clone(), changed.clone()];
+
+        changed.message = "New".to_string();
+        let added = Reminder::new("Added".to_string(), Urgency::Today, ListType::Backlog);
+        let after = vec![kept, changed.clone(), added.clone()];
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+        let find = |id: &str| changes.iter().find(|c| c.id == id).unwrap();
+        assert!(find(&removed.id).after.is_none());
+        assert_eq!(find(&changed.id).after.as_ref().unwrap().message, "New");
+        assert!(find(&added.id).before.is_none());
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_new_actions_clear_redo() {
+        let mut history = History::new(2);
+        history.record(entry("first"));
+        history.record(entry("second"));
+        history.record(entry("third"));
+        assert_eq!(history.listing().undo.len(), 2);
+
+        let undone = history.pop_undo().unwrap();
+        assert_eq!(undone.label, "third");
+        history.undone(undone);
+        assert_eq!(history.listing().redo[0].label, "third");
+
+        history.record(entry("fourth"));
+        assert!(history.listing().redo.is_empty());
+        assert_eq!(history.listing().undo[0].label, "fourth");
+    }
+}
@@ -0,0 +1,345 @@
+use crate::reminder::{ListType, Reminder, ReminderId};
+use crate::sort_key;
+use crate::storage::merge::{merge_reminder, ReminderStore};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Something wrong with a store's structure, as found by `verify`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// The same id more than once in the pending or the completed list
+    DuplicateId { id: ReminderId, copies: usize },
+    /// The same id in both the pending and the completed list
+    InBothLists { id: ReminderId },
+    /// `is_completed` disagrees with the list the reminder is in
+    CompletionMismatch { id: ReminderId, is_completed: bool },
+    /// Several pending reminders of a list share a sort order
+    DuplicateSortOrder {
+        list: ListType,
+        sort_order: String,
+        ids: Vec<ReminderId>,
+    },
+    /// A pending reminder's sort order isn't a valid sort key
+    InvalidSortOrder {
+        list: ListType,
+        id: ReminderId,
+        sort_order: String,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::DuplicateId { id, copies } => {
+                write!(f, "reminder {} is stored {} times", id, copies)
+            }
+            Finding::InBothLists { id } => {
+                write!(f, "reminder {} is both pending and completed", id)
+            }
+            Finding::CompletionMismatch { id, is_completed } => write!(
+                f,
+                "reminder {} is marked {} but filed as {}",
+                id,
+                if *is_completed {
+                    "completed"
+                } else {
+                    "pending"
+                },
+                if *is_completed {
+                    "pending"
+                } else {
+                    "completed"
+                },
+            ),
+            Finding::DuplicateSortOrder {
+                list,
+                sort_order,
+                ids,
+            } => write!(
+                f,
+                "list {} has {} reminders at position {}",
+                list.as_str(),
+                ids.len(),
+                sort_order
+            ),
+            Finding::InvalidSortOrder {
+                list,
+                id,
+                sort_order,
+            } => write!(
+                f,
+                "reminder {} in list {} has invalid position {:?}",
+                id,
+                list.as_str(),
+                sort_order
+            ),
+        }
+    }
+}
+
+/// Everything wrong with `store`, in a stable order: duplicates first, then
+/// completion, then ordering per list
+pub fn verify(store: &ReminderStore) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let mut copies: BTreeMap<&str, (usize, usize)> = BTreeMap::new(); // (pending, completed)
+    for r in &store.pending {
+        copies.entry(r.id.as_str()).or_default().0 += 1;
+    }
+    for r in &store.completed {
+        copies.entry(r.id.as_str()).or_default().1 += 1;
+    }
+    for (id, (pending, completed)) in &copies {
+        if *pending > 1 || *completed > 1 {
+            findings.push(Finding::DuplicateId {
+                id: id.to_string(),
+                copies: pending + completed,
+            });
+        }
+        if *pending > 0 && *completed > 0 {
+            findings.push(Finding::InBothLists { id: id.to_string() });
+        }
+    }
+
+    for (list, is_completed) in [(&store.pending, false), (&store.completed, true)] {
+        for r in list.iter().filter(|r| r.is_completed != is_completed) {
+            findings.push(Finding::CompletionMismatch {
+                id: r.id.clone(),
+                is_completed: r.is_completed,
+            });
+        }
+    }
+
+    for (list, reminders) in pending_by_list(&store.pending) {
+        let mut by_order: BTreeMap<&str, Vec<ReminderId>> = BTreeMap::new();
+        for r in reminders {
+            if !sort_key::is_valid(&r.sort_order) {
+                findings.push(Finding::InvalidSortOrder {
+                    list: list.clone(),
+                    id: r.id.clone(),
+                    sort_order: r.sort_order.clone(),
+                });
+                continue;
+            }
+            by_order
+                .entry(&r.sort_order)
+                .or_default()
+                .push(r.id.clone());
+        }
+        for (sort_order, ids) in by_order {
+            if ids.len() > 1 {
+                findings.push(Finding::DuplicateSortOrder {
+                    list: list.clone(),
+                    sort_order: sort_order.to_string(),
+                    ids,
+                });
+            }
+        }
+    }
+
+    findings
+}
+
+/// Fix everything `verify` finds, the same way on every device:
+/// - copies of a reminder are merged into one, field by field
+/// - reminders are filed by their `is_completed` flag
+/// - reminders sharing a sort key, or without a valid one, get new keys
+///   next to where they were, ties broken by id; invalid keys go last
+///
+/// Fields aren't stamped as changed, since every device makes the same
+/// repair to the same data. Returns what was wrong.
+pub fn repair(store: &mut ReminderStore) -> Vec<Finding> {
+    let findings = verify(store);
+    if findings.is_empty() {
+        return findings;
+    }
+
+    let mut order: Vec<ReminderId> = Vec::new();
+    let mut merged: HashMap<ReminderId, Reminder> = HashMap::new();
+    for r in store.pending.drain(..).chain(store.completed.drain(..)) {
+        match merged.get_mut(&r.id) {
+            Some(existing) => *existing = merge_reminder(existing, &r),
+            None => {
+                order.push(r.id.clone());
+                merged.insert(r.id.clone(), r);
+            }
+        }
+    }
+    for id in order {
+        if let Some(r) = merged.remove(&id) {
+            if r.is_completed {
+                store.completed.push(r);
+            } else {
+                store.pending.push(r);
+            }
+        }
+    }
+
+    let new_keys: Vec<(ReminderId, String)> = pending_by_list(&store.pending)
+        .into_iter()
+        .flat_map(|(_, mut reminders)| {
+            reminders.sort_by(|a, b| {
+                let (valid_a, valid_b) = (
+                    sort_key::is_valid(&a.sort_order),
+                    sort_key::is_valid(&b.sort_order),
+                );
+                valid_b.cmp(&valid_a).then_with(|| a.cmp_position(b))
+            });
+            let keys: Vec<&str> = reminders.iter().map(|r| r.sort_order.as_str()).collect();
+            sort_key::reorder(&keys)
+                .into_iter()
+                .zip(&reminders)
+                .filter_map(|(key, r)| key.map(|key| (r.id.clone(), key)))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    for (id, key) in new_keys {
+        if let Some(r) = store.pending.iter_mut().find(|r| r.id == id) {
+            r.sort_order = key;
+        }
+    }
+
+    findings
+}
+
+/// Pending reminders grouped by list, lists in id order
+fn pending_by_list(pending: &[Reminder]) -> Vec<(ListType, Vec<&Reminder>)> {
+    let mut lists: BTreeMap<&str, (ListType, Vec<&Reminder>)> = BTreeMap::new();
+    for r in pending {
+        lists
+            .entry(r.list_type.as_str())
+            .or_insert_with(|| (r.list_type.clone(), Vec::new()))
+            .1
+            .push(r);
+    }
+    lists.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::Urgency;
+
+    fn make_reminder(id: &str, list_type: ListType, sort_order: i64) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            sort_order: sort_key::from_integer(sort_order),
+            ..Reminder::new(format!("Task {}", id), Urgency::Today, list_type)
+        }
+    }
+
+    #[test]
+    fn test_healthy_store_has_no_findings() {
+        let store = ReminderStore {
+            pending: vec![
+                make_reminder("a", ListType::Actual, 0),
+                make_reminder("b", ListType::Actual, 1),
+                make_reminder("c", ListType::Backlog, -3),
+                make_reminder("d", ListType::Backlog, -2),
+            ],
+            ..Default::default()
+        };
+        assert!(verify(&store).is_empty());
+    }
+
+    #[test]
+    fn test_verify_reports_each_anomaly() {
+        let mut done = make_reminder("b", ListType::Actual, 0);
+        done.is_completed = true;
+        let store = ReminderStore {
+            pending: vec![
+                make_reminder("a", ListType::Actual, 0),
+                make_reminder("a", ListType::Actual, 0),
+                make_reminder("b", ListType::Actual, 3),
+                Reminder {
+                    sort_order: "5".to_string(),
+                    ..make_reminder("c", ListType::Actual, 4)
+                },
+            ],
+            completed: vec![done, make_reminder("d", ListType::Actual, 0)],
+            ..Default::default()
+        };
+
+        let findings = verify(&store);
+        assert_eq!(
+            findings,
+            vec![
+                Finding::DuplicateId {
+                    id: "a".to_string(),
+                    copies: 2
+                },
+                Finding::InBothLists {
+                    id: "b".to_string()
+                },
+                Finding::CompletionMismatch {
+                    id: "d".to_string(),
+                    is_completed: false
+                },
+                Finding::InvalidSortOrder {
+                    list: ListType::Actual,
+                    id: "c".to_string(),
+                    sort_order: "5".to_string()
+                },
+                Finding::DuplicateSortOrder {
+                    list: ListType::Actual,
+                    sort_order: "a0".to_string(),
+                    ids: vec!["a".to_string(), "a".to_string()]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_fixes_every_anomaly() {
+        let mut done = make_reminder("b", ListType::Actual, 0);
+        done.is_completed = true;
+        done.completed_at = Some("2024-01-02T00:00:00Z".to_string());
+        let mut store = ReminderStore {
+            pending: vec![
+                make_reminder("c", ListType::Actual, 5),
+                make_reminder("a", ListType::Actual, 2),
+                make_reminder("a", ListType::Actual, 2),
+                make_reminder("b", ListType::Actual, 2),
+                Reminder {
+                    sort_order: String::new(),
+                    ..make_reminder("e", ListType::Actual, 0)
+                },
+                make_reminder("f", ListType::Actual, 1),
+            ],
+            completed: vec![done, make_reminder("d", ListType::Backlog, 0)],
+            ..Default::default()
+        };
+
+        let findings = repair(&mut store);
+        assert_eq!(findings.len(), 5);
+        assert!(verify(&store).is_empty());
+
+        // Completion wins over the stale pending copy; "d" was never completed
+        let completed: Vec<&str> = store.completed.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(completed, vec!["b"]);
+        let mut actual: Vec<&Reminder> = store
+            .pending
+            .iter()
+            .filter(|r| r.list_type == ListType::Actual)
+            .collect();
+        actual.sort_by(|a, b| a.cmp_position(b));
+        let ids: Vec<&str> = actual.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["f", "a", "c", "e"]);
+        // Only the clashing and the invalid keys were replaced
+        let key = |id: &str| {
+            actual
+                .iter()
+                .find(|r| r.id == id)
+                .unwrap()
+                .sort_order
+                .clone()
+        };
+        assert_eq!(key("f"), sort_key::from_integer(1));
+        assert_eq!(key("c"), sort_key::from_integer(5));
+
+        // A repaired store is left alone
+        assert!(repair(&mut store).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::clock::{self, is_newer};
+use crate::config::MAX_ACTUAL_TASKS;
+use crate::reminder::ListType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Names of the list fields whose changes are tracked for merging
+pub mod list_field {
+    pub const NAME: &str = "name";
+    pub const SORT_ORDER: &str = "sort_order";
+    pub const CAPACITY: &str = "capacity";
+    pub const OVERFLOW_LIST: &str = "overflow_list";
+    pub const SOURCE_LIST: &str = "source_list";
+
+    pub const ALL: [&str; 5] = [NAME, SORT_ORDER, CAPACITY, OVERFLOW_LIST, SOURCE_LIST];
+}
+
+/// A list reminders can be filed in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ListDefinition {
+    pub id: ListType,
+    pub name: String,
+    pub sort_order: i64, // Position among lists
+    #[serde(default)]
+    pub capacity: Option<usize>, // Most pending reminders the list holds; None = unlimited
+    #[serde(default)]
+    pub overflow_list: Option<ListType>, // Where the least important reminder goes when full
+    #[serde(default)]
+    pub source_list: Option<ListType>, // Where reminders are promoted from when there is room
+    #[serde(default)]
+    pub built_in: bool, // Actual and Backlog can't be deleted
+    #[serde(default)]
+    pub modified: HashMap<String, String>, // field name -> clock stamp of last change
+}
+
+impl ListDefinition {
+    pub fn new(id: ListType, name: String, sort_order: i64) -> Self {
+        Self {
+            id,
+            name,
+            sort_order,
+            capacity: None,
+            overflow_list: None,
+            source_list: None,
+            built_in: false,
+            modified: HashMap::new(),
+        }
+    }
+
+    pub fn is_full(&self, count: usize) -> bool {
+        self.capacity.is_some_and(|capacity| count >= capacity)
+    }
+
+    /// Record that `fields` (see `list_field`) changed now
+    pub fn touch(&mut self, fields: &[&str]) {
+        let stamp = clock::now().to_string();
+        for field in fields {
+            self.modified.insert(field.to_string(), stamp.clone());
+        }
+    }
+
+    /// Clock stamp of the last change to one field; empty if it never changed
+    /// since tracking began, e.g. for the built-in lists
+    pub fn field_modified(&self, field: &str) -> &str {
+        self.modified
+            .get(field)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Clock stamp of the last change to any field
+    pub fn last_modified(&self) -> &str {
+        self.modified
+            .values()
+            .map(String::as_str)
+            .fold("", |latest, stamp| {
+                if is_newer(latest, stamp) {
+                    stamp
+                } else {
+                    latest
+                }
+            })
+    }
+}
+
+/// The built-in lists: a short Actual list that overflows into, and is
+/// refilled from, an unlimited Backlog
+pub fn default_lists() -> Vec<ListDefinition> {
+    vec![
+        ListDefinition {
+            capacity: Some(MAX_ACTUAL_TASKS),
+            overflow_list: Some(ListType::Backlog),
+            source_list: Some(ListType::Backlog),
+            built_in: true,
+            ..ListDefinition::new(ListType::Actual, "Actual".to_string(), 0)
+        },
+        ListDefinition {
+            built_in: true,
+            ..ListDefinition::new(ListType::Backlog, "Backlog".to_string(), 1)
+        },
+    ]
+}
+
+/// Union of two list sets by id. Lists both sides have are merged field by
+/// field, each field taken from whichever side changed it last; ties go to
+/// `a`. Deletions are applied by the caller.
+pub fn merge_lists(a: &[ListDefinition], b: &[ListDefinition]) -> Vec<ListDefinition> {
+    let mut lists = a.to_vec();
+    for list in b {
+        match lists.iter_mut().find(|l| l.id == list.id) {
+            Some(existing) => *existing = merge_list(existing, list),
+            None => lists.push(list.clone()),
+        }
+    }
+    lists.sort_by_key(|l| l.sort_order);
+    lists
+}
+
+fn merge_list(a: &ListDefinition, b: &ListDefinition) -> ListDefinition {
+    let pick = |field: &str| {
+        if is_newer(a.field_modified(field), b.field_modified(field)) {
+            b
+        } else {
+            a
+        }
+    };
+    let mut merged = a.clone();
+    merged.name = pick(list_field::NAME).name.clone();
+    merged.sort_order = pick(list_field::SORT_ORDER).sort_order;
+    merged.capacity = pick(list_field::CAPACITY).capacity;
+    merged.overflow_list = pick(list_field::OVERFLOW_LIST).overflow_list.clone();
+    merged.source_list = pick(list_field::SOURCE_LIST).source_list.clone();
+
+    for (name, stamp) in &b.modified {
+        if is_newer(a.field_modified(name), stamp) {
+            merged.modified.insert(name.clone(), stamp.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_lists_are_built_in() {
+        let lists = default_lists();
+        assert_eq!(lists.len(), 2);
+        assert!(lists.iter().all(|l| l.built_in));
+        assert!(lists[0].is_full(MAX_ACTUAL_TASKS));
+        assert!(!lists[1].is_full(usize::MAX));
+    }
+
+    #[test]
+    fn test_merge_lists_takes_each_field_from_its_last_change() {
+        let waiting = ListDefinition::new(
+            ListType::Custom("waiting".to_string()),
+            "Waiting".to_string(),
+            2,
+        );
+        let mut local = default_lists();
+        local.push(waiting.clone());
+        local[2].capacity = Some(3);
+        local[2].modified.insert(
+            list_field::CAPACITY.to_string(),
+            "2024-01-02T00:00:00Z/0/device-a".to_string(),
+        );
+        let mut cloud = default_lists();
+        cloud.push(waiting);
+        cloud[2].name = "On hold".to_string();
+        cloud[2].modified.insert(
+            list_field::NAME.to_string(),
+            "2024-01-03T00:00:00Z/0/device-b".to_string(),
+        );
+        cloud[2].capacity = Some(5);
+        cloud[2].modified.insert(
+            list_field::CAPACITY.to_string(),
+            "2024-01-01T00:00:00Z/0/device-b".to_string(),
+        );
+
+        for merged in [merge_lists(&local, &cloud), merge_lists(&cloud, &local)] {
+            assert_eq!(merged.len(), 3);
+            assert_eq!(merged[2].name, "On hold");
+            assert_eq!(merged[2].capacity, Some(3));
+            assert_eq!(merged[2].last_modified(), "2024-01-03T00:00:00Z/0/device-b");
+        }
+    }
+}
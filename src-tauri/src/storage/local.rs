@@ -1,40 +1,163 @@
-use crate::storage::legacy::try_migrate_legacy_data;
+use crate::error::AppError;
+use crate::storage::backend::{LocalBackendKind, Revision, Snapshot, StorageBackend};
+use crate::storage::backup::write_atomic;
+use crate::storage::crypto::Cipher;
 use crate::storage::merge::ReminderStore;
+use crate::storage::migrations::{self, NEWER_VERSION_ERROR};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// reminders.json in the app data directory
+pub struct LocalJsonBackend {
+    app_data_path: PathBuf,
+    cipher: Cipher,
+}
+
+impl LocalJsonBackend {
+    pub fn new(app_data_path: PathBuf) -> Self {
+        Self {
+            app_data_path,
+            cipher: Cipher::default(),
+        }
+    }
+}
+
+impl StorageBackend for LocalJsonBackend {
+    fn identity(&self) -> String {
+        format!(
+            "local:{}",
+            self.app_data_path.join("reminders.json").display()
+        )
+    }
+
+    fn load(&mut self) -> Result<Snapshot, String> {
+        let data = load_local(&self.app_data_path, &self.cipher)?;
+        Ok(Snapshot {
+            data,
+            revision: self.revision()?,
+        })
+    }
+
+    fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String> {
+        save_local(&self.app_data_path, data, &self.cipher)?;
+        self.revision()
+    }
+
+    /// The file's modification time in nanoseconds
+    fn revision(&mut self) -> Result<Option<Revision>, String> {
+        let path = self.app_data_path.join("reminders.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|e| e.to_string())?;
+        let nanos = modified
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_nanos();
+        Ok(Some(nanos.to_string()))
+    }
+
+    fn set_cipher(&mut self, cipher: Cipher) -> Result<(), String> {
+        self.cipher = cipher;
+        Ok(())
+    }
+}
 
 /// Load reminders from local JSON file
-pub fn load_local(app_data_path: &PathBuf) -> Result<ReminderStore, String> {
+pub fn load_local(app_data_path: &Path, cipher: &Cipher) -> Result<ReminderStore, String> {
     let path = app_data_path.join("reminders.json");
 
     if !path.exists() {
         return Ok(ReminderStore::default());
     }
 
-    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let stored = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    // Data that can't be decrypted yet is left where it is
+    let content = cipher.open(stored.clone())?;
+
+    let error = match migrations::parse_store(&content) {
+        Ok(migrated) => {
+            if migrated.was_migrated() {
+                // Keep the original next to the upgraded file
+                let backup_path =
+                    app_data_path.join(format!("reminders_backup_v{}.json", migrated.from_version));
+                if let Err(e) = fs::write(&backup_path, &stored) {
+                    eprintln!("Warning: Failed to back up data before migration: {}", e);
+                }
+                eprintln!(
+                    "Migrated reminders.json from schema version {}",
+                    migrated.from_version
+                );
+                save_local(app_data_path, &migrated.store, cipher)?;
+            }
+            return Ok(migrated.store);
+        }
+        Err(e) if e.contains(NEWER_VERSION_ERROR) => return Err(e),
+        Err(e) => e,
+    };
+
+    // Move the unreadable file aside so the next save can't overwrite it
+    let corrupt_path = app_data_path.join(format!(
+        "reminders.corrupt-{}.json",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    fs::rename(&path, &corrupt_path).map_err(|e| e.to_string())?;
+    Err(AppError::storage(format!(
+        "reminders.json could not be read ({}) and was moved to {}",
+        error,
+        corrupt_path.display()
+    ))
+    .into())
+}
+
+/// Read this installation's device id, creating one on first run
+pub fn load_or_create_device_id(app_data_path: &Path) -> Result<String, String> {
+    let path = app_data_path.join("device_id");
 
-    // Try to parse as new format first
-    if let Ok(data) = serde_json::from_str::<ReminderStore>(&content) {
-        return Ok(data);
+    if let Ok(content) = fs::read_to_string(&path) {
+        let id = content.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
     }
 
-    // Try migration from legacy format
-    let backup_path = app_data_path.join("reminders_backup_v1.json");
-    if let Some(migrated) = try_migrate_legacy_data(&content, Some(&backup_path)) {
-        // Save migrated data immediately
-        save_local(app_data_path, &migrated)?;
-        return Ok(migrated);
+    let id = uuid::Uuid::now_v7().to_string();
+    fs::write(&path, &id).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// The local backend chosen on this device. Installs that already have a
+/// reminders.db from before the choice was recorded keep using it.
+pub fn load_backend_kind(app_data_path: &Path) -> LocalBackendKind {
+    let chosen = fs::read_to_string(app_data_path.join("local_backend"))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    match chosen {
+        Some(kind) => kind,
+        None if app_data_path.join("reminders.db").exists() => LocalBackendKind::Sqlite,
+        None => LocalBackendKind::Json,
     }
+}
 
-    Ok(ReminderStore::default())
+/// Record the local backend to open on the next start
+pub fn save_backend_kind(app_data_path: &Path, kind: LocalBackendKind) -> Result<(), String> {
+    let content = serde_json::to_string(&kind).map_err(|e| e.to_string())?;
+    write_atomic(&app_data_path.join("local_backend"), content.as_bytes())
 }
 
 /// Save reminders to local JSON file
-pub fn save_local(app_data_path: &PathBuf, data: &ReminderStore) -> Result<(), String> {
+pub fn save_local(
+    app_data_path: &Path,
+    data: &ReminderStore,
+    cipher: &Cipher,
+) -> Result<(), String> {
     let path = app_data_path.join("reminders.json");
     let content = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
-    fs::write(&path, content).map_err(|e| e.to_string())?;
-    Ok(())
+    let content = cipher.seal(content)?;
+    write_atomic(&path, content.as_bytes())
 }
 
 #[cfg(test)]
@@ -47,7 +170,7 @@ mod tests {
         let temp_dir = env::temp_dir().join("test_load_nonexistent");
         let _ = fs::create_dir_all(&temp_dir);
 
-        let result = load_local(&temp_dir);
+        let result = load_local(&temp_dir, &Cipher::default());
         assert!(result.is_ok());
         let store = result.unwrap();
         assert!(store.pending.is_empty());
@@ -59,31 +182,149 @@ mod tests {
     #[test]
     fn test_save_and_load_roundtrip() {
         use crate::reminder::{ListType, Reminder, Urgency};
-        use chrono::Utc;
 
         let temp_dir = env::temp_dir().join("test_roundtrip");
         let _ = fs::create_dir_all(&temp_dir);
 
         let store = ReminderStore {
-            pending: vec![Reminder {
-                id: 1,
-                message: "Test".to_string(),
-                urgency: Urgency::Today,
-                list_type: ListType::Actual,
-                created_at: Utc::now().to_rfc3339(),
-                is_completed: false,
-                completed_at: None,
-                sort_order: 0,
-            }],
+            pending: vec![Reminder::new(
+                "Test".to_string(),
+                Urgency::Today,
+                ListType::Actual,
+            )],
             completed: vec![],
+            ..Default::default()
         };
 
-        save_local(&temp_dir, &store).unwrap();
-        let loaded = load_local(&temp_dir).unwrap();
+        save_local(&temp_dir, &store, &Cipher::default()).unwrap();
+        let loaded = load_local(&temp_dir, &Cipher::default()).unwrap();
 
         assert_eq!(loaded.pending.len(), 1);
         assert_eq!(loaded.pending[0].message, "Test");
 
         let _ = fs::remove_dir_all(&temp_dir);
     }
+
+    #[test]
+    fn test_encrypted_file_is_unreadable_without_key() {
+        use crate::reminder::{ListType, Reminder, Urgency};
+        use crate::storage::crypto::{DataKey, LOCKED_ERROR};
+
+        let temp_dir = env::temp_dir().join("test_encrypted_local");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let _ = fs::create_dir_all(&temp_dir);
+        let store = ReminderStore {
+            pending: vec![Reminder::new(
+                "Call Acme Corp".to_string(),
+                Urgency::Today,
+                ListType::Actual,
+            )],
+            ..Default::default()
+        };
+        let cipher = Cipher::new(Some(DataKey::generate("passphrase").unwrap()));
+
+        save_local(&temp_dir, &store, &cipher).unwrap();
+        let on_disk = fs::read_to_string(temp_dir.join("reminders.json")).unwrap();
+        assert!(!on_disk.contains("Acme"));
+        assert_eq!(
+            load_local(&temp_dir, &cipher).unwrap().pending[0].message,
+            "Call Acme Corp"
+        );
+
+        // Not mistaken for a corrupt file
+        let error = load_local(&temp_dir, &Cipher::default()).err().unwrap();
+        assert!(error.contains(LOCKED_ERROR));
+        assert!(temp_dir.join("reminders.json").exists());
+
+        // Plain data planted in its place isn't loaded as if it were ours
+        let plain = serde_json::to_string(&store).unwrap();
+        fs::write(temp_dir.join("reminders.json"), plain).unwrap();
+        assert!(load_local(&temp_dir, &cipher).is_err());
+        assert!(load_local(&temp_dir, &cipher.accepting_plain(true)).is_ok());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_load_corrupt_file_is_an_error() {
+        let temp_dir = env::temp_dir().join("test_load_corrupt");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let _ = fs::create_dir_all(&temp_dir);
+        fs::write(temp_dir.join("reminders.json"), r#"{"pending":[{"id":"#).unwrap();
+
+        assert!(load_local(&temp_dir, &Cipher::default()).is_err());
+        // The broken file is kept for inspection but no longer in the way
+        assert!(!temp_dir.join("reminders.json").exists());
+        let kept = fs::read_dir(&temp_dir).unwrap().count();
+        assert_eq!(kept, 1);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_load_refuses_newer_schema_version() {
+        let temp_dir = env::temp_dir().join("test_load_newer_version");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let _ = fs::create_dir_all(&temp_dir);
+        let content = r#"{"schema_version":999,"pending":[],"completed":[]}"#;
+        fs::write(temp_dir.join("reminders.json"), content).unwrap();
+
+        let error = load_local(&temp_dir, &Cipher::default()).err().unwrap();
+        assert!(error.contains(NEWER_VERSION_ERROR));
+        // Left exactly as it was
+        let kept = fs::read_to_string(temp_dir.join("reminders.json")).unwrap();
+        assert_eq!(kept, content);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_load_adds_built_in_lists() {
+        let temp_dir = env::temp_dir().join("test_load_built_in_lists");
+        let _ = fs::create_dir_all(&temp_dir);
+        fs::write(
+            temp_dir.join("reminders.json"),
+            r#"{"pending":[],"completed":[]}"#,
+        )
+        .unwrap();
+
+        let store = load_local(&temp_dir, &Cipher::default()).unwrap();
+        let ids: Vec<&str> = store.lists.iter().map(|l| l.id.as_str()).collect();
+        assert_eq!(ids, vec!["actual", "backlog"]);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_device_id_is_stable() {
+        let temp_dir = env::temp_dir().join("test_device_id");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let first = load_or_create_device_id(&temp_dir).unwrap();
+        let second = load_or_create_device_id(&temp_dir).unwrap();
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_load_migrates_integer_ids() {
+        let temp_dir = env::temp_dir().join("test_load_migrates_ids");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let old_format = r#"{"pending":[{"id":3,"message":"Old","urgency":"today",
+            "list_type":"actual","created_at":"2024-01-01T00:00:00Z",
+            "is_completed":false,"completed_at":null,"sort_order":0}],"completed":[]}"#;
+        fs::write(temp_dir.join("reminders.json"), old_format).unwrap();
+
+        let loaded = load_local(&temp_dir, &Cipher::default()).unwrap();
+        assert_eq!(loaded.pending[0].id, "3-1704067200000");
+
+        // The migrated ids are written back
+        let reloaded = load_local(&temp_dir, &Cipher::default()).unwrap();
+        assert_eq!(reloaded.pending[0].id, "3-1704067200000");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
 }
@@ -0,0 +1,132 @@
+use crate::reminder::Reminder;
+use crate::storage::backend::{
+    ArchiveSnapshot, ConditionalSave, Revision, Snapshot, StorageBackend,
+};
+use crate::storage::crypto::Cipher;
+use crate::storage::merge::ReminderStore;
+use crate::storage::oplog::Operation;
+use std::collections::BTreeMap;
+
+/// Backend that keeps the store in memory, for tests
+#[derive(Default)]
+pub struct MemoryBackend {
+    data: Option<ReminderStore>,
+    revision: u64,
+    ops: Vec<Operation>,
+    archive: BTreeMap<i32, (u64, Vec<Reminder>)>, // By year, with its revision
+}
+
+impl MemoryBackend {
+    pub fn with_data(data: ReminderStore) -> Self {
+        Self {
+            data: Some(data),
+            revision: 1,
+            ops: Vec::new(),
+            archive: BTreeMap::new(),
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn identity(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn load(&mut self) -> Result<Snapshot, String> {
+        Ok(Snapshot {
+            data: self.data.clone().unwrap_or_default(),
+            revision: self.revision()?,
+        })
+    }
+
+    fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String> {
+        self.data = Some(data.clone());
+        self.revision += 1;
+        self.revision()
+    }
+
+    fn revision(&mut self) -> Result<Option<Revision>, String> {
+        Ok(self.data.as_ref().map(|_| self.revision.to_string()))
+    }
+
+    fn load_ops(&mut self) -> Option<Result<Vec<Operation>, String>> {
+        Some(Ok(self.ops.clone()))
+    }
+
+    fn push_ops(
+        &mut self,
+        ops: &[Operation],
+        compacted: Option<&str>,
+    ) -> Option<Result<(), String>> {
+        for op in ops {
+            if !self.ops.iter().any(|known| known.id == op.id) {
+                self.ops.push(op.clone());
+            }
+        }
+        if let Some(point) = compacted {
+            self.ops.retain(|op| op.id.as_str() > point);
+        }
+        Some(Ok(()))
+    }
+
+    fn archive_years(&mut self) -> Option<Result<Vec<i32>, String>> {
+        Some(Ok(self.archive.keys().copied().collect()))
+    }
+
+    fn load_archive(&mut self, year: i32) -> Option<Result<ArchiveSnapshot, String>> {
+        let (revision, reminders) = match self.archive.get(&year) {
+            Some((revision, reminders)) => (Some(revision.to_string()), reminders.clone()),
+            None => (None, Vec::new()),
+        };
+        Some(Ok(ArchiveSnapshot {
+            reminders,
+            revision,
+        }))
+    }
+
+    fn save_archive_if_unchanged(
+        &mut self,
+        year: i32,
+        reminders: &[Reminder],
+        expected: Option<&str>,
+    ) -> Option<Result<ConditionalSave, String>> {
+        let current = self.archive.get(&year).map(|(revision, _)| *revision);
+        if current.map(|revision| revision.to_string()).as_deref() != expected {
+            return Some(Ok(ConditionalSave::Conflict));
+        }
+        let revision = current.unwrap_or_default() + 1;
+        self.archive.insert(year, (revision, reminders.to_vec()));
+        Some(Ok(ConditionalSave::Saved(Some(revision.to_string()))))
+    }
+
+    /// Nothing is written anywhere, so there's nothing to encrypt
+    fn set_cipher(&mut self, _cipher: Cipher) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditional_save_detects_concurrent_write() {
+        let mut backend = MemoryBackend::default();
+        let seen = backend.load().unwrap().revision;
+        assert_eq!(seen, None);
+
+        // Someone else writes first
+        backend.save(&ReminderStore::default()).unwrap();
+
+        let outcome = backend
+            .save_if_unchanged(&ReminderStore::default(), seen.as_deref())
+            .unwrap();
+        assert_eq!(outcome, ConditionalSave::Conflict);
+
+        let current = backend.revision().unwrap();
+        let outcome = backend
+            .save_if_unchanged(&ReminderStore::default(), current.as_deref())
+            .unwrap();
+        assert!(matches!(outcome, ConditionalSave::Saved(Some(_))));
+    }
+}
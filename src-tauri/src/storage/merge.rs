@@ -1,94 +1,524 @@
-use crate::reminder::Reminder;
-use chrono::DateTime;
+use crate::clock::{is_newer, Stamp};
+use crate::reminder::{field, ListType, Reminder, ReminderId};
+use crate::storage::conflicts::{self, SyncConflict};
+use crate::storage::integrity;
+use crate::storage::lists::{default_lists, merge_lists, ListDefinition};
+use crate::storage::migrations::SCHEMA_VERSION;
+use crate::storage::settings::{merge_settings, StoreSettings};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// Internal store structure for pending and completed reminders
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReminderStore {
+    /// Format version, see `migrations`
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub pending: Vec<Reminder>,
     pub completed: Vec<Reminder>,
+    #[serde(default)]
+    pub settings: StoreSettings,
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
+    /// Deleted reminders that can still be restored
+    #[serde(default)]
+    pub trash: Vec<TrashedReminder>,
+    /// Stores written before user-defined lists get the built-in lists
+    #[serde(default = "default_lists")]
+    pub lists: Vec<ListDefinition>,
+    /// Records of deleted lists, by list id, kept so merges don't bring them
+    /// back
+    #[serde(default)]
+    pub deleted_lists: Vec<Tombstone>,
+    /// Newest operation each device has read from the remote's operation
+    /// log, by device id
+    #[serde(default)]
+    pub ops_acknowledged: BTreeMap<String, String>,
+    /// Newest operation-log entry this snapshot includes
+    #[serde(default)]
+    pub last_op: Option<String>,
 }
 
-/// Merge two ReminderStores, keeping all unique tasks and preferring newer versions for conflicts
+impl Default for ReminderStore {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            pending: Vec::new(),
+            completed: Vec::new(),
+            settings: StoreSettings::default(),
+            tombstones: Vec::new(),
+            trash: Vec::new(),
+            lists: default_lists(),
+            deleted_lists: Vec::new(),
+            ops_acknowledged: BTreeMap::new(),
+            last_op: None,
+        }
+    }
+}
+
+fn current_schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Record of a deleted reminder, kept so merges don't bring it back
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Tombstone {
+    pub id: ReminderId,
+    pub deleted_at: String, // Clock stamp; ISO string in older data
+    pub device: String,     // Device that deleted the reminder
+}
+
+/// A deleted reminder as it was, with the list it was deleted from. It also
+/// has a tombstone, which is removed again if it is restored.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrashedReminder {
+    pub reminder: Reminder,
+    pub list: ListType,
+    pub deleted_at: String, // ISO string
+    pub device: String,     // Device that deleted the reminder
+}
+
+impl ReminderStore {
+    /// Drop tombstones older than the configured retention period. Returns
+    /// how many were removed.
+    pub fn purge_tombstones(&mut self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - Duration::days(self.settings.tombstone_retention_days as i64);
+        let before = self.tombstones.len() + self.deleted_lists.len();
+        self.tombstones
+            .retain(|t| deleted_since(&t.deleted_at, cutoff));
+        self.deleted_lists
+            .retain(|t| deleted_since(&t.deleted_at, cutoff));
+        before - self.tombstones.len() - self.deleted_lists.len()
+    }
+
+    /// Drop trashed reminders older than the configured retention period.
+    /// Returns how many were removed.
+    pub fn purge_trash(&mut self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - Duration::days(self.settings.trash_retention_days as i64);
+        let before = self.trash.len();
+        self.trash.retain(|t| deleted_since(&t.deleted_at, cutoff));
+        before - self.trash.len()
+    }
+
+    /// Id of the newest operation every known device has read. Operation
+    /// logs can drop everything up to it. None until a device has read one.
+    pub fn ops_compaction_point(&self) -> Option<&str> {
+        self.ops_acknowledged.values().min().map(String::as_str)
+    }
+}
+
+/// Whether a deletion time (or clock stamp) is at or after `cutoff`
+fn deleted_since(deleted_at: &str, cutoff: DateTime<Utc>) -> bool {
+    Stamp::parse(deleted_at).is_some_and(|stamp| stamp.time >= cutoff)
+}
+
+/// Merge local and cloud data, against `base` if both sides last synced it,
+/// and report the fields both sides changed differently
+pub fn merge_with_report(
+    base: Option<&ReminderStore>,
+    local: &ReminderStore,
+    cloud: &ReminderStore,
+) -> (ReminderStore, Vec<SyncConflict>) {
+    let merged = match base {
+        Some(base) => merge_three_way(base, local, cloud),
+        None => merge_stores(local, cloud),
+    };
+    let conflicts = conflicts::find(base, local, cloud, &merged);
+    (merged, conflicts)
+}
+
+/// Merge two ReminderStores, keeping all unique tasks. Reminders present on
+/// both sides are merged field by field, each field taken from whichever side
+/// changed it last. Reminders deleted on either side stay deleted unless they
+/// were changed after the deletion.
 pub fn merge_stores(local: &ReminderStore, cloud: &ReminderStore) -> ReminderStore {
-    // Merge pending reminders
-    let mut pending_map: HashMap<i64, Reminder> = HashMap::new();
-
-    // Add all local pending
-    for r in &local.pending {
-        pending_map.insert(r.id, r.clone());
-    }
-
-    // Merge cloud pending - only add if not exists or if cloud version is newer
-    for r in &cloud.pending {
-        if let Some(existing) = pending_map.get(&r.id) {
-            // Compare and keep newer
-            let a_time = existing.completed_at.as_ref().unwrap_or(&existing.created_at);
-            let b_time = r.completed_at.as_ref().unwrap_or(&r.created_at);
-
-            if let (Ok(a_dt), Ok(b_dt)) = (
-                DateTime::parse_from_rfc3339(a_time),
-                DateTime::parse_from_rfc3339(b_time),
-            ) {
-                if b_dt > a_dt {
-                    pending_map.insert(r.id, r.clone());
+    let cloud_by_id: HashMap<&str, &Reminder> = cloud
+        .pending
+        .iter()
+        .chain(cloud.completed.iter())
+        .map(|r| (r.id.as_str(), r))
+        .collect();
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut merged: Vec<Reminder> = Vec::new();
+
+    for r in local.pending.iter().chain(local.completed.iter()) {
+        if !seen.insert(r.id.as_str()) {
+            continue;
+        }
+        match cloud_by_id.get(r.id.as_str()) {
+            Some(cloud_version) => merged.push(merge_reminder(r, cloud_version)),
+            None => merged.push(r.clone()),
+        }
+    }
+
+    // Tasks only the cloud knows about
+    for r in cloud.pending.iter().chain(cloud.completed.iter()) {
+        if seen.insert(r.id.as_str()) {
+            merged.push(r.clone());
+        }
+    }
+
+    finish_merge(local, cloud, merged)
+}
+
+/// Merge two ReminderStores against `base`, the store both sides last
+/// synced. Comparing each side with the base tells a deletion on one side
+/// from an addition on the other, and a change on one side from a stale copy
+/// on the other:
+/// - a reminder missing from one side was added on the other if the base
+///   doesn't have it, and deleted if it does. A deleted reminder stays
+///   deleted unless the other side changed it since the base, and changed it
+///   after the deletion.
+/// - each field, including completion and the list a reminder is in, is
+///   taken from the side that changed it since the base. Only fields changed
+///   on both sides fall back to whichever changed last, as in `merge_stores`.
+/// - tags added or removed on either side are added or removed.
+pub fn merge_three_way(
+    base: &ReminderStore,
+    local: &ReminderStore,
+    cloud: &ReminderStore,
+) -> ReminderStore {
+    let by_id = |store: &ReminderStore| -> HashMap<String, Reminder> {
+        store
+            .pending
+            .iter()
+            .chain(store.completed.iter())
+            .map(|r| (r.id.clone(), r.clone()))
+            .collect()
+    };
+    let base_by_id = by_id(base);
+    let local_by_id = by_id(local);
+    let cloud_by_id = by_id(cloud);
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut merged: Vec<Reminder> = Vec::new();
+
+    for r in local
+        .pending
+        .iter()
+        .chain(local.completed.iter())
+        .chain(cloud.pending.iter())
+        .chain(cloud.completed.iter())
+    {
+        if !seen.insert(r.id.as_str()) {
+            continue;
+        }
+        let base_version = base_by_id.get(&r.id);
+        let result = match (local_by_id.get(&r.id), cloud_by_id.get(&r.id)) {
+            (Some(local_version), Some(cloud_version)) => match base_version {
+                Some(base_version) => {
+                    merge_reminder_three_way(base_version, local_version, cloud_version)
                 }
+                // Added on both sides, e.g. restored from the trash on both
+                None => merge_reminder(local_version, cloud_version),
+            },
+            // Deleted on the other side; a newer deletion is applied below
+            (Some(kept), None) | (None, Some(kept)) => match base_version {
+                Some(base_version) if base_version == kept => continue,
+                _ => kept.clone(),
+            },
+            (None, None) => continue,
+        };
+        merged.push(result);
+    }
+
+    finish_merge(local, cloud, merged)
+}
+
+/// Apply deletions from both sides to the merged reminders and combine
+/// everything besides reminders
+fn finish_merge(
+    local: &ReminderStore,
+    cloud: &ReminderStore,
+    mut merged: Vec<Reminder>,
+) -> ReminderStore {
+    let mut tombstones = latest_tombstones(&local.tombstones, &cloud.tombstones);
+    merged.retain(|r| {
+        !tombstones
+            .iter()
+            .any(|t| t.id == r.id && !is_newer(&t.deleted_at, r.last_stamp()))
+    });
+    tombstones.retain(|t| !merged.iter().any(|r| r.id == t.id));
+
+    // Latest deletion per id, unless it was restored on either side
+    let mut trash: Vec<TrashedReminder> = Vec::new();
+    for t in local.trash.iter().chain(cloud.trash.iter()) {
+        if merged.iter().any(|r| r.id == t.reminder.id) {
+            continue;
+        }
+        match trash
+            .iter_mut()
+            .find(|existing| existing.reminder.id == t.reminder.id)
+        {
+            Some(existing) if is_newer(&existing.deleted_at, &t.deleted_at) => {
+                *existing = t.clone()
             }
-        } else {
-            // New task from cloud - add it
-            pending_map.insert(r.id, r.clone());
+            Some(_) => {}
+            None => trash.push(t.clone()),
         }
     }
 
-    // Merge completed reminders
-    let mut completed_map: HashMap<i64, Reminder> = HashMap::new();
+    // Lists deleted on either side stay deleted unless they were changed
+    // after the deletion
+    let mut deleted_lists = latest_tombstones(&local.deleted_lists, &cloud.deleted_lists);
+    let mut lists = merge_lists(&local.lists, &cloud.lists);
+    lists.retain(|l| {
+        l.built_in
+            || !deleted_lists
+                .iter()
+                .any(|t| t.id == l.id.as_str() && !is_newer(&t.deleted_at, l.last_modified()))
+    });
+    deleted_lists.retain(|t| !lists.iter().any(|l| l.id.as_str() == t.id));
 
-    for r in &local.completed {
-        completed_map.insert(r.id, r.clone());
+    let list_ids: Vec<ListType> = lists.iter().map(|l| l.id.clone()).collect();
+    for list in lists.iter_mut() {
+        list.overflow_list = list.overflow_list.take().filter(|id| list_ids.contains(id));
+        list.source_list = list.source_list.take().filter(|id| list_ids.contains(id));
+    }
+    // Filed in a list deleted on the other side: to the Backlog, as
+    // `delete_list` does
+    for r in merged.iter_mut() {
+        if !r.is_completed && !list_ids.contains(&r.list_type) {
+            r.list_type = ListType::Backlog;
+        }
     }
 
-    for r in &cloud.completed {
-        if !completed_map.contains_key(&r.id) {
-            completed_map.insert(r.id, r.clone());
+    // Devices only ever read further
+    let mut ops_acknowledged = local.ops_acknowledged.clone();
+    for (device, op) in &cloud.ops_acknowledged {
+        let known = ops_acknowledged.entry(device.clone()).or_default();
+        if op > known {
+            *known = op.clone();
         }
-        // For completed items, also check if it exists in pending - if so, it was completed
-        if pending_map.contains_key(&r.id) {
-            pending_map.remove(&r.id);
-            completed_map.insert(r.id, r.clone());
+    }
+
+    let (completed, pending) = merged.into_iter().partition(|r| r.is_completed);
+
+    let mut merged = ReminderStore {
+        schema_version: SCHEMA_VERSION,
+        pending,
+        completed,
+        settings: merge_settings(&local.settings, &cloud.settings),
+        tombstones,
+        trash,
+        lists,
+        deleted_lists,
+        ops_acknowledged,
+        // Only meaningful against this device's operation log
+        last_op: local.last_op.clone(),
+    };
+
+    for finding in integrity::repair(&mut merged) {
+        eprintln!("Repaired after merge: {}", finding);
+    }
+    merged
+}
+
+/// Latest deletion per id from either side
+pub(crate) fn latest_tombstones(a: &[Tombstone], b: &[Tombstone]) -> Vec<Tombstone> {
+    let mut tombstones: Vec<Tombstone> = Vec::new();
+    for t in a.iter().chain(b.iter()) {
+        match tombstones.iter_mut().find(|existing| existing.id == t.id) {
+            Some(existing) if is_newer(&existing.deleted_at, &t.deleted_at) => {
+                *existing = t.clone()
+            }
+            Some(_) => {}
+            None => tombstones.push(t.clone()),
         }
     }
+    tombstones
+}
+
+/// Merge two versions of the same reminder field by field
+pub(crate) fn merge_reminder(local: &Reminder, cloud: &Reminder) -> Reminder {
+    let mut merged = local.clone();
+
+    merged.message = pick(local, cloud, field::MESSAGE).message.clone();
+    merged.urgency = pick(local, cloud, field::URGENCY).urgency.clone();
+    merged.list_type = pick(local, cloud, field::LIST_TYPE).list_type.clone();
+    merged.sort_order = pick(local, cloud, field::SORT_ORDER).sort_order.clone();
+    merged.due_at = pick(local, cloud, field::DUE_AT).due_at.clone();
+    merged.recurrence = pick(local, cloud, field::RECURRENCE).recurrence.clone();
+    merged.hidden_until = pick(local, cloud, field::HIDDEN_UNTIL).hidden_until.clone();
+    merged.notes = pick(local, cloud, field::NOTES).notes.clone();
+    merged.checklist = pick(local, cloud, field::CHECKLIST).checklist.clone();
+    merged.tags = union_tags(&local.tags, &cloud.tags);
 
-    // Also check local completed against cloud pending
-    for r in &local.completed {
-        if cloud.pending.iter().any(|cr| cr.id == r.id) {
-            // Local has it as completed, cloud has as pending - keep as completed
-            pending_map.remove(&r.id);
+    let completion = pick_completion(local, cloud);
+    merged.is_completed = completion.is_completed;
+    merged.completed_at = completion.completed_at.clone();
+    merged.next_occurrence = completion.next_occurrence.clone();
+
+    for (name, time) in &cloud.modified {
+        let newer = match merged.modified.get(name) {
+            Some(existing) => is_newer(existing, time),
+            None => true,
+        };
+        if newer {
+            merged.modified.insert(name.clone(), time.clone());
         }
     }
+    if is_newer(local.last_modified(), cloud.last_modified()) {
+        merged.updated_at = cloud.updated_at.clone();
+    }
+
+    merged
+}
+
+/// Merge two versions of the same reminder against the version both sides
+/// last synced. Fields changed on one side only are taken from that side.
+fn merge_reminder_three_way(base: &Reminder, local: &Reminder, cloud: &Reminder) -> Reminder {
+    // Fields changed on both sides keep the newest change
+    let mut merged = merge_reminder(local, cloud);
+
+    merged.message = three_way(
+        &base.message,
+        &local.message,
+        &cloud.message,
+        merged.message,
+    );
+    merged.urgency = three_way(
+        &base.urgency,
+        &local.urgency,
+        &cloud.urgency,
+        merged.urgency,
+    );
+    merged.list_type = three_way(
+        &base.list_type,
+        &local.list_type,
+        &cloud.list_type,
+        merged.list_type,
+    );
+    merged.sort_order = three_way(
+        &base.sort_order,
+        &local.sort_order,
+        &cloud.sort_order,
+        merged.sort_order,
+    );
+    merged.due_at = three_way(&base.due_at, &local.due_at, &cloud.due_at, merged.due_at);
+    merged.recurrence = three_way(
+        &base.recurrence,
+        &local.recurrence,
+        &cloud.recurrence,
+        merged.recurrence,
+    );
+    merged.hidden_until = three_way(
+        &base.hidden_until,
+        &local.hidden_until,
+        &cloud.hidden_until,
+        merged.hidden_until,
+    );
+    merged.notes = three_way(&base.notes, &local.notes, &cloud.notes, merged.notes);
+    merged.checklist = three_way(
+        &base.checklist,
+        &local.checklist,
+        &cloud.checklist,
+        merged.checklist,
+    );
+    merged.tags = three_way_tags(&base.tags, &local.tags, &cloud.tags);
+
+    let completion = |r: &Reminder| {
+        (
+            r.is_completed,
+            r.completed_at.clone(),
+            r.next_occurrence.clone(),
+        )
+    };
+    (
+        merged.is_completed,
+        merged.completed_at,
+        merged.next_occurrence,
+    ) = three_way(
+        &completion(base),
+        &completion(local),
+        &completion(cloud),
+        completion(&merged),
+    );
+
+    merged
+}
+
+/// The side's value if only one side changed it since `base`, otherwise
+/// `conflict`
+fn three_way<T: Clone + PartialEq>(base: &T, local: &T, cloud: &T, conflict: T) -> T {
+    if local == cloud || cloud == base {
+        local.clone()
+    } else if local == base {
+        cloud.clone()
+    } else {
+        conflict
+    }
+}
+
+/// `base` tags with the tags added on either side and without the tags
+/// removed on either side (case-insensitive)
+fn three_way_tags(base: &[String], local: &[String], cloud: &[String]) -> Vec<String> {
+    let has = |tags: &[String], tag: &str| tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+    let mut tags: Vec<String> = base
+        .iter()
+        .filter(|tag| has(local, tag) && has(cloud, tag))
+        .cloned()
+        .collect();
+    for tag in local.iter().chain(cloud.iter()) {
+        if !has(base, tag) && !has(&tags, tag) {
+            tags.push(tag.clone());
+        }
+    }
+    tags
+}
+
+/// The version whose `field` changed last; ties go to `local`
+fn pick<'a>(local: &'a Reminder, cloud: &'a Reminder, field: &str) -> &'a Reminder {
+    if is_newer(local.field_modified(field), cloud.field_modified(field)) {
+        cloud
+    } else {
+        local
+    }
+}
+
+/// Completion is only trusted when it was explicitly changed. Reminders that
+/// never recorded a completion change (or changed it at the same time) fall
+/// back to completion winning, as a done task is rarely meant to reopen.
+fn pick_completion<'a>(local: &'a Reminder, cloud: &'a Reminder) -> &'a Reminder {
+    let local_time = local.modified.get(field::COMPLETION);
+    let cloud_time = cloud.modified.get(field::COMPLETION);
+
+    match (local_time, cloud_time) {
+        (Some(a), Some(b)) if is_newer(a, b) => cloud,
+        (Some(a), Some(b)) if is_newer(b, a) => local,
+        (None, Some(_)) => cloud,
+        (Some(_), None) => local,
+        _ if cloud.is_completed && !local.is_completed => cloud,
+        _ => local,
+    }
+}
 
-    ReminderStore {
-        pending: pending_map.into_values().collect(),
-        completed: completed_map.into_values().collect(),
+/// Union of two tag lists, keeping the order of `a` and skipping
+/// case-insensitive duplicates from `b`
+fn union_tags(a: &[String], b: &[String]) -> Vec<String> {
+    let mut tags = a.to_vec();
+    for tag in b {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.clone());
+        }
     }
+    tags
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::reminder::{ListType, Urgency};
-    use chrono::Utc;
+    use crate::reminder::{unique_legacy_id, ListType, Urgency};
+    use crate::sort_key;
+    use crate::storage::lists;
 
     fn make_reminder(id: i64, created_at: &str) -> Reminder {
         Reminder {
-            id,
-            message: format!("Task {}", id),
-            urgency: Urgency::Today,
-            list_type: ListType::Actual,
+            id: id.to_string(),
             created_at: created_at.to_string(),
-            is_completed: false,
-            completed_at: None,
-            sort_order: 0,
+            ..Reminder::new(format!("Task {}", id), Urgency::Today, ListType::Actual)
         }
     }
 
@@ -97,6 +527,7 @@ mod tests {
         let local = ReminderStore {
             pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
             completed: vec![],
+            ..Default::default()
         };
         let cloud = ReminderStore {
             pending: vec![
@@ -104,6 +535,7 @@ mod tests {
                 make_reminder(2, "2024-01-02T00:00:00Z"),
             ],
             completed: vec![],
+            ..Default::default()
         };
 
         let merged = merge_stores(&local, &cloud);
@@ -115,16 +547,732 @@ mod tests {
         let local = ReminderStore {
             pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
             completed: vec![],
+            ..Default::default()
         };
         let mut newer = make_reminder(1, "2024-01-02T00:00:00Z");
         newer.message = "Updated".to_string();
         let cloud = ReminderStore {
             pending: vec![newer],
             completed: vec![],
+            ..Default::default()
         };
 
         let merged = merge_stores(&local, &cloud);
         assert_eq!(merged.pending.len(), 1);
         assert_eq!(merged.pending[0].message, "Updated");
     }
+
+    #[test]
+    fn test_merge_unions_tags() {
+        let mut local_version = make_reminder(1, "2024-01-01T00:00:00Z");
+        local_version.tags = vec!["client-a".to_string()];
+        let mut cloud_version = make_reminder(1, "2024-01-02T00:00:00Z");
+        cloud_version.tags = vec!["urgent".to_string(), "Client-A".to_string()];
+
+        let local = ReminderStore {
+            pending: vec![local_version],
+            completed: vec![],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![cloud_version],
+            completed: vec![],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.pending.len(), 1);
+        assert_eq!(merged.pending[0].tags, vec!["client-a", "urgent"]);
+    }
+
+    #[test]
+    fn test_migrated_integer_ids_no_longer_collide() {
+        // Two devices both handed out id 5 to different reminders while offline
+        let migrated = |created_at: &str| {
+            let mut reminder = make_reminder(5, created_at);
+            reminder.id = unique_legacy_id(&reminder.id, created_at).unwrap();
+            reminder
+        };
+        let local = ReminderStore {
+            pending: vec![migrated("2024-01-01T00:00:00Z")],
+            completed: vec![],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![migrated("2024-01-03T00:00:00Z")],
+            completed: vec![],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.pending.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_keeps_edits_to_different_fields() {
+        let base = make_reminder(1, "2024-01-01T00:00:00Z");
+
+        let mut local_version = base.clone();
+        local_version.message = "Renamed locally".to_string();
+        local_version.modified.insert(
+            field::MESSAGE.to_string(),
+            "2024-01-02T00:00:00Z".to_string(),
+        );
+        local_version.updated_at = Some("2024-01-02T00:00:00Z".to_string());
+
+        let mut cloud_version = base;
+        cloud_version.urgency = Urgency::Now;
+        cloud_version.modified.insert(
+            field::URGENCY.to_string(),
+            "2024-01-03T00:00:00Z".to_string(),
+        );
+        cloud_version.updated_at = Some("2024-01-03T00:00:00Z".to_string());
+
+        let local = ReminderStore {
+            pending: vec![local_version],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![cloud_version],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.pending.len(), 1);
+        assert_eq!(merged.pending[0].message, "Renamed locally");
+        assert_eq!(merged.pending[0].urgency, Urgency::Now);
+        assert_eq!(
+            merged.pending[0].updated_at.as_deref(),
+            Some("2024-01-03T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_merge_orders_fields_by_clock_stamps() {
+        let base = make_reminder(1, "2024-01-01T00:00:00Z");
+        // Device "b" runs an hour fast. Device "a" synced its change first, so
+        // its clock moved past b's stamp even though its own time is earlier.
+        let b_stamp = "2024-01-02T01:00:00Z/0/device-b";
+        let a_stamp = "2024-01-02T01:00:00Z/1/device-a";
+
+        let mut local_version = base.clone();
+        local_version.message = "Renamed after sync".to_string();
+        local_version
+            .modified
+            .insert(field::MESSAGE.to_string(), a_stamp.to_string());
+        // Made at the same moment on both devices: the device id decides
+        local_version.urgency = Urgency::Now;
+        local_version.modified.insert(
+            field::URGENCY.to_string(),
+            "2024-01-02T01:00:00Z/0/device-a".to_string(),
+        );
+        local_version.updated_at = Some("2024-01-02T01:00:00Z".to_string());
+
+        let mut cloud_version = base;
+        cloud_version.message = "Renamed on fast clock".to_string();
+        cloud_version.urgency = Urgency::Whenever;
+        for name in [field::MESSAGE, field::URGENCY] {
+            cloud_version
+                .modified
+                .insert(name.to_string(), b_stamp.to_string());
+        }
+        cloud_version.updated_at = Some("2024-01-02T01:00:00Z".to_string());
+
+        let local = ReminderStore {
+            pending: vec![local_version.clone()],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![cloud_version.clone()],
+            ..Default::default()
+        };
+
+        for merged in [merge_stores(&local, &cloud), merge_stores(&cloud, &local)] {
+            assert_eq!(merged.pending[0].message, "Renamed after sync");
+            assert_eq!(merged.pending[0].urgency, Urgency::Whenever);
+        }
+    }
+
+    #[test]
+    fn test_merge_far_future_edit_loses_to_later_local_edit() {
+        let base = make_reminder(1, "2024-01-01T00:00:00Z");
+        // Device "wrong" has its clock set a year ahead
+        let wrong_stamp = Stamp {
+            time: Utc::now() + Duration::days(365),
+            counter: 0,
+            device: "wrong".to_string(),
+        }
+        .to_string();
+        let mut cloud_version = base.clone();
+        cloud_version.message = "Renamed on wrong clock".to_string();
+        cloud_version.is_completed = true;
+        cloud_version.completed_at = Some(Utc::now().to_rfc3339());
+        for name in [field::MESSAGE, field::COMPLETION] {
+            cloud_version
+                .modified
+                .insert(name.to_string(), wrong_stamp.clone());
+        }
+
+        // Edited here after seeing that change
+        let mut local_version = base;
+        local_version.message = "Renamed after sync".to_string();
+        local_version.touch(&[field::MESSAGE, field::COMPLETION]);
+
+        let local = ReminderStore {
+            pending: vec![local_version],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            completed: vec![cloud_version],
+            ..Default::default()
+        };
+
+        for merged in [merge_stores(&local, &cloud), merge_stores(&cloud, &local)] {
+            assert_eq!(merged.pending.len(), 1);
+            assert_eq!(merged.pending[0].message, "Renamed after sync");
+            assert!(!merged.pending[0].modified[field::MESSAGE].ends_with("/wrong"));
+        }
+    }
+
+    #[test]
+    fn test_merge_completion_wins_without_timestamps() {
+        let mut done = make_reminder(1, "2024-01-01T00:00:00Z");
+        done.is_completed = true;
+        done.completed_at = Some("2024-01-02T00:00:00Z".to_string());
+
+        let local = ReminderStore {
+            pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            completed: vec![done],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert!(merged.pending.is_empty());
+        assert_eq!(merged.completed.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_later_uncomplete_wins() {
+        let mut done = make_reminder(1, "2024-01-01T00:00:00Z");
+        done.is_completed = true;
+        done.completed_at = Some("2024-01-02T00:00:00Z".to_string());
+        done.modified.insert(
+            field::COMPLETION.to_string(),
+            "2024-01-02T00:00:00Z".to_string(),
+        );
+
+        let mut reopened = make_reminder(1, "2024-01-01T00:00:00Z");
+        reopened.modified.insert(
+            field::COMPLETION.to_string(),
+            "2024-01-03T00:00:00Z".to_string(),
+        );
+
+        let local = ReminderStore {
+            completed: vec![done],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![reopened],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert!(merged.completed.is_empty());
+        assert_eq!(merged.pending.len(), 1);
+        assert_eq!(merged.pending[0].completed_at, None);
+    }
+
+    #[test]
+    fn test_merge_repairs_clashing_sort_orders() {
+        // Both devices put a new reminder at the top of the same list
+        let local = ReminderStore {
+            pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![make_reminder(2, "2024-01-02T00:00:00Z")],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_ne!(merged.pending[0].sort_order, merged.pending[1].sort_order);
+        assert!(integrity::verify(&merged).is_empty());
+    }
+
+    fn make_tombstone(id: &str, deleted_at: &str) -> Tombstone {
+        Tombstone {
+            id: id.to_string(),
+            deleted_at: deleted_at.to_string(),
+            device: "other-device".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_honours_tombstones() {
+        let local = ReminderStore {
+            tombstones: vec![make_tombstone("1", "2024-01-05T00:00:00Z")],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert!(merged.pending.is_empty());
+        assert_eq!(merged.tombstones.len(), 1);
+
+        // The other direction behaves the same
+        let merged = merge_stores(&cloud, &local);
+        assert!(merged.pending.is_empty());
+        assert_eq!(merged.tombstones.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_keeps_reminder_changed_after_deletion() {
+        let mut edited = make_reminder(1, "2024-01-01T00:00:00Z");
+        edited.updated_at = Some("2024-01-06T00:00:00Z".to_string());
+
+        let local = ReminderStore {
+            tombstones: vec![make_tombstone("1", "2024-01-05T00:00:00Z")],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![edited],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.pending.len(), 1);
+        assert!(merged.tombstones.is_empty());
+    }
+
+    #[test]
+    fn test_merge_keeps_deleted_lists_deleted() {
+        let waiting = ListType::Custom("waiting".to_string());
+        let mut list = ListDefinition::new(waiting.clone(), "Waiting".to_string(), 2);
+        list.modified.insert(
+            lists::list_field::NAME.to_string(),
+            "2024-01-01T00:00:00Z/0/other-device".to_string(),
+        );
+        let mut filed = make_reminder(1, "2024-01-01T00:00:00Z");
+        filed.list_type = waiting.clone();
+
+        let local = ReminderStore {
+            deleted_lists: vec![make_tombstone(
+                "waiting",
+                "2024-01-05T00:00:00Z/0/this-device",
+            )],
+            ..Default::default()
+        };
+        let mut cloud = ReminderStore {
+            pending: vec![filed],
+            ..Default::default()
+        };
+        cloud.lists.push(list);
+
+        for merged in [merge_stores(&local, &cloud), merge_stores(&cloud, &local)] {
+            assert!(merged.lists.iter().all(|l| l.id != waiting));
+            assert_eq!(merged.deleted_lists.len(), 1);
+            assert_eq!(merged.pending[0].list_type, ListType::Backlog);
+        }
+
+        // Renamed on the other device after the deletion: the list stays
+        cloud.lists[2].modified.insert(
+            lists::list_field::NAME.to_string(),
+            "2024-01-06T00:00:00Z/0/other-device".to_string(),
+        );
+        let merged = merge_stores(&local, &cloud);
+        assert!(merged.lists.iter().any(|l| l.id == waiting));
+        assert!(merged.deleted_lists.is_empty());
+        assert_eq!(merged.pending[0].list_type, waiting);
+    }
+
+    #[test]
+    fn test_merge_keeps_furthest_acknowledgement_per_device() {
+        let mut local = ReminderStore::default();
+        local
+            .ops_acknowledged
+            .insert("laptop".to_string(), "0002".to_string());
+        local
+            .ops_acknowledged
+            .insert("phone".to_string(), "0001".to_string());
+        let mut cloud = ReminderStore::default();
+        cloud
+            .ops_acknowledged
+            .insert("phone".to_string(), "0003".to_string());
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.ops_acknowledged["laptop"], "0002");
+        assert_eq!(merged.ops_acknowledged["phone"], "0003");
+        assert_eq!(merged.ops_compaction_point(), Some("0002"));
+    }
+
+    #[test]
+    fn test_merge_takes_each_setting_from_its_last_change() {
+        use crate::storage::settings::setting_field;
+
+        let mut local = ReminderStore::default();
+        local.settings.trash_retention_days = 7;
+        local.settings.modified.insert(
+            setting_field::TRASH_RETENTION_DAYS.to_string(),
+            "2024-01-02T00:00:00Z/0/device-a".to_string(),
+        );
+        // The other device shortened tombstone retention, and changed the
+        // trash retention before this device did
+        let mut cloud = ReminderStore::default();
+        cloud.settings.tombstone_retention_days = 14;
+        cloud.settings.modified.insert(
+            setting_field::TOMBSTONE_RETENTION_DAYS.to_string(),
+            "2024-01-03T00:00:00Z/0/device-b".to_string(),
+        );
+        cloud.settings.trash_retention_days = 60;
+        cloud.settings.modified.insert(
+            setting_field::TRASH_RETENTION_DAYS.to_string(),
+            "2024-01-01T00:00:00Z/0/device-b".to_string(),
+        );
+
+        for merged in [merge_stores(&local, &cloud), merge_stores(&cloud, &local)] {
+            assert_eq!(merged.settings.tombstone_retention_days, 14);
+            assert_eq!(merged.settings.trash_retention_days, 7);
+            assert_eq!(
+                merged
+                    .settings
+                    .field_modified(setting_field::TOMBSTONE_RETENTION_DAYS),
+                "2024-01-03T00:00:00Z/0/device-b"
+            );
+        }
+    }
+
+    fn make_trashed(id: i64, deleted_at: &str) -> TrashedReminder {
+        TrashedReminder {
+            reminder: make_reminder(id, "2024-01-01T00:00:00Z"),
+            list: ListType::Actual,
+            deleted_at: deleted_at.to_string(),
+            device: "other-device".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_trash_drops_restored_reminders() {
+        let local = ReminderStore {
+            trash: vec![make_trashed(1, "2024-01-05T00:00:00Z")],
+            ..Default::default()
+        };
+        // Restored on the other device, and deleted again there
+        let mut restored = make_reminder(1, "2024-01-01T00:00:00Z");
+        restored.updated_at = Some("2024-01-06T00:00:00Z".to_string());
+        let cloud = ReminderStore {
+            pending: vec![restored],
+            trash: vec![make_trashed(2, "2024-01-07T00:00:00Z")],
+            ..Default::default()
+        };
+
+        let merged = merge_stores(&local, &cloud);
+        assert_eq!(merged.pending.len(), 1);
+        assert_eq!(merged.trash.len(), 1);
+        assert_eq!(merged.trash[0].reminder.id, "2");
+    }
+
+    #[test]
+    fn test_purge_tombstones_after_retention() {
+        let now = Utc::now();
+        let mut store = ReminderStore {
+            tombstones: vec![
+                make_tombstone("1", &(now - Duration::days(100)).to_rfc3339()),
+                make_tombstone("2", &(now - Duration::days(10)).to_rfc3339()),
+            ],
+            ..Default::default()
+        };
+        store.settings.tombstone_retention_days = 30;
+
+        assert_eq!(store.purge_tombstones(now), 1);
+        assert_eq!(store.tombstones.len(), 1);
+        assert_eq!(store.tombstones[0].id, "2");
+    }
+
+    #[test]
+    fn test_purge_trash_after_retention() {
+        let now = Utc::now();
+        let mut store = ReminderStore {
+            trash: vec![
+                make_trashed(1, &(now - Duration::days(31)).to_rfc3339()),
+                make_trashed(2, &(now - Duration::days(1)).to_rfc3339()),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(store.purge_trash(now), 1);
+        assert_eq!(store.trash.len(), 1);
+        assert_eq!(store.trash[0].reminder.id, "2");
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Change {
+        Nothing,
+        Add,
+        Edit,
+        Complete,
+        Uncomplete,
+        Delete,
+    }
+
+    const CHANGES: [Change; 6] = [
+        Change::Nothing,
+        Change::Add,
+        Change::Edit,
+        Change::Complete,
+        Change::Uncomplete,
+        Change::Delete,
+    ];
+    const DELETED_AT: &str = "2024-02-01T00:00:00Z";
+    const CHANGED_AT: &str = "2024-02-02T00:00:00Z";
+
+    /// Synced store with a pending reminder "p" and a completed one "c"
+    fn three_way_base() -> ReminderStore {
+        let mut pending = make_reminder(1, "2024-01-01T00:00:00Z");
+        pending.id = "p".to_string();
+        pending.message = "Task p".to_string();
+        let mut completed = make_reminder(2, "2024-01-01T00:00:00Z");
+        completed.id = "c".to_string();
+        completed.message = "Task c".to_string();
+        completed.is_completed = true;
+        completed.completed_at = Some("2024-01-01T12:00:00Z".to_string());
+        ReminderStore {
+            pending: vec![pending],
+            completed: vec![completed],
+            ..Default::default()
+        }
+    }
+
+    /// Make `change` to a copy of the base: Add adds a reminder, Edit renames
+    /// both reminders, Complete completes "p", Uncomplete reopens "c" and
+    /// Delete deletes both
+    fn change_side(change: Change, side: &str) -> ReminderStore {
+        let mut store = three_way_base();
+        let stamp = |r: &mut Reminder, name: &str| {
+            r.modified.insert(name.to_string(), CHANGED_AT.to_string());
+            r.updated_at = Some(CHANGED_AT.to_string());
+        };
+        match change {
+            Change::Nothing => {}
+            Change::Add => {
+                let mut added = make_reminder(3, CHANGED_AT);
+                added.id = format!("added-{}", side);
+                store.pending.push(added);
+            }
+            Change::Edit => {
+                for r in store.pending.iter_mut().chain(store.completed.iter_mut()) {
+                    r.message = format!("{} {}", r.id, side);
+                    stamp(r, field::MESSAGE);
+                }
+            }
+            Change::Complete => {
+                let mut r = store.pending.remove(0);
+                r.is_completed = true;
+                r.completed_at = Some(CHANGED_AT.to_string());
+                stamp(&mut r, field::COMPLETION);
+                store.completed.push(r);
+            }
+            Change::Uncomplete => {
+                let mut r = store.completed.remove(0);
+                r.is_completed = false;
+                r.completed_at = None;
+                stamp(&mut r, field::COMPLETION);
+                store.pending.push(r);
+            }
+            Change::Delete => {
+                store.pending.clear();
+                store.completed.clear();
+                store.tombstones = vec![
+                    make_tombstone("p", DELETED_AT),
+                    make_tombstone("c", DELETED_AT),
+                ];
+            }
+        }
+        store
+    }
+
+    fn find<'a>(store: &'a ReminderStore, id: &str) -> Option<&'a Reminder> {
+        store
+            .pending
+            .iter()
+            .chain(store.completed.iter())
+            .find(|r| r.id == id)
+    }
+
+    #[test]
+    fn test_three_way_merge_of_every_pair_of_changes() {
+        let base = three_way_base();
+        for local_change in CHANGES {
+            for cloud_change in CHANGES {
+                let context = format!("local {:?}, cloud {:?}", local_change, cloud_change);
+                let local = change_side(local_change, "local");
+                let cloud = change_side(cloud_change, "cloud");
+                let merged = merge_three_way(&base, &local, &cloud);
+                assert!(integrity::verify(&merged).is_empty(), "{}", context);
+
+                let either = |change: Change| local_change == change || cloud_change == change;
+                // A deletion loses only to a later change on the other side
+                let survives = |changes: &[Change]| match (local_change, cloud_change) {
+                    (Change::Delete, Change::Delete) => false,
+                    (Change::Delete, other) | (other, Change::Delete) => changes.contains(&other),
+                    _ => true,
+                };
+                let message = |id: &str| match (local_change, cloud_change) {
+                    (Change::Edit, _) => format!("{} local", id),
+                    (_, Change::Edit) => format!("{} cloud", id),
+                    _ => format!("Task {}", id),
+                };
+
+                let pending = find(&merged, "p");
+                assert_eq!(
+                    pending.is_some(),
+                    survives(&[Change::Edit, Change::Complete]),
+                    "{}",
+                    context
+                );
+                if let Some(r) = pending {
+                    assert_eq!(r.message, message("p"), "{}", context);
+                    assert_eq!(r.is_completed, either(Change::Complete), "{}", context);
+                }
+
+                let completed = find(&merged, "c");
+                assert_eq!(
+                    completed.is_some(),
+                    survives(&[Change::Edit, Change::Uncomplete]),
+                    "{}",
+                    context
+                );
+                if let Some(r) = completed {
+                    assert_eq!(r.message, message("c"), "{}", context);
+                    assert_eq!(r.is_completed, !either(Change::Uncomplete), "{}", context);
+                }
+
+                assert_eq!(
+                    find(&merged, "added-local").is_some(),
+                    local_change == Change::Add,
+                    "{}",
+                    context
+                );
+                assert_eq!(
+                    find(&merged, "added-cloud").is_some(),
+                    cloud_change == Change::Add,
+                    "{}",
+                    context
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_three_way_merge_applies_later_deletion_of_edited_reminder() {
+        let base = three_way_base();
+        let local = change_side(Change::Edit, "local");
+        let mut cloud = change_side(Change::Delete, "cloud");
+        for t in cloud.tombstones.iter_mut() {
+            t.deleted_at = "2024-02-03T00:00:00Z".to_string();
+        }
+
+        let merged = merge_three_way(&base, &local, &cloud);
+        assert!(find(&merged, "p").is_none());
+        assert!(find(&merged, "c").is_none());
+        assert_eq!(merged.tombstones.len(), 2);
+    }
+
+    #[test]
+    fn test_three_way_merge_tells_deletion_from_addition() {
+        // The tombstone was purged, so only the base shows "1" was deleted
+        let base = ReminderStore {
+            pending: vec![make_reminder(1, "2024-01-01T00:00:00Z")],
+            ..Default::default()
+        };
+        let local = ReminderStore::default();
+        let cloud = ReminderStore {
+            pending: vec![
+                make_reminder(1, "2024-01-01T00:00:00Z"),
+                make_reminder(2, "2024-01-02T00:00:00Z"),
+            ],
+            ..Default::default()
+        };
+
+        let merged = merge_three_way(&base, &local, &cloud);
+        let ids: Vec<&str> = merged.pending.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["2"]);
+        assert_eq!(merge_stores(&local, &cloud).pending.len(), 2);
+    }
+
+    #[test]
+    fn test_three_way_merge_keeps_unstamped_changes_from_both_sides() {
+        let mut original = make_reminder(1, "2024-01-01T00:00:00Z");
+        original.tags = vec!["home".to_string(), "errand".to_string()];
+        let base = ReminderStore {
+            pending: vec![original.clone()],
+            ..Default::default()
+        };
+
+        // Saved by versions that didn't stamp fields; local was saved last
+        let mut local_version = original.clone();
+        local_version.message = "Renamed".to_string();
+        local_version.tags = vec!["home".to_string(), "errand".to_string(), "car".to_string()];
+        local_version.updated_at = Some("2024-01-03T00:00:00Z".to_string());
+        let mut cloud_version = original;
+        cloud_version.list_type = ListType::Backlog;
+        cloud_version.tags = vec!["home".to_string()];
+        cloud_version.updated_at = Some("2024-01-02T00:00:00Z".to_string());
+
+        let local = ReminderStore {
+            pending: vec![local_version],
+            ..Default::default()
+        };
+        let cloud = ReminderStore {
+            pending: vec![cloud_version],
+            ..Default::default()
+        };
+
+        let merged = merge_three_way(&base, &local, &cloud);
+        assert_eq!(merged.pending.len(), 1);
+        assert_eq!(merged.pending[0].message, "Renamed");
+        assert_eq!(merged.pending[0].list_type, ListType::Backlog);
+        assert_eq!(merged.pending[0].tags, vec!["home", "car"]);
+    }
+
+    #[test]
+    fn test_three_way_merge_keeps_moves_from_both_devices() {
+        let base = ReminderStore {
+            pending: (1..=4)
+                .map(|id| Reminder {
+                    sort_order: sort_key::from_integer(id),
+                    ..make_reminder(id, "2024-01-01T00:00:00Z")
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let moved = |store: &ReminderStore, id: &str, key: String| {
+            let mut store = store.clone();
+            let r = store.pending.iter_mut().find(|r| r.id == id).unwrap();
+            r.sort_order = key;
+            r.modified.insert(
+                field::SORT_ORDER.to_string(),
+                "2024-01-02T00:00:00Z".to_string(),
+            );
+            store
+        };
+        let key = |id: i64| sort_key::from_integer(id);
+
+        // Local moves 4 to the top; cloud moves 1 between 3 and 4
+        let local = moved(&base, "4", sort_key::between(None, Some(&key(1))));
+        let cloud = moved(&base, "1", sort_key::between(Some(&key(3)), Some(&key(4))));
+
+        let mut merged = merge_three_way(&base, &local, &cloud).pending;
+        merged.sort_by(|a, b| a.cmp_position(b));
+        let ids: Vec<&str> = merged.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["4", "2", "3", "1"]);
+        // Reminders neither device moved keep their keys
+        assert_eq!(merged[1].sort_order, key(2));
+        assert_eq!(merged[2].sort_order, key(3));
+    }
 }
@@ -0,0 +1,251 @@
+use crate::error::AppError;
+use crate::reminder::unique_legacy_id;
+use crate::sort_key;
+use crate::storage::legacy::{migrate_legacy_reminder, LegacyReminder};
+use crate::storage::merge::ReminderStore;
+use serde_json::Value;
+
+/// Version of the stored data format. When the format changes, bump this and
+/// append the step that converts the previous version to `MIGRATIONS`.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Part of the error for data written by a newer version, which must be left
+/// untouched rather than replaced
+pub const NEWER_VERSION_ERROR: &str = "saved by a newer version of the app";
+
+/// Converts data of one version into the next, on the raw JSON so old
+/// formats don't need Rust types of their own
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` turns version n data into version n + 1
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    migrate_v0_due_times,   // 0 -> 1: reminders with due times become urgency-based
+    migrate_v1_integer_ids, // 1 -> 2: integer ids become globally unique
+    migrate_v2_sort_keys,   // 2 -> 3: integer positions become fractional sort keys
+];
+
+/// Data read from storage, brought up to the current version
+pub struct Migrated {
+    pub store: ReminderStore,
+    pub from_version: u32,
+}
+
+impl Migrated {
+    /// True if the data was stored in an older format and should be saved
+    /// again
+    pub fn was_migrated(&self) -> bool {
+        self.from_version < SCHEMA_VERSION
+    }
+}
+
+/// Parse stored JSON of any version up to the current one
+pub fn parse_store(content: &str) -> Result<Migrated, String> {
+    let value: Value = serde_json::from_str(content)
+        .map_err(|e| AppError::storage(format!("Data is not valid JSON: {}", e)))?;
+    migrate(value)
+}
+
+/// Run the migration steps between the data's version and the current one
+pub fn migrate(mut value: Value) -> Result<Migrated, String> {
+    let from_version = stored_version(&value)?;
+    if from_version > SCHEMA_VERSION {
+        return Err(AppError::storage(format!(
+            "Data was {} (schema version {}, this version reads up to {}). Please update the app.",
+            NEWER_VERSION_ERROR, from_version, SCHEMA_VERSION
+        ))
+        .into());
+    }
+
+    for (version, step) in MIGRATIONS.iter().enumerate().skip(from_version as usize) {
+        step(&mut value).map_err(|e| {
+            AppError::storage(format!(
+                "Failed to migrate data from schema version {}: {}",
+                version, e
+            ))
+        })?;
+    }
+    value["schema_version"] = SCHEMA_VERSION.into();
+
+    let store = serde_json::from_value(value)
+        .map_err(|e| AppError::storage(format!("Data could not be read: {}", e)))?;
+    Ok(Migrated {
+        store,
+        from_version,
+    })
+}
+
+/// The version stored data was written in. Data from before versioning has
+/// none; the original format is recognised by its reminders' `due_time`.
+fn stored_version(value: &Value) -> Result<u32, String> {
+    if !value.is_object() {
+        return Err(AppError::storage("Data is not a reminder store").into());
+    }
+    if let Some(version) = value.get("schema_version") {
+        return version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| {
+                AppError::storage(format!("Invalid schema version: {}", version)).into()
+            });
+    }
+
+    let mut reminders = ["pending", "completed"]
+        .iter()
+        .filter_map(|list| value.get(*list).and_then(Value::as_array))
+        .flatten();
+    if reminders.any(|r| r.get("due_time").is_some()) {
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
+
+/// The pending and completed reminders of a store
+fn reminders_mut(value: &mut Value) -> impl Iterator<Item = &mut Value> {
+    value
+        .as_object_mut()
+        .into_iter()
+        .flat_map(|store| store.iter_mut())
+        .filter(|(key, _)| *key == "pending" || *key == "completed")
+        .filter_map(|(_, list)| list.as_array_mut())
+        .flatten()
+}
+
+fn migrate_v0_due_times(value: &mut Value) -> Result<(), String> {
+    for reminder in reminders_mut(value) {
+        let legacy: LegacyReminder =
+            serde_json::from_value(reminder.clone()).map_err(|e| e.to_string())?;
+        *reminder =
+            serde_json::to_value(migrate_legacy_reminder(legacy)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn migrate_v1_integer_ids(value: &mut Value) -> Result<(), String> {
+    for reminder in reminders_mut(value) {
+        let id = match &reminder["id"] {
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.clone(),
+            other => return Err(format!("Invalid reminder id: {}", other)),
+        };
+        let created_at = reminder["created_at"].as_str().unwrap_or_default();
+        let id = unique_legacy_id(&id, created_at).unwrap_or(id);
+        reminder["id"] = Value::String(id);
+    }
+    Ok(())
+}
+
+fn migrate_v2_sort_keys(value: &mut Value) -> Result<(), String> {
+    for reminder in reminders_mut(value) {
+        let key = match &reminder["sort_order"] {
+            Value::Null => sort_key::FIRST.to_string(),
+            Value::Number(n) => {
+                let n = n
+                    .as_i64()
+                    .ok_or_else(|| format!("Invalid sort order: {}", n))?;
+                sort_key::from_integer(n)
+            }
+            Value::String(key) => key.clone(),
+            other => return Err(format!("Invalid sort order: {}", other)),
+        };
+        reminder["sort_order"] = Value::String(key);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+
+    #[test]
+    fn test_migrations_cover_every_version() {
+        assert_eq!(MIGRATIONS.len(), SCHEMA_VERSION as usize);
+    }
+
+    #[test]
+    fn test_migrates_original_format() {
+        let v0 = r#"{"pending":[{"id":1,"message":"Pay rent","due_time":"2099-01-01T09:00:00Z",
+            "created_at":"2024-01-01T00:00:00Z","recurrence":"","is_completed":false,
+            "original_due_time":null,"completed_at":null,"sort_order":0}],"completed":[]}"#;
+
+        let migrated = parse_store(v0).unwrap();
+        assert_eq!(migrated.from_version, 0);
+        assert!(migrated.was_migrated());
+        let reminder = &migrated.store.pending[0];
+        assert_eq!(reminder.id, "1-1704067200000");
+        assert_eq!(reminder.due_at.as_deref(), Some("2099-01-01T09:00:00Z"));
+        assert_eq!(reminder.list_type, ListType::Actual);
+        assert_eq!(reminder.urgency, Urgency::Whenever);
+        assert_eq!(migrated.store.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrates_date_only_snooze() {
+        let v0 = r#"{"pending":[{"id":1,"message":"Call back","due_time":"2099-01-02",
+            "created_at":"2024-01-01T00:00:00Z","recurrence":"","is_completed":false,
+            "is_snoozed":true,"original_due_time":"2098-12-31","completed_at":null,
+            "sort_order":0}],"completed":[]}"#;
+
+        let migrated = parse_store(v0).unwrap();
+        let reminder = &migrated.store.pending[0];
+        assert_eq!(reminder.due_at.as_deref(), Some("2098-12-31"));
+        let until = reminder.hidden_until.as_deref().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(until).is_ok());
+        assert!(reminder.is_snoozed(chrono::Utc::now()));
+    }
+
+    #[test]
+    fn test_migrates_integer_ids() {
+        let v1 = r#"{"pending":[{"id":3,"message":"Old","urgency":"today",
+            "list_type":"actual","created_at":"2024-01-01T00:00:00Z",
+            "is_completed":false,"completed_at":null,"sort_order":0}],"completed":[]}"#;
+
+        let migrated = parse_store(v1).unwrap();
+        assert_eq!(migrated.from_version, 1);
+        assert_eq!(migrated.store.pending[0].id, "3-1704067200000");
+    }
+
+    #[test]
+    fn test_migrates_integer_sort_orders() {
+        let v2 = r#"{"schema_version":2,"pending":[
+            {"id":"a","message":"First","urgency":"today","list_type":"actual",
+            "created_at":"2024-01-01T00:00:00Z","is_completed":false,"completed_at":null,
+            "sort_order":-1},
+            {"id":"b","message":"Second","urgency":"today","list_type":"actual",
+            "created_at":"2024-01-01T00:00:00Z","is_completed":false,"completed_at":null,
+            "sort_order":10}],"completed":[]}"#;
+
+        let migrated = parse_store(v2).unwrap();
+        assert_eq!(migrated.from_version, 2);
+        let keys: Vec<&str> = migrated
+            .store
+            .pending
+            .iter()
+            .map(|r| r.sort_order.as_str())
+            .collect();
+        assert_eq!(keys, vec!["Zz", "aA"]);
+    }
+
+    #[test]
+    fn test_current_version_is_left_alone() {
+        let store = ReminderStore::default();
+        let content = serde_json::to_string(&store).unwrap();
+
+        let migrated = parse_store(&content).unwrap();
+        assert_eq!(migrated.from_version, SCHEMA_VERSION);
+        assert!(!migrated.was_migrated());
+    }
+
+    #[test]
+    fn test_newer_version_is_refused() {
+        let content = format!(
+            r#"{{"schema_version":{},"pending":[],"completed":[]}}"#,
+            SCHEMA_VERSION + 1
+        );
+
+        let error = parse_store(&content).err().unwrap();
+        assert!(error.starts_with("Storage error:"));
+        assert!(error.contains(NEWER_VERSION_ERROR));
+    }
+}
@@ -1,32 +1,93 @@
+mod archive;
+pub mod backend;
+pub mod backup;
+mod conflicts;
+pub mod crypto;
 mod drive;
+pub mod history;
+pub mod integrity;
 mod legacy;
+pub mod lists;
 mod local;
+#[cfg(test)]
+pub mod memory;
 pub mod merge;
+mod migrations;
 pub mod oauth;
-
-use crate::config::MAX_ACTUAL_TASKS;
-use crate::reminder::{ListType, Reminder, Urgency};
+pub mod oplog;
+pub mod settings;
+mod sqlite;
+mod sync_base;
+
+use crate::clock;
+use crate::config::{
+    BACKUP_INTERVAL_MINUTES, CONFIRM_OPEN_CHECKLIST, MAX_BACKUPS, MAX_NOTES_LENGTH,
+    MAX_SYNC_CONFLICTS, MAX_UNDO_HISTORY, MAX_UPLOAD_ATTEMPTS, MIN_PASSPHRASE_LENGTH,
+    OPLOG_KEEP_ENTRIES, OPLOG_SNAPSHOT_INTERVAL,
+};
+use crate::error::AppError;
+use crate::recurrence::RecurrenceRule;
+use crate::reminder::{
+    field, parse_due_at, ChecklistItem, ListType, Reminder, ReminderId, Urgency,
+};
+use crate::sort_key;
+use backend::{ConditionalSave, Revision, StorageBackend};
 use chrono::{DateTime, Datelike, Timelike, Utc};
-use merge::{merge_stores, ReminderStore};
+use crypto::{Cipher, DataKey, EncryptionConfig};
+use history::{History, HistoryEntry};
+use merge::{latest_tombstones, merge_with_report, ReminderStore, Tombstone};
+use oplog::OpLog;
+use std::collections::HashSet;
 use std::fs;
 use std::path::PathBuf;
 
+pub use backend::{LocalBackendKind, LocalBackendStatus, UploadOutcome};
+pub use backup::BackupInfo;
+pub use conflicts::{ConflictChoice, SyncConflict};
+pub use crypto::EncryptionStatus;
+pub use history::HistoryListing;
+pub use integrity::Finding;
+use lists::list_field;
+pub use lists::ListDefinition;
+pub use merge::TrashedReminder;
 pub use oauth::OAuthCredentials;
+pub use oplog::Operation;
+use settings::setting_field;
+pub use settings::{ChecklistPolicy, StoreSettings};
+
+/// Connected, unsynced changes, last sync time, last sync error, last upload
+pub type SyncStatus = (
+    bool,
+    bool,
+    Option<String>,
+    Option<String>,
+    Option<UploadOutcome>,
+);
 
 /// Main storage struct managing both local and cloud persistence
 pub struct Storage {
     data: ReminderStore,
     app_data_path: PathBuf,
-    use_drive: bool,
+    device_id: String,
+    local: Box<dyn StorageBackend>,
+    local_kind: LocalBackendKind,
+    local_error: Option<String>,
+    remote: Option<Box<dyn StorageBackend>>, // Cloud sync target, if connected
+    remote_revision: Option<Revision>,       // Remote revision our data was last synced with
+    sync_base: Option<ReminderStore>,        // Data as last synced with the remote
+    conflicts: Vec<SyncConflict>,            // Recent sync conflicts, oldest first
     cloud_dirty: bool,
     last_sync_time: Option<String>,
     last_sync_error: Option<String>,
-    access_token: Option<String>,
-    refresh_token: Option<String>,
-    client_id: Option<String>,
-    client_secret: Option<String>,
-    folder_id: Option<String>,
-    file_id: Option<String>,
+    last_upload: Option<UploadOutcome>,
+    history: History, // Undo/redo for this session
+    oplog: OpLog,
+    log_base: Vec<Reminder>,   // Reminders as of the last logged operation
+    meta_base: String,         // Settings, lists, tombstones and trash as of the last snapshot
+    ops_since_snapshot: usize, // Logged operations the local snapshot doesn't include
+    remote_ops_pushed: Option<String>, // Newest logged operation pushed to the remote
+    cipher: Cipher,
+    locked: bool, // Data is encrypted and the passphrase hasn't been entered yet
 }
 
 impl Storage {
@@ -36,37 +97,90 @@ impl Storage {
             .join("ReminderApp");
 
         fs::create_dir_all(&app_data_path).map_err(|e| e.to_string())?;
+        let device_id = local::load_or_create_device_id(&app_data_path)?;
+        let mut local_kind = local::load_backend_kind(&app_data_path);
+        let mut local_error = None;
+        let local: Box<dyn StorageBackend> = match local_kind {
+            LocalBackendKind::Sqlite => match sqlite::SqliteBackend::open(&app_data_path) {
+                Ok(backend) => Box::new(backend),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to open reminders.db, falling back to reminders.json: {}",
+                        e
+                    );
+                    local_kind = LocalBackendKind::Json;
+                    local_error = Some(e);
+                    Box::new(local::LocalJsonBackend::new(app_data_path.clone()))
+                }
+            },
+            LocalBackendKind::Json => Box::new(local::LocalJsonBackend::new(app_data_path.clone())),
+        };
+
+        let mut storage = Self::with_backend(app_data_path, device_id, local);
+        storage.local_kind = local_kind;
+        storage.local_error = local_error;
+
+        // Encrypted data is only loaded once the passphrase is entered
+        if crypto::load_config(&storage.app_data_path)?.is_some() {
+            eprintln!("Data is encrypted, waiting for the passphrase");
+            storage.locked = true;
+            return Ok(storage);
+        }
+
+        storage.load_all()?;
+        Ok(storage)
+    }
+
+    /// Load local data and connect to Drive if possible
+    fn load_all(&mut self) -> Result<(), String> {
+        // Try to initialize Drive storage
+        if let Err(e) = self.init_drive() {
+            eprintln!("Drive initialization failed, using local storage: {}", e);
+            self.remote = None;
+            self.load_local_data()?;
+        }
+        Ok(())
+    }
 
-        let mut storage = Self {
+    /// Storage persisted to `local` only, with nothing loaded yet
+    fn with_backend(
+        app_data_path: PathBuf,
+        device_id: String,
+        local: Box<dyn StorageBackend>,
+    ) -> Self {
+        clock::set_device(&device_id);
+        Self {
             data: ReminderStore::default(),
+            oplog: OpLog::new(&app_data_path),
             app_data_path,
-            use_drive: false,
+            device_id,
+            local,
+            local_kind: LocalBackendKind::Json,
+            local_error: None,
+            remote: None,
+            remote_revision: None,
+            sync_base: None,
+            conflicts: Vec::new(),
             cloud_dirty: false,
             last_sync_time: None,
             last_sync_error: None,
-            access_token: None,
-            refresh_token: None,
-            client_id: None,
-            client_secret: None,
-            folder_id: None,
-            file_id: None,
-        };
-
-        // Try to initialize Drive storage
-        if let Err(e) = storage.init_drive() {
-            eprintln!("Drive initialization failed, using local storage: {}", e);
-            storage.use_drive = false;
-            storage.data = local::load_local(&storage.app_data_path)?;
+            last_upload: None,
+            history: History::new(MAX_UNDO_HISTORY),
+            log_base: Vec::new(),
+            meta_base: String::new(),
+            ops_since_snapshot: 0,
+            remote_ops_pushed: None,
+            cipher: Cipher::default(),
+            locked: false,
         }
-
-        Ok(storage)
     }
 
     fn init_drive(&mut self) -> Result<(), String> {
+        self.require_unlocked()?;
+
         // Load local data first so we can merge with cloud
-        match local::load_local(&self.app_data_path) {
-            Ok(data) => {
-                self.data = data;
+        match self.load_local_data() {
+            Ok(()) => {
                 eprintln!(
                     "Loaded {} local pending, {} local completed",
                     self.data.pending.len(),
@@ -78,43 +192,26 @@ impl Storage {
             }
         }
 
-        // Load OAuth state
-        let oauth_state = oauth::load_oauth_state(&self.app_data_path)?;
-        self.access_token = Some(oauth_state.access_token);
-        self.refresh_token = oauth_state.refresh_token;
-        self.client_id = oauth_state.client_id;
-        self.client_secret = oauth_state.client_secret;
-        self.folder_id = Some(oauth_state.folder_id);
-        self.use_drive = true;
-
-        // Find or create reminders.json in Drive
-        // Clone values to avoid borrow conflicts with refresh_access_token
-        let folder_id = self.folder_id.clone().ok_or("No folder ID")?;
-        let access_token = self.access_token.clone().ok_or("No access token")?;
-
-        match drive::find_or_create_drive_file(&access_token, &folder_id, &self.data) {
-            Ok(file_id) => {
-                self.file_id = Some(file_id);
-            }
-            Err(e) if e.contains("expired") => {
-                eprintln!("Drive file search failed: {}, trying token refresh...", e);
-                self.refresh_access_token()?;
-                let access_token = self.access_token.as_ref().ok_or("No access token")?;
-                self.file_id =
-                    Some(drive::find_or_create_drive_file(access_token, &folder_id, &self.data)?);
-            }
-            Err(e) => return Err(e),
-        }
+        let drive = drive::DriveBackend::connect(&self.app_data_path, &self.data, &self.cipher)?;
+        self.connect_remote(Box::new(drive))
+    }
 
-        // Load from Drive and merge
-        if let Err(e) = self.load_from_drive() {
-            eprintln!("Drive load failed: {}, trying token refresh...", e);
-            self.refresh_access_token()?;
-            self.load_from_drive()?;
+    /// Start syncing with `remote`: merge its data into ours and push the
+    /// result back to both sides
+    fn connect_remote(&mut self, mut remote: Box<dyn StorageBackend>) -> Result<(), String> {
+        eprintln!("Connecting to {}", remote.identity());
+        remote.set_cipher(self.cipher.clone())?;
+        self.sync_base = sync_base::load(&self.app_data_path, &remote.identity(), &self.cipher);
+        self.remote = Some(remote);
+        self.remote_revision = None;
+
+        if let Err(e) = self.load_from_remote() {
+            self.remote = None;
+            return Err(e);
         }
 
         // Push merged data back to cloud and local
-        if let Err(e) = self.save_to_drive() {
+        if let Err(e) = self.save_to_remote() {
             eprintln!("Warning: Failed to sync merged data to cloud: {}", e);
         }
         if let Err(e) = self.save_local() {
@@ -122,7 +219,7 @@ impl Storage {
         }
 
         eprintln!(
-            "Drive sync initialized successfully. Found {} pending, {} completed reminders.",
+            "Cloud sync initialized successfully. Found {} pending, {} completed reminders.",
             self.data.pending.len(),
             self.data.completed.len()
         );
@@ -130,74 +227,353 @@ impl Storage {
         Ok(())
     }
 
-    fn refresh_access_token(&mut self) -> Result<(), String> {
-        let refresh_token = self.refresh_token.as_ref().ok_or("No refresh token")?;
-        let client_id = self.client_id.as_ref().ok_or("No client ID")?;
-        let client_secret = self.client_secret.as_ref().ok_or("No client secret")?;
-
-        let new_token = oauth::refresh_access_token(
-            &self.app_data_path,
-            refresh_token,
-            client_id,
-            client_secret,
-        )?;
-
-        self.access_token = Some(new_token);
-        Ok(())
-    }
-
-    fn load_from_drive(&mut self) -> Result<(), String> {
-        let token = self.access_token.as_ref().ok_or("No access token")?;
-        let file_id = self.file_id.as_ref().ok_or("No file ID")?;
+    fn load_from_remote(&mut self) -> Result<(), String> {
+        // Operations first; the snapshot merge below still picks up changes
+        // from replicas that don't log operations
+        if let Err(e) = self.pull_remote_ops() {
+            eprintln!("Failed to pull operations from remote: {}", e);
+        }
 
-        let cloud_data = drive::load_from_drive(token, file_id)?;
+        let remote = self.remote.as_mut().ok_or("No remote storage")?;
+        let snapshot = remote.load()?;
+        self.remote_revision = snapshot.revision;
+        let cloud_data = snapshot.data;
 
         // Merge cloud data with local data
         let local_count = self.data.pending.len() + self.data.completed.len();
         let cloud_count = cloud_data.pending.len() + cloud_data.completed.len();
 
-        if local_count > 0 && cloud_count > 0 {
+        // Deletions must be merged too, even when no reminders are left
+        let local_has_data = local_count > 0 || !self.data.tombstones.is_empty();
+        let cloud_has_data = cloud_count > 0 || !cloud_data.tombstones.is_empty();
+
+        if local_has_data && cloud_has_data {
             eprintln!(
                 "Merging {} local items with {} cloud items",
                 local_count, cloud_count
             );
-            self.data = merge_stores(&self.data, &cloud_data);
+            let (merged, conflicts) =
+                merge_with_report(self.sync_base.as_ref(), &self.data, &cloud_data);
+            self.data = merged;
+            self.record_conflicts(conflicts);
             eprintln!(
                 "After merge: {} pending, {} completed",
                 self.data.pending.len(),
                 self.data.completed.len()
             );
-        } else if cloud_count > 0 {
-            self.data = cloud_data;
+        } else if cloud_has_data {
+            self.data = ReminderStore {
+                last_op: self.data.last_op.take(),
+                ..cloud_data
+            };
+        }
+        self.observe_stamps();
+
+        Ok(())
+    }
+
+    /// Upload our data unless the remote changed since we last read it. In
+    /// that case merge the remote changes in first and try again, up to
+    /// `MAX_UPLOAD_ATTEMPTS` uploads in all.
+    fn save_to_remote(&mut self) -> Result<(), String> {
+        for attempt in 1..=MAX_UPLOAD_ATTEMPTS {
+            if attempt > 1 {
+                eprintln!(
+                    "Cloud data changed since last sync, merging before upload attempt {}",
+                    attempt
+                );
+                self.load_from_remote()?;
+                self.save_local()?;
+            }
+
+            let remote = self.remote.as_mut().ok_or("No remote storage")?;
+            let expected = self.remote_revision.as_deref();
+            if let ConditionalSave::Saved(revision) =
+                remote.save_if_unchanged(&self.data, expected)?
+            {
+                self.remote_revision = revision;
+                self.record_upload(attempt, true);
+                self.update_sync_base();
+                if let Err(e) = self.push_remote_ops() {
+                    eprintln!("Failed to push operations to remote: {}", e);
+                }
+                return Ok(());
+            }
+        }
+        self.record_upload(MAX_UPLOAD_ATTEMPTS, false);
+        Err(format!(
+            "Cloud data kept changing during sync ({} attempts)",
+            MAX_UPLOAD_ATTEMPTS
+        ))
+    }
+
+    fn record_upload(&mut self, attempts: u32, saved: bool) {
+        self.last_upload = Some(UploadOutcome {
+            attempts,
+            saved,
+            at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    /// Keep conflict reports for review, replacing older ones for the same
+    /// field
+    fn record_conflicts(&mut self, conflicts: Vec<SyncConflict>) {
+        for conflict in conflicts {
+            eprintln!(
+                "Sync conflict in {} of \"{}\", kept the {:?} version",
+                conflict.field, conflict.message, conflict.resolution
+            );
+            self.conflicts
+                .retain(|c| c.reminder_id != conflict.reminder_id || c.field != conflict.field);
+            self.conflicts.push(conflict);
+        }
+        let excess = self.conflicts.len().saturating_sub(MAX_SYNC_CONFLICTS);
+        self.conflicts.drain(..excess);
+    }
+
+    /// Remember our data as the remote has it now
+    fn update_sync_base(&mut self) {
+        let remote = self.remote_identity();
+        if let Err(e) = sync_base::save(&self.app_data_path, &remote, &self.data, &self.cipher) {
+            eprintln!("Failed to save sync base: {}", e);
+        }
+        self.sync_base = Some(self.data.clone());
+    }
+
+    /// Local data, or the newest readable backup if it can't be read
+    fn load_local_or_backup(&mut self) -> Result<ReminderStore, String> {
+        let error = match self.local.load() {
+            Ok(snapshot) => return Ok(snapshot.data),
+            // Falling back would overwrite data this version can't read
+            Err(e) if e.contains(migrations::NEWER_VERSION_ERROR) => return Err(e),
+            Err(e) if e.contains(crypto::LOCKED_ERROR) => return Err(e),
+            Err(e) => e,
+        };
+        let (name, data) =
+            backup::load_latest_backup(&self.app_data_path, &self.cipher).ok_or(error.clone())?;
+        eprintln!(
+            "Local data unreadable ({}), restored backup {}",
+            error, name
+        );
+        self.local.save(&data)?;
+        Ok(data)
+    }
+
+    /// Load the local snapshot and replay the operations logged after it
+    fn load_local_data(&mut self) -> Result<(), String> {
+        self.data = self.load_local_or_backup()?;
+
+        let ops = match self.oplog.read_after(self.data.last_op.as_deref()) {
+            Ok(ops) => ops,
+            Err(e) => {
+                eprintln!("Failed to read operation log, using snapshot only: {}", e);
+                Vec::new()
+            }
+        };
+        for op in &ops {
+            oplog::apply_operation(&mut self.data, op);
+        }
+        if let Some(last) = ops.last() {
+            eprintln!("Replayed {} logged operations", ops.len());
+            self.data.last_op = Some(last.id.clone());
+        }
+
+        self.ops_since_snapshot = ops.len();
+        self.log_base = self.snapshot_reminders();
+        self.meta_base = self.meta_fingerprint();
+        self.observe_stamps();
+        Ok(())
+    }
+
+    /// Move the clock past every change in our data, so later changes here
+    /// are ordered after them
+    fn observe_stamps(&self) {
+        let live = self.data.pending.iter().chain(self.data.completed.iter());
+        for r in live.chain(self.data.trash.iter().map(|t| &t.reminder)) {
+            for stamp in r.modified.values() {
+                clock::observe(stamp);
+            }
         }
+        for list in &self.data.lists {
+            for stamp in list.modified.values() {
+                clock::observe(stamp);
+            }
+        }
+    }
+
+    /// Settings, lists, tombstones and trash, which change outside the
+    /// operation log
+    fn meta_fingerprint(&self) -> String {
+        serde_json::to_string(&(
+            &self.data.settings,
+            &self.data.lists,
+            &self.data.deleted_lists,
+            &self.data.ops_acknowledged,
+            &self.data.tombstones,
+            &self.data.trash,
+        ))
+        .unwrap_or_default()
+    }
+
+    /// Log the operations made since the last save. The full snapshot is only
+    /// rewritten every `OPLOG_SNAPSHOT_INTERVAL` operations, or when something
+    /// outside the log changed.
+    fn save_local(&mut self) -> Result<(), String> {
+        let current = self.snapshot_reminders();
+        let changes = history::diff(&self.log_base, &current);
+        let ops = oplog::operations_from_changes(&changes, &self.device_id);
+        self.oplog.append(&ops)?;
+        if let Some(last) = ops.last() {
+            self.data.last_op = Some(last.id.clone());
+        }
+        self.ops_since_snapshot += ops.len();
+        self.log_base = current;
+
+        // Archived reminders leave without a logged operation, so other
+        // devices archive them too rather than delete them
+        let archived = self.archive_completed(Utc::now());
+        if archived > 0 {
+            self.log_base = self.snapshot_reminders();
+        }
+
+        let meta = self.meta_fingerprint();
+        if self.ops_since_snapshot >= OPLOG_SNAPSHOT_INTERVAL
+            || meta != self.meta_base
+            || archived > 0
+        {
+            self.compact()?;
+            self.meta_base = meta;
+        }
+
+        if let Err(e) = backup::backup_if_due(
+            &self.app_data_path,
+            &self.data,
+            &self.cipher,
+            Utc::now(),
+            BACKUP_INTERVAL_MINUTES,
+            MAX_BACKUPS,
+        ) {
+            eprintln!("Warning: Failed to back up local data: {}", e);
+        }
+        Ok(())
+    }
 
+    fn snapshot_is_current(&self) -> bool {
+        self.ops_since_snapshot == 0
+    }
+
+    /// Write a snapshot including every logged operation, then cut the log
+    /// back to the newest `OPLOG_KEEP_ENTRIES`. Without a remote no other
+    /// device needs the older ones; with one, only what was pushed and every
+    /// device has read is dropped.
+    fn compact(&mut self) -> Result<(), String> {
+        self.local.save(&self.data)?;
+        self.ops_since_snapshot = 0;
+
+        let cut = match (&self.remote, self.data.ops_compaction_point()) {
+            (None, _) => self.data.last_op.clone().map(|last| (last.clone(), last)),
+            (Some(_), Some(point)) => {
+                let pushed = self.remote_ops_pushed.clone();
+                pushed.map(|last| (last, point.to_string()))
+            }
+            (Some(_), None) => None,
+        };
+        if let Some((last, point)) = cut {
+            if let Err(e) = self.oplog.truncate(&last, &point, OPLOG_KEEP_ENTRIES) {
+                eprintln!("Warning: Failed to cut back the operation log: {}", e);
+            }
+        }
         Ok(())
     }
 
-    fn save_to_drive(&mut self) -> Result<(), String> {
-        let token = self.access_token.as_ref().ok_or("No access token")?.clone();
-        let file_id = self.file_id.as_ref().ok_or("No file ID")?.clone();
+    /// Apply the operations other replicas left on the remote that aren't in
+    /// our log yet
+    fn pull_remote_ops(&mut self) -> Result<(), String> {
+        let Some(remote_ops) = self.remote.as_mut().and_then(|r| r.load_ops()) else {
+            return Ok(());
+        };
+        let remote_ops = remote_ops?;
+
+        // Our own unlogged changes go first, so they aren't mistaken for the
+        // remote's below
+        self.save_local()?;
+
+        // Operations up to the compaction point may be gone from our log, but
+        // they were applied here before
+        let compacted = self.data.ops_compaction_point().map(str::to_string);
+        let known: HashSet<String> = self.oplog.read()?.into_iter().map(|op| op.id).collect();
+        let newest = remote_ops.iter().map(|op| op.id.clone()).max();
+        let mut missing: Vec<Operation> = remote_ops
+            .into_iter()
+            .filter(|op| !known.contains(&op.id))
+            .filter(|op| compacted.as_ref().is_none_or(|point| op.id > *point))
+            .collect();
 
-        match drive::save_to_drive(&token, &file_id, &self.data) {
-            Ok(_) => Ok(()),
-            Err(e) if e.contains("expired") => {
-                self.refresh_access_token()?;
-                let new_token = self.access_token.as_ref().ok_or("No token after refresh")?;
-                drive::save_to_drive(new_token, &file_id, &self.data)
+        // Everything the remote logged is applied here now
+        if let Some(newest) = newest {
+            let acknowledged = self
+                .data
+                .ops_acknowledged
+                .entry(self.device_id.clone())
+                .or_default();
+            if newest > *acknowledged {
+                *acknowledged = newest;
             }
-            Err(e) => Err(e),
         }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        missing.sort_by(|a, b| a.id.cmp(&b.id));
+
+        for op in &missing {
+            oplog::apply_operation(&mut self.data, op);
+        }
+        self.oplog.append(&missing)?;
+        self.data.last_op = missing.last().map(|op| op.id.clone());
+        self.ops_since_snapshot += missing.len();
+        self.log_base = self.snapshot_reminders();
+        eprintln!(
+            "Applied {} operations from {}",
+            missing.len(),
+            self.remote_identity()
+        );
+        Ok(())
+    }
+
+    /// Give the remote the logged operations it hasn't been sent yet
+    fn push_remote_ops(&mut self) -> Result<(), String> {
+        let ops = self.oplog.read_after(self.remote_ops_pushed.as_deref())?;
+        let Some(last) = ops.last().map(|op| op.id.clone()) else {
+            return Ok(());
+        };
+        let compacted = self.data.ops_compaction_point().map(str::to_string);
+        let Some(result) = self
+            .remote
+            .as_mut()
+            .and_then(|r| r.push_ops(&ops, compacted.as_deref()))
+        else {
+            return Ok(());
+        };
+        result?;
+        self.remote_ops_pushed = Some(last);
+        Ok(())
     }
 
-    fn save_local(&self) -> Result<(), String> {
-        local::save_local(&self.app_data_path, &self.data)
+    fn remote_identity(&self) -> String {
+        self.remote
+            .as_ref()
+            .map(|r| r.identity())
+            .unwrap_or_default()
     }
 
     fn save(&mut self) -> Result<(), String> {
+        self.require_unlocked()?;
+        self.data.purge_tombstones(Utc::now());
+        self.data.purge_trash(Utc::now());
         self.save_local()?;
 
-        if self.use_drive {
-            match self.save_to_drive() {
+        if self.remote.is_some() {
+            match self.save_to_remote() {
                 Ok(_) => {
                     self.cloud_dirty = false;
                     self.last_sync_time = Some(Utc::now().to_rfc3339());
@@ -214,45 +590,92 @@ impl Storage {
         Ok(())
     }
 
-    fn next_id(&self) -> i64 {
-        let max_pending = self.data.pending.iter().map(|r| r.id).max().unwrap_or(0);
-        let max_completed = self.data.completed.iter().map(|r| r.id).max().unwrap_or(0);
-        max_pending.max(max_completed) + 1
-    }
-
     // ============ Public API ============
 
     pub fn get_pending_reminders(&self) -> Vec<Reminder> {
         let mut reminders = self.data.pending.clone();
-        reminders.sort_by(|a, b| a.sort_order.cmp(&b.sort_order));
+        reminders.sort_by(|a, b| a.cmp_position(b));
         reminders
     }
 
     pub fn get_actual_reminders(&self) -> Vec<Reminder> {
+        self.get_list_reminders(&ListType::Actual)
+    }
+
+    pub fn get_backlog_reminders(&self) -> Vec<Reminder> {
+        self.get_list_reminders(&ListType::Backlog)
+    }
+
+    /// Pending, non-snoozed reminders of one list, in sort_order
+    pub fn get_list_reminders(&self, list: &ListType) -> Vec<Reminder> {
+        let now = Utc::now();
         let mut reminders: Vec<Reminder> = self
             .data
             .pending
             .iter()
-            .filter(|r| r.list_type == ListType::Actual)
+            .filter(|r| r.list_type == *list && !r.is_snoozed(now))
             .cloned()
             .collect();
-        reminders.sort_by(|a, b| a.sort_order.cmp(&b.sort_order));
+        reminders.sort_by(|a, b| a.cmp_position(b));
         reminders
     }
 
-    pub fn get_backlog_reminders(&self) -> Vec<Reminder> {
+    pub fn get_snoozed_reminders(&self) -> Vec<Reminder> {
+        let now = Utc::now();
+        let mut reminders: Vec<Reminder> = self
+            .data
+            .pending
+            .iter()
+            .filter(|r| r.is_snoozed(now))
+            .cloned()
+            .collect();
+        reminders.sort_by(|a, b| a.hidden_until.cmp(&b.hidden_until));
+        reminders
+    }
+
+    /// Pending reminders carrying `tag` (case-insensitive), in sort_order
+    pub fn get_reminders_by_tag(&self, tag: &str) -> Vec<Reminder> {
         let mut reminders: Vec<Reminder> = self
             .data
             .pending
             .iter()
-            .filter(|r| r.list_type == ListType::Backlog)
+            .filter(|r| r.has_tag(tag.trim()))
             .cloned()
             .collect();
-        reminders.sort_by(|a, b| a.sort_order.cmp(&b.sort_order));
+        reminders.sort_by(|a, b| a.cmp_position(b));
         reminders
     }
 
+    /// All tags in use across pending and completed reminders, sorted
+    pub fn get_all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self
+            .data
+            .pending
+            .iter()
+            .chain(self.data.completed.iter())
+            .flat_map(|r| r.tags.iter())
+        {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+        tags.sort_by_key(|t| t.to_lowercase());
+        tags
+    }
+
     pub fn get_completed_reminders(&self) -> Vec<Reminder> {
+        // The local index lags behind operations not yet compacted into it
+        let indexed = self
+            .snapshot_is_current()
+            .then(|| self.local.query_completed());
+        if let Some(result) = indexed.flatten() {
+            match result {
+                Ok(reminders) => return reminders,
+                Err(e) => eprintln!("Completed query failed, using loaded data: {}", e),
+            }
+        }
+
         let mut reminders = self.data.completed.clone();
         reminders.sort_by(|a, b| {
             let a_time = a.completed_at.as_deref().unwrap_or("");
@@ -262,119 +685,190 @@ impl Storage {
         reminders
     }
 
-    pub fn add_reminder(&mut self, mut reminder: Reminder) -> Result<i64, String> {
-        reminder.id = self.next_id();
-        let id = reminder.id;
-
-        if reminder.list_type == ListType::Actual {
-            let actual_count = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Actual)
-                .count();
+    pub fn add_reminder(&mut self, reminder: Reminder) -> Result<ReminderId, String> {
+        self.require_list(&reminder.list_type)?;
+        let before = self.snapshot_reminders();
+        let label = format!("Add \"{}\"", reminder.message);
+        let id = self.insert_reminder(reminder);
+        self.record_change(label, before);
+        self.save()?;
+        Ok(id)
+    }
 
-            if actual_count >= MAX_ACTUAL_TASKS {
-                self.bump_least_important_to_backlog();
-            }
+    /// Place a new reminder at the top of its list, bumping the list's least
+    /// important reminder if it is full. Does not save.
+    fn insert_reminder(&mut self, mut reminder: Reminder) -> ReminderId {
+        let id = reminder.id.clone();
+        let list = reminder.list_type.clone();
 
-            for r in self.data.pending.iter_mut() {
-                if r.list_type == ListType::Actual {
-                    r.sort_order += 1;
-                }
-            }
-            reminder.sort_order = 0;
-        } else {
-            let min_backlog_order = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Backlog)
-                .map(|r| r.sort_order)
-                .min()
-                .unwrap_or(0);
-            reminder.sort_order = min_backlog_order - 1;
+        if self.is_list_full(&list, None) {
+            self.bump_least_important(&list);
         }
+        reminder.sort_order = self.top_sort_order(&list);
 
         self.data.pending.push(reminder);
-        self.save()?;
-        Ok(id)
+        id
     }
 
-    fn bump_least_important_to_backlog(&mut self) {
-        if let Some(idx) = self
-            .data
+    fn list_definition(&self, list: &ListType) -> Option<&ListDefinition> {
+        self.data.lists.iter().find(|l| l.id == *list)
+    }
+
+    fn require_list(&self, list: &ListType) -> Result<&ListDefinition, String> {
+        self.list_definition(list).ok_or_else(|| {
+            AppError::validation(format!("No list with id {}", list.as_str())).into()
+        })
+    }
+
+    /// Number of pending reminders showing in `list`, not counting `except`.
+    /// Snoozed reminders don't take up a slot until they wake.
+    fn list_count(&self, list: &ListType, except: Option<&str>) -> usize {
+        let now = Utc::now();
+        self.data
             .pending
             .iter()
-            .enumerate()
-            .filter(|(_, r)| r.list_type == ListType::Actual)
-            .max_by_key(|(_, r)| r.sort_order)
-            .map(|(i, _)| i)
-        {
-            self.data.pending[idx].list_type = ListType::Backlog;
-            let min_backlog_order = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Backlog)
-                .map(|r| r.sort_order)
-                .min()
-                .unwrap_or(0);
-            self.data.pending[idx].sort_order = min_backlog_order - 1;
-        }
+            .filter(|r| r.list_type == *list && Some(r.id.as_str()) != except)
+            .filter(|r| !r.is_snoozed(now))
+            .count()
+    }
+
+    fn is_list_full(&self, list: &ListType, except: Option<&str>) -> bool {
+        let count = self.list_count(list, except);
+        self.list_definition(list).is_some_and(|l| l.is_full(count))
+    }
+
+    /// The first and last pending reminder of `list`
+    fn list_ends(&self, list: &ListType) -> (Option<&Reminder>, Option<&Reminder>) {
+        let in_list = || self.data.pending.iter().filter(|r| r.list_type == *list);
+        (
+            in_list().min_by(|a, b| a.cmp_position(b)),
+            in_list().max_by(|a, b| a.cmp_position(b)),
+        )
+    }
+
+    /// Sort key that puts a reminder at the top of `list`. Only the reminder
+    /// given it changes; the rest of the list keeps its keys.
+    fn top_sort_order(&self, list: &ListType) -> String {
+        let (first, _) = self.list_ends(list);
+        sort_key::between(None, first.map(|r| r.sort_order.as_str()))
+    }
+
+    /// Sort key that puts a reminder at the bottom of `list`
+    fn bottom_sort_order(&self, list: &ListType) -> String {
+        let (_, last) = self.list_ends(list);
+        sort_key::between(last.map(|r| r.sort_order.as_str()), None)
+    }
+
+    /// Move the least important reminder of `list` to the top of its overflow
+    /// list. Returns false if the list has no overflow list or no reminders.
+    fn bump_least_important(&mut self, list: &ListType) -> bool {
+        let depth = self.data.lists.len();
+        self.bump_least_important_within(list, depth)
     }
 
-    fn promote_from_backlog_if_room(&mut self) {
-        let actual_count = self
+    /// `bump_least_important`, cascading while overflow lists are full too.
+    /// `depth` stops overflow cycles.
+    fn bump_least_important_within(&mut self, list: &ListType, depth: usize) -> bool {
+        let overflow = match self
+            .list_definition(list)
+            .and_then(|l| l.overflow_list.clone())
+        {
+            Some(overflow) if overflow != *list && depth > 0 => overflow,
+            _ => return false,
+        };
+
+        let now = Utc::now();
+        let idx = self
             .data
             .pending
             .iter()
-            .filter(|r| r.list_type == ListType::Actual)
-            .count();
+            .enumerate()
+            .filter(|(_, r)| r.list_type == *list && !r.is_snoozed(now))
+            .max_by(|(_, a), (_, b)| a.cmp_position(b))
+            .map(|(i, _)| i);
+
+        match idx {
+            Some(idx) => {
+                if self.is_list_full(&overflow, None) {
+                    self.bump_least_important_within(&overflow, depth - 1);
+                }
+                let sort_order = self.top_sort_order(&overflow);
+                let reminder = &mut self.data.pending[idx];
+                reminder.list_type = overflow;
+                reminder.sort_order = sort_order;
+                reminder.touch(&[field::LIST_TYPE, field::SORT_ORDER]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Bump reminders out of `list` until it is within its capacity again
+    fn fit_to_capacity(&mut self, list: &ListType) {
+        let Some(capacity) = self.list_definition(list).and_then(|l| l.capacity) else {
+            return;
+        };
+        while self.list_count(list, None) > capacity {
+            if !self.bump_least_important(list) {
+                break;
+            }
+        }
+    }
+
+    /// Fill a free slot in a list with a capacity from its source list
+    fn promote_if_room(&mut self, list: &ListType) {
+        let source = match self.list_definition(list) {
+            Some(l) if l.capacity.is_some() => match l.source_list.clone() {
+                Some(source) if source != *list => source,
+                _ => return,
+            },
+            _ => return,
+        };
 
-        if actual_count >= MAX_ACTUAL_TASKS {
+        if self.is_list_full(list, None) {
             return;
         }
 
-        let first_backlog_idx = self
+        let now = Utc::now();
+        let first_source_idx = self
             .data
             .pending
             .iter()
             .enumerate()
-            .filter(|(_, r)| r.list_type == ListType::Backlog)
-            .min_by_key(|(_, r)| r.sort_order)
+            .filter(|(_, r)| r.list_type == source && !r.is_snoozed(now))
+            .min_by(|(_, a), (_, b)| a.cmp_position(b))
             .map(|(i, _)| i);
 
-        if let Some(idx) = first_backlog_idx {
-            let max_actual_order = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Actual)
-                .map(|r| r.sort_order)
-                .max()
-                .unwrap_or(-1);
-
-            self.data.pending[idx].list_type = ListType::Actual;
-            self.data.pending[idx].sort_order = max_actual_order + 1;
+        if let Some(idx) = first_source_idx {
+            let sort_order = self.bottom_sort_order(list);
+            let reminder = &mut self.data.pending[idx];
+            reminder.list_type = list.clone();
+            reminder.sort_order = sort_order;
+            reminder.touch(&[field::LIST_TYPE, field::SORT_ORDER]);
         }
     }
 
     pub fn update_reminder(
         &mut self,
-        id: i64,
+        id: &str,
         message: String,
         urgency: Urgency,
     ) -> Result<(), String> {
+        let before = self.snapshot_reminders();
         if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            let label = format!("Edit \"{}\"", reminder.message);
             reminder.message = message;
             reminder.urgency = urgency;
+            reminder.touch(&[field::MESSAGE, field::URGENCY]);
+            self.record_change(label, before);
             self.save()?;
         }
         Ok(())
     }
 
-    pub fn move_reminder(&mut self, id: i64, to_list: ListType) -> Result<(), String> {
+    pub fn move_reminder(&mut self, id: &str, to_list: ListType) -> Result<(), String> {
+        self.require_list(&to_list)?;
+
         let current_list = self
             .data
             .pending
@@ -382,150 +876,485 @@ impl Storage {
             .find(|r| r.id == id)
             .map(|r| r.list_type.clone());
 
-        let _current_list = match current_list {
+        match current_list {
             Some(list) if list == to_list => return Ok(()),
-            Some(list) => list,
+            Some(_) => {}
             None => return Ok(()),
         };
 
-        if to_list == ListType::Actual {
-            let actual_count = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Actual && r.id != id)
-                .count();
-
-            if actual_count >= MAX_ACTUAL_TASKS {
-                self.bump_least_important_to_backlog();
-            }
-
-            for r in self.data.pending.iter_mut() {
-                if r.list_type == ListType::Actual {
-                    r.sort_order += 1;
-                }
-            }
-
-            if let Some(r) = self.data.pending.iter_mut().find(|r| r.id == id) {
-                r.list_type = ListType::Actual;
-                r.sort_order = 0;
-            }
-        } else {
-            let min_backlog_order = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Backlog)
-                .map(|r| r.sort_order)
-                .min()
-                .unwrap_or(0);
-
-            if let Some(r) = self.data.pending.iter_mut().find(|r| r.id == id) {
-                r.list_type = ListType::Backlog;
-                r.sort_order = min_backlog_order - 1;
-            }
+        let before = self.snapshot_reminders();
+        if self.is_list_full(&to_list, Some(id)) {
+            self.bump_least_important(&to_list);
+        }
+        let sort_order = self.top_sort_order(&to_list);
+        let list_name = self
+            .list_definition(&to_list)
+            .map(|l| l.name.clone())
+            .unwrap_or_default();
+
+        let mut label = String::new();
+        if let Some(r) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            label = format!("Move \"{}\" to {}", r.message, list_name);
+            r.list_type = to_list;
+            r.sort_order = sort_order;
+            r.touch(&[field::LIST_TYPE, field::SORT_ORDER]);
         }
 
+        self.record_change(label, before);
         self.save()?;
         Ok(())
     }
 
-    pub fn set_urgency(&mut self, id: i64, urgency: Urgency) -> Result<(), String> {
+    pub fn set_urgency(&mut self, id: &str, urgency: Urgency) -> Result<(), String> {
         if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
             reminder.urgency = urgency;
+            reminder.touch(&[field::URGENCY]);
             self.save()?;
         }
         Ok(())
     }
 
-    pub fn delete_reminder(&mut self, id: i64) -> Result<(), String> {
-        let was_actual = self
-            .data
-            .pending
-            .iter()
-            .find(|r| r.id == id)
-            .map(|r| r.list_type == ListType::Actual)
-            .unwrap_or(false);
-
-        self.data.pending.retain(|r| r.id != id);
-        self.data.completed.retain(|r| r.id != id);
+    /// Set a deadline and derive urgency from the time left until it
+    pub fn set_due_date(&mut self, id: &str, due_at: String) -> Result<(), String> {
+        let due = parse_due_at(&due_at)
+            .ok_or_else(|| AppError::validation(format!("Invalid due date: {}", due_at)))?;
 
-        if was_actual {
-            self.promote_from_backlog_if_room();
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            reminder.due_at = Some(due_at);
+            reminder.urgency = Urgency::from_time_left(due - Utc::now());
+            reminder.touch(&[field::DUE_AT, field::URGENCY]);
+            self.save()?;
         }
-
-        self.save()?;
         Ok(())
     }
 
-    pub fn complete_reminder(&mut self, id: i64) -> Result<(), String> {
-        if let Some(pos) = self.data.pending.iter().position(|r| r.id == id) {
-            let was_actual = self.data.pending[pos].list_type == ListType::Actual;
-            let mut reminder = self.data.pending.remove(pos);
-            reminder.is_completed = true;
-            reminder.completed_at = Some(Utc::now().to_rfc3339());
-            self.data.completed.push(reminder);
-
-            if was_actual {
-                self.promote_from_backlog_if_room();
+    pub fn clear_due_date(&mut self, id: &str) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            if reminder.due_at.take().is_some() {
+                reminder.touch(&[field::DUE_AT]);
+                self.save()?;
             }
-
-            self.save()?;
         }
         Ok(())
     }
 
-    pub fn uncomplete_reminder(&mut self, id: i64) -> Result<(), String> {
+    /// Escalate urgency of pending reminders whose deadline has come closer.
+    /// Never lowers an urgency the user picked. Returns true if anything changed.
+    pub fn refresh_due_urgencies(&mut self) -> Result<bool, String> {
+        let now = Utc::now();
+        let mut changed = false;
+
+        for reminder in self.data.pending.iter_mut() {
+            if let Some(suggested) = reminder.suggested_urgency(now) {
+                if suggested < reminder.urgency {
+                    reminder.urgency = suggested;
+                    reminder.touch(&[field::URGENCY]);
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+
+    /// Hide a pending reminder from its list until the given RFC3339 time
+    pub fn snooze_reminder(&mut self, id: &str, until: String) -> Result<(), String> {
+        let until_dt = DateTime::parse_from_rfc3339(&until)
+            .map_err(|_| AppError::validation(format!("Invalid snooze time: {}", until)))?;
+        if until_dt <= Utc::now() {
+            return Err(AppError::validation("Snooze time must be in the future").into());
+        }
+
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            reminder.hidden_until = Some(until);
+            reminder.touch(&[field::HIDDEN_UNTIL]);
+            // Its slot is free until it wakes
+            let list = reminder.list_type.clone();
+            self.promote_if_room(&list);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn unsnooze_reminder(&mut self, id: &str) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            if reminder.hidden_until.take().is_some() {
+                reminder.touch(&[field::HIDDEN_UNTIL]);
+                let list = reminder.list_type.clone();
+                self.fit_to_capacity(&list);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore reminders whose snooze has run out. Returns the ids that woke up.
+    pub fn wake_snoozed_reminders(&mut self) -> Result<Vec<ReminderId>, String> {
+        let now = Utc::now();
+        let mut woken = Vec::new();
+        let mut lists = Vec::new();
+
+        for reminder in self.data.pending.iter_mut() {
+            if reminder.hidden_until.is_some() && !reminder.is_snoozed(now) {
+                reminder.hidden_until = None;
+                reminder.touch(&[field::HIDDEN_UNTIL]);
+                woken.push(reminder.id.clone());
+                if !lists.contains(&reminder.list_type) {
+                    lists.push(reminder.list_type.clone());
+                }
+            }
+        }
+        // Lists filled up while these were snoozed
+        for list in &lists {
+            self.fit_to_capacity(list);
+        }
+
+        if !woken.is_empty() {
+            self.save()?;
+        }
+        Ok(woken)
+    }
+
+    /// Replace the Markdown notes of a pending or completed reminder
+    pub fn set_notes(&mut self, id: &str, notes: String) -> Result<(), String> {
+        let length = notes.chars().count();
+        if length > MAX_NOTES_LENGTH {
+            return Err(AppError::validation(format!(
+                "Notes are too long ({} characters, maximum is {})",
+                length, MAX_NOTES_LENGTH
+            ))
+            .into());
+        }
+
+        if let Some(reminder) = self.find_reminder_mut(id) {
+            if reminder.notes != notes {
+                reminder.notes = notes;
+                reminder.touch(&[field::NOTES]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append a checklist item to a pending reminder, returning the item id
+    pub fn add_checklist_item(&mut self, id: &str, text: String) -> Result<u32, String> {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Err(AppError::validation("Checklist item cannot be empty").into());
+        }
+
+        let reminder = self
+            .data
+            .pending
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| AppError::validation(format!("No pending reminder with id {}", id)))?;
+        let item_id = reminder
+            .checklist
+            .iter()
+            .map(|item| item.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        reminder.checklist.push(ChecklistItem::new(item_id, text));
+        reminder.touch(&[field::CHECKLIST]);
+
+        self.save()?;
+        Ok(item_id)
+    }
+
+    pub fn toggle_checklist_item(&mut self, id: &str, item_id: u32) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            if let Some(item) = reminder
+                .checklist
+                .iter_mut()
+                .find(|item| item.id == item_id)
+            {
+                let done = !item.done;
+                item.set_done(done);
+                reminder.touch(&[field::CHECKLIST]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reorder checklist items; items missing from `ordered_item_ids` keep
+    /// their relative order after the listed ones
+    pub fn reorder_checklist_items(
+        &mut self,
+        id: &str,
+        ordered_item_ids: Vec<u32>,
+    ) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            let position = |item: &ChecklistItem| {
+                ordered_item_ids
+                    .iter()
+                    .position(|&item_id| item_id == item.id)
+                    .unwrap_or(usize::MAX)
+            };
+            reminder.checklist.sort_by_key(position);
+            reminder.touch(&[field::CHECKLIST]);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn delete_checklist_item(&mut self, id: &str, item_id: u32) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            let before = reminder.checklist.len();
+            reminder.checklist.retain(|item| item.id != item_id);
+            if reminder.checklist.len() != before {
+                reminder.touch(&[field::CHECKLIST]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_tag(&mut self, id: &str, tag: String) -> Result<(), String> {
+        let tag = normalize_tag(&tag)?;
+        if let Some(reminder) = self.find_reminder_mut(id) {
+            if !reminder.has_tag(&tag) {
+                reminder.tags.push(tag);
+                reminder.touch(&[field::TAGS]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_tag(&mut self, id: &str, tag: String) -> Result<(), String> {
+        let tag = tag.trim().to_string();
+        if let Some(reminder) = self.find_reminder_mut(id) {
+            let before = reminder.tags.len();
+            reminder.tags.retain(|t| !t.eq_ignore_ascii_case(&tag));
+            if reminder.tags.len() != before {
+                reminder.touch(&[field::TAGS]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename a tag on every pending and completed reminder. Returns how many
+    /// reminders were changed.
+    pub fn rename_tag(&mut self, from: String, to: String) -> Result<usize, String> {
+        let from = from.trim().to_string();
+        let to = normalize_tag(&to)?;
+        let mut changed = 0;
+
+        for reminder in self
+            .data
+            .pending
+            .iter_mut()
+            .chain(self.data.completed.iter_mut())
+        {
+            if !reminder.has_tag(&from) {
+                continue;
+            }
+            let renamed_at = reminder
+                .tags
+                .iter()
+                .position(|t| t.eq_ignore_ascii_case(&from));
+            reminder
+                .tags
+                .retain(|t| !t.eq_ignore_ascii_case(&from) && !t.eq_ignore_ascii_case(&to));
+            let index = renamed_at.unwrap_or(0).min(reminder.tags.len());
+            reminder.tags.insert(index, to.clone());
+            reminder.touch(&[field::TAGS]);
+            changed += 1;
+        }
+
+        if changed > 0 {
+            self.save()?;
+        }
+        Ok(changed)
+    }
+
+    fn find_reminder_mut(&mut self, id: &str) -> Option<&mut Reminder> {
+        self.data
+            .pending
+            .iter_mut()
+            .chain(self.data.completed.iter_mut())
+            .find(|r| r.id == id)
+    }
+
+    /// Move a reminder to the trash
+    pub fn delete_reminder(&mut self, id: &str) -> Result<(), String> {
+        let snapshot = self.snapshot_reminders();
+        let Some(reminder) = self.take_reminder(id) else {
+            return Ok(());
+        };
+        let label = format!("Delete \"{}\"", reminder.message);
+        let list = (!reminder.is_completed).then(|| reminder.list_type.clone());
+
+        self.trash_reminder(reminder);
+        if let Some(list) = list {
+            self.promote_if_room(&list);
+        }
+
+        self.record_change(label, snapshot);
+        self.save()?;
+        Ok(())
+    }
+
+    /// Complete a pending reminder. Open checklist items are handled according
+    /// to the checklist policy; `force` confirms completion under `Warn`.
+    pub fn complete_reminder(&mut self, id: &str, force: bool) -> Result<(), String> {
+        if let Some(pos) = self.data.pending.iter().position(|r| r.id == id) {
+            let (done, total) = self.data.pending[pos].checklist_progress();
+            let open_items = total - done;
+            if open_items > 0 {
+                match self.data.settings.checklist_policy {
+                    ChecklistPolicy::CompleteAll => {}
+                    ChecklistPolicy::Warn if force => {}
+                    ChecklistPolicy::Warn => {
+                        return Err(AppError::validation(format!(
+                            "{} of {} checklist items are still open; {}",
+                            open_items, total, CONFIRM_OPEN_CHECKLIST
+                        ))
+                        .into());
+                    }
+                    ChecklistPolicy::Refuse => {
+                        return Err(AppError::validation(format!(
+                            "{} of {} checklist items are still open",
+                            open_items, total
+                        ))
+                        .into());
+                    }
+                }
+            }
+
+            let before = self.snapshot_reminders();
+            let list = self.data.pending[pos].list_type.clone();
+            let mut reminder = self.data.pending.remove(pos);
+            let label = format!("Complete \"{}\"", reminder.message);
+            let now = Utc::now();
+            let complete_all = self.data.settings.checklist_policy == ChecklistPolicy::CompleteAll;
+            if open_items > 0 && complete_all {
+                for item in reminder.checklist.iter_mut().filter(|item| !item.done) {
+                    item.set_done(true);
+                }
+                reminder.touch(&[field::CHECKLIST]);
+            }
+            let next = Self::next_occurrence(&reminder, now);
+            reminder.is_completed = true;
+            reminder.completed_at = Some(now.to_rfc3339());
+            reminder.next_occurrence = next.as_ref().map(|next| next.id.clone());
+            reminder.touch(&[field::COMPLETION]);
+            self.data.completed.push(reminder);
+
+            self.promote_if_room(&list);
+
+            if let Some(next) = next {
+                self.insert_reminder(next);
+            }
+
+            self.record_change(label, before);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Build the follow-up reminder for a recurring reminder that was just completed
+    fn next_occurrence(completed: &Reminder, completed_at: DateTime<Utc>) -> Option<Reminder> {
+        let rule = completed.recurrence.as_ref()?;
+        let due_at = rule.next_due(completed.due_at.as_deref(), completed_at)?;
+
+        let mut next = Reminder::new(
+            completed.message.clone(),
+            completed.urgency.clone(),
+            completed.list_type.clone(),
+        );
+        next.urgency = parse_due_at(&due_at)
+            .map(|due| Urgency::from_time_left(due - completed_at))
+            .unwrap_or(next.urgency);
+        next.due_at = Some(due_at);
+        next.recurrence = Some(rule.advanced());
+        next.tags = completed.tags.clone();
+        next.notes = completed.notes.clone();
+        next.checklist = completed
+            .checklist
+            .iter()
+            .map(|item| ChecklistItem::new(item.id, item.text.clone()))
+            .collect();
+        Some(next)
+    }
+
+    /// Set a recurrence rule from an RRULE-style string, anchored on the
+    /// reminder's due date (see `RecurrenceRule::parse`)
+    pub fn set_recurrence(&mut self, id: &str, rule: &str) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            let rule = RecurrenceRule::parse(rule, reminder.due_datetime())
+                .map_err(AppError::validation)?;
+            reminder.recurrence = Some(rule);
+            reminder.touch(&[field::RECURRENCE]);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_recurrence(&mut self, id: &str) -> Result<(), String> {
+        if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == id) {
+            if reminder.recurrence.take().is_some() {
+                reminder.touch(&[field::RECURRENCE]);
+                self.save()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn uncomplete_reminder(&mut self, id: &str) -> Result<(), String> {
         if let Some(pos) = self.data.completed.iter().position(|r| r.id == id) {
+            let before = self.snapshot_reminders();
             let mut reminder = self.data.completed.remove(pos);
+            let label = format!("Uncomplete \"{}\"", reminder.message);
             reminder.is_completed = false;
             reminder.completed_at = None;
+            // The next occurrence is due again only once this one is done
+            if let Some(next) = reminder.next_occurrence.take() {
+                self.discard_occurrence(&next);
+            }
 
-            let actual_count = self
-                .data
-                .pending
-                .iter()
-                .filter(|r| r.list_type == ListType::Actual)
-                .count();
-
-            if actual_count < MAX_ACTUAL_TASKS {
-                reminder.list_type = ListType::Actual;
-                for r in self.data.pending.iter_mut() {
-                    if r.list_type == ListType::Actual {
-                        r.sort_order += 1;
-                    }
+            // Custom lists take their reminders back; everything else returns to
+            // Actual. A full list passes it on to its overflow list.
+            let mut list = match reminder.list_type {
+                ListType::Custom(_) if self.list_definition(&reminder.list_type).is_some() => {
+                    reminder.list_type.clone()
+                }
+                _ => ListType::Actual,
+            };
+            if self.is_list_full(&list, None) {
+                if let Some(overflow) = self
+                    .list_definition(&list)
+                    .and_then(|l| l.overflow_list.clone())
+                {
+                    list = overflow;
                 }
-                reminder.sort_order = 0;
-            } else {
-                reminder.list_type = ListType::Backlog;
-                let min_backlog = self
-                    .data
-                    .pending
-                    .iter()
-                    .filter(|r| r.list_type == ListType::Backlog)
-                    .map(|r| r.sort_order)
-                    .min()
-                    .unwrap_or(0);
-                reminder.sort_order = min_backlog - 1;
             }
+            reminder.sort_order = self.top_sort_order(&list);
+            reminder.list_type = list;
 
+            reminder.touch(&[field::COMPLETION, field::LIST_TYPE, field::SORT_ORDER]);
             self.data.pending.push(reminder);
+            self.record_change(label, before);
             self.save()?;
         }
         Ok(())
     }
 
     pub fn refresh_from_cloud(&mut self) -> Result<bool, String> {
-        if !self.use_drive {
+        if self.remote.is_none() {
             return Ok(false);
         }
 
-        if let Err(_) = self.load_from_drive() {
-            self.refresh_access_token()?;
-            self.load_from_drive()?;
-        }
+        self.load_from_remote()?;
 
-        match self.save_to_drive() {
+        match self.save_to_remote() {
             Ok(_) => {
                 self.cloud_dirty = false;
                 self.last_sync_time = Some(Utc::now().to_rfc3339());
@@ -542,112 +1371,161 @@ impl Storage {
         Ok(true)
     }
 
-    pub fn get_completion_stats(&self) -> (usize, usize) {
-        let now = Utc::now();
-        let today_start = now.date_naive().and_hms_opt(0, 0, 0).unwrap();
-        let week_start =
-            today_start - chrono::Duration::days(now.weekday().num_days_from_monday() as i64);
+    /// Completion times at or after `since` (all if None), from the local
+    /// backend's index when it has one
+    fn completion_times(
+        &self,
+        since: Option<DateTime<Utc>>,
+        include_archive: bool,
+    ) -> Vec<DateTime<Utc>> {
+        let mut times = self.live_completion_times(since);
+        if include_archive {
+            times.extend(self.archived_completion_times(since));
+        }
+        times
+    }
 
-        let today_count = self
-            .data
-            .completed
-            .iter()
-            .filter(|r| {
-                if let Some(ref completed_at) = r.completed_at {
-                    if let Ok(dt) = DateTime::parse_from_rfc3339(completed_at) {
-                        return dt.naive_utc() >= today_start.and_utc().naive_utc();
-                    }
-                }
-                false
-            })
-            .count();
+    fn live_completion_times(&self, since: Option<DateTime<Utc>>) -> Vec<DateTime<Utc>> {
+        let indexed = self
+            .snapshot_is_current()
+            .then(|| self.local.query_completion_times(since));
+        if let Some(result) = indexed.flatten() {
+            match result {
+                Ok(times) => return times,
+                Err(e) => eprintln!("Completion query failed, using loaded data: {}", e),
+            }
+        }
 
-        let week_count = self
-            .data
+        self.data
             .completed
             .iter()
-            .filter(|r| {
-                if let Some(ref completed_at) = r.completed_at {
-                    if let Ok(dt) = DateTime::parse_from_rfc3339(completed_at) {
-                        return dt.naive_utc() >= week_start.and_utc().naive_utc();
-                    }
-                }
-                false
-            })
-            .count();
+            .filter_map(|r| r.completed_at.as_deref())
+            .filter_map(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|dt| dt.with_timezone(&Utc))
+            .filter(|dt| since.is_none_or(|since| *dt >= since))
+            .collect()
+    }
+
+    /// Completion times in the local archive, skipping reminders that are
+    /// still in the completed list too
+    fn archived_completion_times(&self, since: Option<DateTime<Utc>>) -> Vec<DateTime<Utc>> {
+        let live: HashSet<&str> = self.data.completed.iter().map(|r| r.id.as_str()).collect();
+        let mut times = Vec::new();
+        for year in archive::list_years(&self.app_data_path) {
+            if since.is_some_and(|since| year < since.year()) {
+                continue;
+            }
+            match archive::load_year(&self.app_data_path, year, &self.cipher) {
+                Ok(reminders) => times.extend(
+                    reminders
+                        .iter()
+                        .filter(|r| !live.contains(r.id.as_str()))
+                        .filter_map(|r| r.completed_at.as_deref())
+                        .filter_map(|time| DateTime::parse_from_rfc3339(time).ok())
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .filter(|dt| since.is_none_or(|since| *dt >= since)),
+                ),
+                Err(e) => eprintln!("Skipping archive {} in statistics: {}", year, e),
+            }
+        }
+        times
+    }
+
+    /// Completions (today, this week). `include_archive` also counts
+    /// archived reminders.
+    pub fn get_completion_stats(&self, include_archive: bool) -> (usize, usize) {
+        let now = Utc::now();
+        let today_start = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let week_start =
+            today_start - chrono::Duration::days(now.weekday().num_days_from_monday() as i64);
 
-        (today_count, week_count)
+        let this_week = self.completion_times(Some(week_start), include_archive);
+        let today_count = this_week.iter().filter(|dt| **dt >= today_start).count();
+
+        (today_count, this_week.len())
     }
 
     pub fn get_historical_stats(
         &self,
+        include_archive: bool,
     ) -> (Vec<(String, usize)>, Vec<usize>, Vec<usize>, usize) {
         let now = Utc::now();
+        let completions = self.completion_times(None, include_archive);
 
         // Daily completions for past 14 days
         let mut daily_completions: Vec<(String, usize)> = Vec::new();
         for days_ago in (0..14).rev() {
             let date = now.date_naive() - chrono::Duration::days(days_ago);
             let date_str = date.format("%Y-%m-%d").to_string();
-            let count = self
-                .data
-                .completed
+            let count = completions
                 .iter()
-                .filter(|r| {
-                    if let Some(ref completed_at) = r.completed_at {
-                        if let Ok(dt) = DateTime::parse_from_rfc3339(completed_at) {
-                            return dt.date_naive() == date;
-                        }
-                    }
-                    false
-                })
+                .filter(|dt| dt.date_naive() == date)
                 .count();
             daily_completions.push((date_str, count));
         }
 
         // Hourly distribution
         let mut hourly: Vec<usize> = vec![0; 24];
-        for r in &self.data.completed {
-            if let Some(ref completed_at) = r.completed_at {
-                if let Ok(dt) = DateTime::parse_from_rfc3339(completed_at) {
-                    hourly[dt.hour() as usize] += 1;
-                }
-            }
+        for dt in &completions {
+            hourly[dt.hour() as usize] += 1;
         }
 
         // Daily distribution (0=Monday, 6=Sunday)
         let mut daily: Vec<usize> = vec![0; 7];
-        for r in &self.data.completed {
-            if let Some(ref completed_at) = r.completed_at {
-                if let Ok(dt) = DateTime::parse_from_rfc3339(completed_at) {
-                    daily[dt.weekday().num_days_from_monday() as usize] += 1;
-                }
-            }
+        for dt in &completions {
+            daily[dt.weekday().num_days_from_monday() as usize] += 1;
         }
 
-        // Backlog size
-        let backlog_size = self
+        // Backlog size, as the Backlog list shows it
+        let backlog_size = self.list_count(&ListType::Backlog, None);
+
+        (daily_completions, hourly, daily, backlog_size)
+    }
+
+    /// Checklist progress of pending reminders that have a checklist, in
+    /// sort_order: (id, done, total)
+    pub fn get_checklist_stats(&self) -> Vec<(ReminderId, usize, usize)> {
+        let mut reminders: Vec<&Reminder> = self
             .data
             .pending
             .iter()
-            .filter(|r| r.list_type == ListType::Backlog)
-            .count();
-
-        (daily_completions, hourly, daily, backlog_size)
+            .filter(|r| !r.checklist.is_empty())
+            .collect();
+        reminders.sort_by(|a, b| a.cmp_position(b));
+        reminders
+            .into_iter()
+            .map(|r| {
+                let (done, total) = r.checklist_progress();
+                (r.id.clone(), done, total)
+            })
+            .collect()
     }
 
-    pub fn reorder_reminders(&mut self, ordered_ids: Vec<i64>) -> Result<(), String> {
-        for (index, id) in ordered_ids.iter().enumerate() {
+    /// Put pending reminders in the order of `ordered_ids`. Only reminders
+    /// out of place get new sort keys, so a reorder on another device merges
+    /// with this one instead of overwriting it.
+    pub fn reorder_reminders(&mut self, ordered_ids: Vec<ReminderId>) -> Result<(), String> {
+        let before = self.snapshot_reminders();
+        let current: Vec<(ReminderId, String)> = ordered_ids
+            .iter()
+            .filter_map(|id| self.data.pending.iter().find(|r| r.id == *id))
+            .map(|r| (r.id.clone(), r.sort_order.clone()))
+            .collect();
+        let keys: Vec<&str> = current.iter().map(|(_, key)| key.as_str()).collect();
+        for ((id, _), key) in current.iter().zip(sort_key::reorder(&keys)) {
+            let Some(key) = key else { continue };
             if let Some(reminder) = self.data.pending.iter_mut().find(|r| r.id == *id) {
-                reminder.sort_order = index as i64;
+                reminder.sort_order = key;
+                reminder.touch(&[field::SORT_ORDER]);
             }
         }
+        self.record_change("Reorder reminders".to_string(), before);
         self.save()
     }
 
     pub fn sync_to_cloud(&mut self) -> Result<(), String> {
-        if self.use_drive {
-            match self.save_to_drive() {
+        if self.remote.is_some() {
+            match self.save_to_remote() {
                 Ok(_) => {
                     self.cloud_dirty = false;
                     self.last_sync_time = Some(Utc::now().to_rfc3339());
@@ -663,187 +1541,2539 @@ impl Storage {
         Ok(())
     }
 
-    // ============ Sync Status Methods ============
-
-    pub fn retry_cloud_sync(&mut self) -> Result<bool, String> {
-        if !self.use_drive || !self.cloud_dirty {
-            return Ok(false);
-        }
+    // ============ Lists ============
 
-        match self.save_to_drive() {
-            Ok(_) => {
-                self.cloud_dirty = false;
-                self.last_sync_time = Some(Utc::now().to_rfc3339());
-                self.last_sync_error = None;
-                eprintln!("Retry cloud sync succeeded");
-                Ok(true)
-            }
-            Err(e) => {
-                eprintln!("Retry cloud sync still failing: {}", e);
-                self.last_sync_error = Some(e.clone());
-                Err(e)
-            }
-        }
+    pub fn get_lists(&self) -> Vec<ListDefinition> {
+        let mut lists = self.data.lists.clone();
+        lists.sort_by_key(|l| l.sort_order);
+        lists
     }
 
-    pub fn get_sync_status(&self) -> (bool, bool, Option<String>, Option<String>) {
-        (
-            self.use_drive,
-            self.cloud_dirty,
-            self.last_sync_time.clone(),
-            self.last_sync_error.clone(),
-        )
+    /// Create a user-defined list after the existing ones, returning its id
+    pub fn create_list(
+        &mut self,
+        name: String,
+        capacity: Option<usize>,
+    ) -> Result<ListType, String> {
+        let name = self.validate_list_name(&name, None)?;
+        validate_capacity(capacity)?;
+
+        let id = ListType::Custom(uuid::Uuid::now_v7().to_string());
+        let sort_order = self
+            .data
+            .lists
+            .iter()
+            .map(|l| l.sort_order)
+            .max()
+            .unwrap_or(-1)
+            + 1;
+        let mut definition = ListDefinition {
+            capacity,
+            ..ListDefinition::new(id.clone(), name, sort_order)
+        };
+        definition.touch(&list_field::ALL);
+        self.data.lists.push(definition);
+
+        self.save()?;
+        Ok(id)
     }
 
-    pub fn try_reconnect_drive(&mut self) -> Result<bool, String> {
-        if self.use_drive {
-            return Ok(true);
-        }
-        match self.init_drive() {
-            Ok(_) => {
-                eprintln!("Drive reconnection succeeded");
-                Ok(true)
-            }
-            Err(e) => {
-                eprintln!("Drive reconnection failed: {}", e);
-                Err(e)
+    /// Rename a list and change its capacity and overflow/source lists. A list
+    /// shrunk below its current size sends the excess to its overflow list.
+    pub fn update_list(
+        &mut self,
+        list: &ListType,
+        name: String,
+        capacity: Option<usize>,
+        overflow_list: Option<ListType>,
+        source_list: Option<ListType>,
+    ) -> Result<(), String> {
+        self.require_list(list)?;
+        let name = self.validate_list_name(&name, Some(list))?;
+        validate_capacity(capacity)?;
+        for other in overflow_list.iter().chain(source_list.iter()) {
+            self.require_list(other)?;
+            if other == list {
+                return Err(AppError::validation("A list cannot overflow into itself").into());
             }
         }
-    }
 
-    // ============ OAuth Methods ============
+        if let Some(definition) = self.data.lists.iter_mut().find(|l| l.id == *list) {
+            let changed: Vec<&str> = [
+                (list_field::NAME, definition.name != name),
+                (list_field::CAPACITY, definition.capacity != capacity),
+                (
+                    list_field::OVERFLOW_LIST,
+                    definition.overflow_list != overflow_list,
+                ),
+                (
+                    list_field::SOURCE_LIST,
+                    definition.source_list != source_list,
+                ),
+            ]
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(field, _)| field)
+            .collect();
+            definition.name = name;
+            definition.capacity = capacity;
+            definition.overflow_list = overflow_list;
+            definition.source_list = source_list;
+            definition.touch(&changed);
+        }
 
-    pub fn has_oauth_credentials(&self) -> bool {
-        oauth::has_oauth_credentials(&self.app_data_path)
+        self.fit_to_capacity(list);
+        self.save()
+    }
+
+    pub fn reorder_lists(&mut self, ordered_ids: Vec<ListType>) -> Result<(), String> {
+        for (index, id) in ordered_ids.iter().enumerate() {
+            if let Some(list) = self.data.lists.iter_mut().find(|l| l.id == *id) {
+                if list.sort_order != index as i64 {
+                    list.sort_order = index as i64;
+                    list.touch(&[list_field::SORT_ORDER]);
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// Delete a user-defined list. Its pending reminders move to the Backlog.
+    pub fn delete_list(&mut self, list: &ListType) -> Result<(), String> {
+        if self.require_list(list)?.built_in {
+            return Err(AppError::validation("Built-in lists cannot be deleted").into());
+        }
+
+        self.data.lists.retain(|l| l.id != *list);
+        self.data.deleted_lists.retain(|t| t.id != list.as_str());
+        self.data.deleted_lists.push(Tombstone {
+            id: list.as_str().to_string(),
+            deleted_at: clock::now().to_string(),
+            device: self.device_id.clone(),
+        });
+        for other in self.data.lists.iter_mut() {
+            if other.overflow_list.as_ref() == Some(list) {
+                other.overflow_list = None;
+                other.touch(&[list_field::OVERFLOW_LIST]);
+            }
+            if other.source_list.as_ref() == Some(list) {
+                other.source_list = None;
+                other.touch(&[list_field::SOURCE_LIST]);
+            }
+        }
+
+        // Orphans go to the top of the Backlog, in the order they had
+        let mut orphaned: Vec<&Reminder> = self
+            .data
+            .pending
+            .iter()
+            .filter(|r| r.list_type == *list)
+            .collect();
+        orphaned.sort_by(|a, b| a.cmp_position(b));
+        let orphaned: Vec<ReminderId> = orphaned.into_iter().map(|r| r.id.clone()).collect();
+        let (first, _) = self.list_ends(&ListType::Backlog);
+        let keys = sort_key::n_between(None, first.map(|r| r.sort_order.as_str()), orphaned.len());
+        for (id, sort_order) in orphaned.into_iter().zip(keys) {
+            if let Some(r) = self.data.pending.iter_mut().find(|r| r.id == id) {
+                r.list_type = ListType::Backlog;
+                r.sort_order = sort_order;
+                r.touch(&[field::LIST_TYPE, field::SORT_ORDER]);
+            }
+        }
+
+        self.save()
+    }
+
+    /// Trimmed list name that no other list uses (case-insensitive)
+    fn validate_list_name(&self, name: &str, except: Option<&ListType>) -> Result<String, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::validation("List name cannot be empty").into());
+        }
+        if self
+            .data
+            .lists
+            .iter()
+            .any(|l| Some(&l.id) != except && l.name.eq_ignore_ascii_case(name))
+        {
+            return Err(
+                AppError::validation(format!("A list named {} already exists", name)).into(),
+            );
+        }
+        Ok(name.to_string())
+    }
+
+    // ============ Settings ============
+
+    pub fn get_settings(&self) -> StoreSettings {
+        self.data.settings.clone()
+    }
+
+    pub fn set_checklist_policy(&mut self, policy: ChecklistPolicy) -> Result<(), String> {
+        self.data.settings.checklist_policy = policy;
+        self.data.settings.touch(&[setting_field::CHECKLIST_POLICY]);
+        self.save()
+    }
+
+    /// Set how many days deletions are remembered for sync
+    pub fn set_tombstone_retention_days(&mut self, days: u32) -> Result<(), String> {
+        if days == 0 {
+            return Err(AppError::validation("Retention must be at least one day").into());
+        }
+        self.data.settings.tombstone_retention_days = days;
+        self.data
+            .settings
+            .touch(&[setting_field::TOMBSTONE_RETENTION_DAYS]);
+        self.save()
+    }
+
+    /// Set how many days deleted reminders stay in the trash
+    pub fn set_trash_retention_days(&mut self, days: u32) -> Result<(), String> {
+        if days == 0 {
+            return Err(AppError::validation("Retention must be at least one day").into());
+        }
+        self.data.settings.trash_retention_days = days;
+        self.data
+            .settings
+            .touch(&[setting_field::TRASH_RETENTION_DAYS]);
+        self.save()
+    }
+
+    /// Set how many days after completion reminders are archived
+    pub fn set_archive_after_days(&mut self, days: u32) -> Result<(), String> {
+        if days == 0 {
+            return Err(AppError::validation("Archive age must be at least one day").into());
+        }
+        self.data.settings.archive_after_days = days;
+        self.data
+            .settings
+            .touch(&[setting_field::ARCHIVE_AFTER_DAYS]);
+        self.save()
+    }
+
+    // ============ Trash ============
+
+    /// Deleted reminders that can still be restored, most recent first
+    pub fn get_trash(&self) -> Vec<TrashedReminder> {
+        let mut trash = self.data.trash.clone();
+        trash.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        trash
+    }
+
+    /// Bring a reminder back from the trash. Completed reminders return to the
+    /// completed list; pending ones go back on top of the list they were
+    /// deleted from (the Backlog if that list no longer exists), bumping its
+    /// least important reminder if it is full.
+    pub fn restore_from_trash(&mut self, id: &str) -> Result<(), String> {
+        let Some(pos) = self.data.trash.iter().position(|t| t.reminder.id == id) else {
+            return Err(AppError::validation("Reminder is not in the trash").into());
+        };
+        let before = self.snapshot_reminders();
+        let entry = self.data.trash.remove(pos);
+        let mut reminder = entry.reminder;
+        let label = format!("Restore \"{}\"", reminder.message);
+        self.data.tombstones.retain(|t| t.id != id);
+
+        // Stamped now so the restore wins over the deletion in the next merge
+        reminder.touch(&field::ALL);
+        if reminder.is_completed {
+            self.data.completed.push(reminder);
+        } else {
+            reminder.list_type = match self.list_definition(&entry.list) {
+                Some(_) => entry.list,
+                None => ListType::Backlog,
+            };
+            self.insert_reminder(reminder);
+        }
+
+        self.record_change(label, before);
+        self.save()
+    }
+
+    /// Permanently delete everything in the trash. Returns how many reminders
+    /// were removed.
+    pub fn empty_trash(&mut self) -> Result<usize, String> {
+        let count = self.data.trash.len();
+        if count > 0 {
+            self.data.trash.clear();
+            self.save()?;
+        }
+        Ok(count)
+    }
+
+    // ============ Sync Conflicts ============
+
+    /// Recent sync conflicts, newest first
+    pub fn get_sync_conflicts(&self) -> Vec<SyncConflict> {
+        self.conflicts.iter().rev().cloned().collect()
+    }
+
+    /// Keep `choice`'s version of a conflicting field instead of the one the
+    /// merge kept
+    pub fn resolve_conflict(&mut self, id: &str, choice: ConflictChoice) -> Result<(), String> {
+        let Some(pos) = self.conflicts.iter().position(|c| c.id == id) else {
+            return Err(AppError::validation("No sync conflict with that id").into());
+        };
+        if self.conflicts[pos].resolution == choice {
+            return Ok(());
+        }
+        let conflict = self.conflicts[pos].clone();
+        let Some(current) = self
+            .data
+            .pending
+            .iter()
+            .chain(self.data.completed.iter())
+            .find(|r| r.id == conflict.reminder_id)
+            .cloned()
+        else {
+            return Err(AppError::validation("The reminder no longer exists").into());
+        };
+        let mut reminder =
+            conflicts::with_field_value(&current, &conflict.field, conflict.value(choice))?;
+
+        let before = self.snapshot_reminders();
+        let label = format!("Resolve conflict in \"{}\"", reminder.message);
+        self.take_reminder(&reminder.id);
+        // Reopened: the occurrence completing it spawned goes, as on uncomplete
+        if current.is_completed && !reminder.is_completed {
+            if let Some(next) = &current.next_occurrence {
+                if reminder.next_occurrence.as_ref() != Some(next) {
+                    self.discard_occurrence(next);
+                }
+            }
+        }
+        // Completed again, but the occurrence it spawned was discarded when
+        // it was reopened: spawn a new one, as completing it does
+        let mut respawned = None;
+        if reminder.is_completed {
+            let lost = reminder
+                .next_occurrence
+                .as_deref()
+                .is_some_and(|next| self.find_reminder_mut(next).is_none());
+            if lost {
+                let completed_at = reminder
+                    .completed_at
+                    .as_deref()
+                    .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                    .map_or_else(Utc::now, |time| time.with_timezone(&Utc));
+                respawned = Self::next_occurrence(&reminder, completed_at);
+                reminder.next_occurrence = respawned.as_ref().map(|next| next.id.clone());
+            }
+        }
+        // Stamped now so the choice wins the next merge
+        reminder.touch(&[conflict.field.as_str()]);
+        if reminder.is_completed {
+            self.data.completed.push(reminder);
+            if !current.is_completed {
+                self.promote_if_room(&current.list_type);
+            }
+            if let Some(next) = respawned {
+                self.insert_reminder(next);
+            }
+        } else if current.is_completed || reminder.list_type != current.list_type {
+            // Reopened or moved: to the top of its list like any arrival
+            if self.list_definition(&reminder.list_type).is_none() {
+                reminder.list_type = ListType::Backlog;
+            }
+            reminder.touch(&[field::LIST_TYPE, field::SORT_ORDER]);
+            self.insert_reminder(reminder);
+        } else {
+            self.data.pending.push(reminder);
+        }
+
+        self.conflicts[pos].resolution = choice;
+        self.record_change(label, before);
+        self.save()
+    }
+
+    // ============ Integrity ============
+
+    /// Structural problems in the loaded data
+    pub fn verify_store(&self) -> Vec<Finding> {
+        integrity::verify(&self.data)
+    }
+
+    /// Fix what `verify_store` finds and save. Returns what was fixed.
+    pub fn repair_store(&mut self) -> Result<Vec<Finding>, String> {
+        let before = self.snapshot_reminders();
+        let findings = integrity::repair(&mut self.data);
+        if findings.is_empty() {
+            return Ok(findings);
+        }
+        for finding in &findings {
+            eprintln!("Repaired: {}", finding);
+        }
+        self.record_change("Repair reminders".to_string(), before);
+        self.save()?;
+        Ok(findings)
+    }
+
+    // ============ Archive ============
+
+    /// Move completed reminders older than the archive age into the yearly
+    /// archives. They only leave the completed list once the local and the
+    /// remote archive have them; years that fail are retried on the next
+    /// save. Returns how many reminders were archived.
+    fn archive_completed(&mut self, now: DateTime<Utc>) -> usize {
+        let cutoff = now - chrono::Duration::days(self.data.settings.archive_after_days as i64);
+        let mut archived: HashSet<ReminderId> = HashSet::new();
+        for (year, reminders) in archive::due_for_archive(&self.data.completed, cutoff) {
+            let ids: Vec<ReminderId> = reminders.iter().map(|r| r.id.clone()).collect();
+            match self.add_to_archive(year, reminders) {
+                Ok(()) => archived.extend(ids),
+                Err(e) => eprintln!("Failed to archive reminders from {}: {}", year, e),
+            }
+        }
+
+        if !archived.is_empty() {
+            self.data.completed.retain(|r| !archived.contains(&r.id));
+            eprintln!("Archived {} completed reminders", archived.len());
+        }
+        archived.len()
+    }
+
+    fn add_to_archive(&mut self, year: i32, reminders: Vec<Reminder>) -> Result<(), String> {
+        let mut local = archive::load_year(&self.app_data_path, year, &self.cipher)?;
+        if archive::merge_into(&mut local, reminders) {
+            archive::save_year(&self.app_data_path, year, &local, &self.cipher)?;
+        }
+        self.push_archive(year, local, false)
+    }
+
+    /// Add `reminders` to the remote's archive of `year`. `rewrite` uploads
+    /// it even if it already had them, to store it under a new key. The
+    /// upload only goes through if nobody else wrote the archive since it
+    /// was read; otherwise it is read and merged into again, up to
+    /// `MAX_UPLOAD_ATTEMPTS` uploads in all.
+    fn push_archive(
+        &mut self,
+        year: i32,
+        reminders: Vec<Reminder>,
+        rewrite: bool,
+    ) -> Result<(), String> {
+        let Some(remote) = self.remote.as_mut() else {
+            return Ok(());
+        };
+        for _ in 0..MAX_UPLOAD_ATTEMPTS {
+            let Some(stored) = remote.load_archive(year) else {
+                return Ok(());
+            };
+            let stored = stored?;
+            let mut archived = stored.reminders;
+            if !archive::merge_into(&mut archived, reminders.clone()) && !rewrite {
+                return Ok(());
+            }
+            let upload = remote
+                .save_archive_if_unchanged(year, &archived, stored.revision.as_deref())
+                .unwrap_or(Ok(ConditionalSave::Saved(None)))?;
+            if let ConditionalSave::Saved(_) = upload {
+                return Ok(());
+            }
+            eprintln!(
+                "Archive {} changed on the remote during upload, reading it again",
+                year
+            );
+        }
+        Err(format!(
+            "Archive {} kept changing on the remote, gave up after {} attempts",
+            year, MAX_UPLOAD_ATTEMPTS
+        ))
+    }
+
+    /// Years with archived reminders here or on the remote, newest first
+    pub fn get_archive_years(&mut self) -> Vec<i32> {
+        let mut years = archive::list_years(&self.app_data_path);
+        match self.remote.as_mut().and_then(|r| r.archive_years()) {
+            Some(Ok(remote_years)) => years.extend(remote_years),
+            Some(Err(e)) => eprintln!("Failed to list the remote archive: {}", e),
+            None => {}
+        }
+        years.sort_unstable_by(|a, b| b.cmp(a));
+        years.dedup();
+        years
+    }
+
+    /// Archived reminders completed in `year`, most recently completed first.
+    /// Ones only the remote has (archived by another device) are copied to
+    /// the local archive.
+    pub fn get_archived_reminders(&mut self, year: i32) -> Result<Vec<Reminder>, String> {
+        let mut reminders = archive::load_year(&self.app_data_path, year, &self.cipher)?;
+        match self.remote.as_mut().and_then(|r| r.load_archive(year)) {
+            Some(Ok(remote)) => {
+                let missing: Vec<Reminder> = remote
+                    .reminders
+                    .into_iter()
+                    .filter(|r| !reminders.iter().any(|local| local.id == r.id))
+                    .collect();
+                if !missing.is_empty() {
+                    reminders.extend(missing);
+                    archive::save_year(&self.app_data_path, year, &reminders, &self.cipher)?;
+                }
+            }
+            Some(Err(e)) => eprintln!("Failed to read the remote archive {}: {}", year, e),
+            None => {}
+        }
+
+        reminders.sort_by(|a, b| {
+            let a_time = a.completed_at.as_deref().unwrap_or("");
+            let b_time = b.completed_at.as_deref().unwrap_or("");
+            b_time.cmp(a_time)
+        });
+        Ok(reminders)
+    }
+
+    /// Archived reminders whose message, notes or tags contain `query`
+    /// (case-insensitive), most recently completed first
+    pub fn search_archive(&mut self, query: &str) -> Result<Vec<Reminder>, String> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut found = Vec::new();
+        for year in self.get_archive_years() {
+            let reminders = self.get_archived_reminders(year)?;
+            found.extend(reminders.into_iter().filter(|r| archive::matches(r, query)));
+        }
+        Ok(found)
+    }
+
+    /// Rewrite the local and remote archives with the current key
+    fn reseal_archives(&mut self) -> Result<(), String> {
+        archive::reseal_archives(&self.app_data_path, &self.cipher)?;
+        if self.remote.is_none() {
+            return Ok(());
+        }
+        for year in self.get_archive_years() {
+            let reminders = self.get_archived_reminders(year)?;
+            self.push_archive(year, reminders, true)?;
+        }
+        Ok(())
+    }
+
+    // ============ Undo History ============
+
+    /// All reminders, to compare against after a change
+    fn snapshot_reminders(&self) -> Vec<Reminder> {
+        self.data
+            .pending
+            .iter()
+            .chain(self.data.completed.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Record everything that changed since `before` as one undoable action
+    fn record_change(&mut self, label: String, before: Vec<Reminder>) {
+        let changes = history::diff(&before, &self.snapshot_reminders());
+        if !changes.is_empty() {
+            self.history.record(HistoryEntry {
+                label,
+                at: Utc::now().to_rfc3339(),
+                changes,
+            });
+        }
+    }
+
+    /// Put a removed reminder in the trash, with a tombstone so merges don't
+    /// bring it back
+    fn trash_reminder(&mut self, reminder: Reminder) {
+        let deleted_at = self.add_tombstone(&reminder.id).time_string();
+        self.data.trash.retain(|t| t.reminder.id != reminder.id);
+        self.data.trash.push(TrashedReminder {
+            list: reminder.list_type.clone(),
+            reminder,
+            deleted_at,
+            device: self.device_id.clone(),
+        });
+    }
+
+    /// Record a deletion made here so merges don't bring the reminder back.
+    /// Returns the deletion's clock stamp.
+    fn add_tombstone(&mut self, id: &str) -> clock::Stamp {
+        let stamp = clock::now();
+        self.data.tombstones.retain(|t| t.id != id);
+        self.data.tombstones.push(Tombstone {
+            id: id.to_string(),
+            deleted_at: stamp.to_string(),
+            device: self.device_id.clone(),
+        });
+        stamp
+    }
+
+    /// Remove the occurrence a recurring reminder spawned when it was
+    /// completed, unless it has been completed itself since. The app made it,
+    /// not the user, so it doesn't go to the trash.
+    fn discard_occurrence(&mut self, id: &str) {
+        if let Some(pos) = self.data.pending.iter().position(|r| r.id == id) {
+            self.data.pending.remove(pos);
+            self.add_tombstone(id);
+        }
+    }
+
+    fn take_reminder(&mut self, id: &str) -> Option<Reminder> {
+        if let Some(pos) = self.data.pending.iter().position(|r| r.id == id) {
+            return Some(self.data.pending.remove(pos));
+        }
+        let pos = self.data.completed.iter().position(|r| r.id == id)?;
+        Some(self.data.completed.remove(pos))
+    }
+
+    /// Take a reminder from one recorded state (`from`) to another (`to`,
+    /// None = deleted). Only the fields that differ between the two are set,
+    /// and only where the reminder still has its `from` value, so changes
+    /// made since (synced from another device, say) survive. The fields that
+    /// change are stamped now so the restored values win the next merge.
+    fn restore_reminder(&mut self, id: &str, from: Option<&Reminder>, to: Option<&Reminder>) {
+        let current = self.take_reminder(id);
+        let Some(to) = to else {
+            if let Some(current) = current {
+                self.trash_reminder(current);
+            }
+            return;
+        };
+
+        self.data.tombstones.retain(|t| t.id != id);
+        self.data.trash.retain(|t| t.reminder.id != id);
+        let (mut reminder, fields) = match (current, from) {
+            (Some(mut current), Some(from)) => {
+                let changed_since = current.changed_fields(from);
+                let fields: Vec<&str> = from
+                    .changed_fields(to)
+                    .into_iter()
+                    .filter(|name| !changed_since.contains(name))
+                    .collect();
+                for name in &fields {
+                    current.copy_field(to, name);
+                }
+                (current, fields)
+            }
+            (Some(current), None) => {
+                let fields = current.changed_fields(to);
+                let mut reminder = to.clone();
+                reminder.modified = current.modified;
+                reminder.updated_at = current.updated_at;
+                (reminder, fields)
+            }
+            (None, _) => (to.clone(), field::ALL.to_vec()),
+        };
+        if !fields.is_empty() {
+            reminder.touch(&fields);
+        }
+
+        if reminder.is_completed {
+            self.data.completed.push(reminder);
+        } else {
+            self.data.pending.push(reminder);
+        }
+    }
+
+    /// Revert the most recent action, including the reminders it bumped or
+    /// promoted. Returns its label, None if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<Option<String>, String> {
+        let Some(entry) = self.history.pop_undo() else {
+            return Ok(None);
+        };
+        let spawned: Vec<&str> = entry
+            .changes
+            .iter()
+            .filter_map(|c| c.after.as_ref()?.next_occurrence.as_deref())
+            .collect();
+        for change in &entry.changes {
+            if change.before.is_none() && spawned.contains(&change.id.as_str()) {
+                self.discard_occurrence(&change.id);
+            } else {
+                self.restore_reminder(&change.id, change.after.as_ref(), change.before.as_ref());
+            }
+        }
+        let label = entry.label.clone();
+        self.history.undone(entry);
+        self.save()?;
+        Ok(Some(label))
+    }
+
+    /// Reapply the most recently undone action
+    pub fn redo(&mut self) -> Result<Option<String>, String> {
+        let Some(entry) = self.history.pop_redo() else {
+            return Ok(None);
+        };
+        for change in &entry.changes {
+            self.restore_reminder(&change.id, change.before.as_ref(), change.after.as_ref());
+        }
+        let label = entry.label.clone();
+        self.history.redone(entry);
+        self.save()?;
+        Ok(Some(label))
+    }
+
+    pub fn get_history(&self) -> HistoryListing {
+        self.history.listing()
+    }
+
+    /// Logged operations, newest first, optionally only for one reminder
+    pub fn get_audit_log(&self, reminder_id: Option<&str>) -> Result<Vec<Operation>, String> {
+        let mut ops = self.oplog.read()?;
+        if let Some(id) = reminder_id {
+            ops.retain(|op| op.reminder_id == id);
+        }
+        ops.reverse();
+        Ok(ops)
+    }
+
+    // ============ Backup Methods ============
+
+    pub fn list_backups(&self) -> Vec<BackupInfo> {
+        backup::list_backups(&self.app_data_path)
+    }
+
+    /// Replace all data with a backup. The current data is backed up first so
+    /// the restore itself can be undone. The restore counts as a change made
+    /// now: restored reminders and lists are stamped afresh and anything the
+    /// backup lacks is deleted, so syncing doesn't undo it.
+    pub fn restore_backup(&mut self, name: &str) -> Result<(), String> {
+        let mut restored = backup::load_backup(&self.app_data_path, name, &self.cipher)?;
+        backup::create_backup(
+            &self.app_data_path,
+            &self.data,
+            &self.cipher,
+            Utc::now(),
+            MAX_BACKUPS,
+        )?;
+
+        let mut restored_ids = HashSet::new();
+        for reminder in restored
+            .pending
+            .iter_mut()
+            .chain(restored.completed.iter_mut())
+        {
+            reminder.touch(&field::ALL);
+            restored_ids.insert(reminder.id.clone());
+        }
+        let missing: Vec<ReminderId> = self
+            .snapshot_reminders()
+            .into_iter()
+            .map(|r| r.id)
+            .filter(|id| !restored_ids.contains(id))
+            .collect();
+        restored.tombstones = latest_tombstones(&restored.tombstones, &self.data.tombstones);
+        restored
+            .tombstones
+            .retain(|t| !restored_ids.contains(&t.id));
+
+        let deleted_at = clock::now().to_string();
+        for list in &self.data.lists {
+            if !list.built_in && restored.lists.iter().all(|l| l.id != list.id) {
+                restored.deleted_lists.retain(|t| t.id != list.id.as_str());
+                restored.deleted_lists.push(Tombstone {
+                    id: list.id.as_str().to_string(),
+                    deleted_at: deleted_at.clone(),
+                    device: self.device_id.clone(),
+                });
+            }
+        }
+        for list in restored.lists.iter_mut() {
+            list.touch(&list_field::ALL);
+        }
+        restored.last_op = self.data.last_op.clone();
+
+        self.data = restored;
+        for id in &missing {
+            self.add_tombstone(id);
+        }
+        // Undo history describes the data that was replaced
+        self.history = History::new(MAX_UNDO_HISTORY);
+        self.save()
+    }
+
+    // ============ Local Backend ============
+
+    pub fn get_local_backend(&self) -> LocalBackendStatus {
+        LocalBackendStatus {
+            kind: self.local_kind,
+            error: self.local_error.clone(),
+        }
+    }
+
+    /// Move the local data to the `kind` backend and open that one on later
+    /// starts. Only possible once unlocked, so the data is written with the
+    /// cipher it was read with. Returns how many reminders were moved.
+    pub fn switch_local_backend(&mut self, kind: LocalBackendKind) -> Result<usize, String> {
+        self.require_unlocked()?;
+        if kind == self.local_kind {
+            return Err(
+                AppError::validation(format!("Already using {}", self.local.identity())).into(),
+            );
+        }
+
+        let local: Box<dyn StorageBackend> = match kind {
+            LocalBackendKind::Json => {
+                let mut backend = local::LocalJsonBackend::new(self.app_data_path.clone());
+                backend.set_cipher(self.cipher.clone())?;
+                backend.save(&self.data)?;
+                Box::new(backend)
+            }
+            LocalBackendKind::Sqlite => {
+                let mut backend = sqlite::SqliteBackend::open(&self.app_data_path)?;
+                backend.set_cipher(self.cipher.clone())?;
+                backend.import(&self.data)?;
+                Box::new(backend)
+            }
+        };
+        local::save_backend_kind(&self.app_data_path, kind)?;
+        eprintln!("Local data moved to {}", local.identity());
+
+        // Replacing the backend closes the database before it's removed
+        self.local = local;
+        self.local_kind = kind;
+        self.local_error = None;
+        self.ops_since_snapshot = 0;
+        if kind == LocalBackendKind::Json {
+            if let Err(e) = sqlite::remove_database(&self.app_data_path) {
+                eprintln!("Warning: Failed to remove reminders.db: {}", e);
+            }
+        }
+        Ok(self.data.pending.len() + self.data.completed.len())
+    }
+
+    // ============ Encryption Methods ============
+
+    pub fn get_encryption_status(&self) -> EncryptionStatus {
+        EncryptionStatus {
+            enabled: self.locked || self.cipher.is_enabled(),
+            locked: self.locked,
+        }
+    }
+
+    /// Decrypt and load the data after startup
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        if !self.locked {
+            return Ok(());
+        }
+        let config = self.encryption_config()?;
+        let key = config.unlock(passphrase)?;
+        // Enabling was interrupted: what is still plain is read once more
+        self.use_cipher(Cipher::new(Some(key.clone())).accepting_plain(config.migrating))?;
+
+        self.locked = false;
+        if let Err(e) = self.load_all() {
+            self.locked = true;
+            return Err(e);
+        }
+        if config.migrating {
+            self.finish_encryption(key)?;
+        }
+        // Anything attempted while locked was never saved
+        self.history = History::new(MAX_UNDO_HISTORY);
+        Ok(())
+    }
+
+    /// Encrypt all data, locally and in the cloud, with a key derived from
+    /// `passphrase`
+    pub fn enable_encryption(&mut self, passphrase: &str) -> Result<(), String> {
+        self.require_unlocked()?;
+        if self.cipher.is_enabled() {
+            return Err(AppError::validation("Encryption is already enabled").into());
+        }
+        let key = new_key(passphrase)?;
+        // Written first: after a crash halfway, the data is treated as
+        // encrypted, and plain parts are still read until unlocking finishes
+        // encrypting them
+        crypto::save_config(&self.app_data_path, &EncryptionConfig::new(&key, true)?)?;
+        self.finish_encryption(key)
+    }
+
+    /// Encrypt everything under `key`, the one time plain data is read while
+    /// encryption is enabled, and from then on refuse plain data
+    fn finish_encryption(&mut self, key: DataKey) -> Result<(), String> {
+        let cipher = self.cipher.replaced_by(Some(key.clone()));
+        self.reencrypt(cipher.accepting_plain(true))?;
+        self.use_cipher(self.cipher.accepting_plain(false))?;
+        crypto::save_config(&self.app_data_path, &EncryptionConfig::new(&key, false)?)
+    }
+
+    /// Store all data as plain text again
+    pub fn disable_encryption(&mut self, passphrase: &str) -> Result<(), String> {
+        self.require_unlocked()?;
+        self.encryption_config()?.unlock(passphrase)?;
+        self.reencrypt(self.cipher.replaced_by(None))?;
+        crypto::remove_config(&self.app_data_path)
+    }
+
+    /// Re-encrypt all data with a key derived from a new passphrase. Other
+    /// devices syncing the same data need the new passphrase too.
+    pub fn change_passphrase(&mut self, current: &str, new: &str) -> Result<(), String> {
+        self.require_unlocked()?;
+        self.encryption_config()?.unlock(current)?;
+        let key = new_key(new)?;
+        crypto::save_config(&self.app_data_path, &EncryptionConfig::new(&key, false)?)?;
+        self.reencrypt(self.cipher.replaced_by(Some(key)))
+    }
+
+    fn encryption_config(&self) -> Result<EncryptionConfig, String> {
+        crypto::load_config(&self.app_data_path)?
+            .ok_or_else(|| AppError::validation("Encryption is not enabled").into())
+    }
+
+    fn require_unlocked(&self) -> Result<(), String> {
+        if self.locked {
+            return Err(AppError::storage(format!(
+                "Data is {}; enter the passphrase first",
+                crypto::LOCKED_ERROR
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Read and write everything with `cipher` from now on
+    fn use_cipher(&mut self, cipher: Cipher) -> Result<(), String> {
+        self.local.set_cipher(cipher.clone())?;
+        if let Some(remote) = self.remote.as_mut() {
+            remote.set_cipher(cipher.clone())?;
+        }
+        self.oplog.set_cipher(cipher.clone());
+        self.cipher = cipher;
+        Ok(())
+    }
+
+    /// Rewrite all stored data, local and remote, with `cipher`, which should
+    /// still read data under the old key (see `Cipher::replaced_by`), so a
+    /// remote that changed meanwhile can still be merged
+    fn reencrypt(&mut self, cipher: Cipher) -> Result<(), String> {
+        let ops = self.oplog.read()?;
+        self.use_cipher(cipher)?;
+
+        self.oplog.rewrite(&ops)?;
+        self.compact()?;
+        backup::reseal_backups(&self.app_data_path, &self.cipher)?;
+        self.reseal_archives()?;
+        if let Some(base) = &self.sync_base {
+            let remote = self.remote_identity();
+            sync_base::save(&self.app_data_path, &remote, base, &self.cipher)?;
+        }
+
+        if self.remote.is_some() {
+            self.remote_ops_pushed = None;
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    // ============ Sync Status Methods ============
+
+    pub fn retry_cloud_sync(&mut self) -> Result<bool, String> {
+        if self.remote.is_none() || !self.cloud_dirty {
+            return Ok(false);
+        }
+
+        match self.save_to_remote() {
+            Ok(_) => {
+                self.cloud_dirty = false;
+                self.last_sync_time = Some(Utc::now().to_rfc3339());
+                self.last_sync_error = None;
+                eprintln!("Retry cloud sync succeeded");
+                Ok(true)
+            }
+            Err(e) => {
+                eprintln!("Retry cloud sync still failing: {}", e);
+                self.last_sync_error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn get_sync_status(&self) -> SyncStatus {
+        (
+            self.remote.is_some(),
+            self.cloud_dirty,
+            self.last_sync_time.clone(),
+            self.last_sync_error.clone(),
+            self.last_upload.clone(),
+        )
+    }
+
+    pub fn try_reconnect_drive(&mut self) -> Result<bool, String> {
+        if self.remote.is_some() {
+            return Ok(true);
+        }
+        match self.init_drive() {
+            Ok(_) => {
+                eprintln!("Drive reconnection succeeded");
+                Ok(true)
+            }
+            Err(e) => {
+                eprintln!("Drive reconnection failed: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    // ============ OAuth Methods ============
+
+    pub fn has_oauth_credentials(&self) -> bool {
+        oauth::has_oauth_credentials(&self.app_data_path)
     }
 
     pub fn is_logged_in(&self) -> bool {
-        self.use_drive && self.access_token.is_some()
+        self.remote.is_some()
+    }
+
+    pub fn get_oauth_status(&self) -> (bool, bool) {
+        (self.has_oauth_credentials(), self.is_logged_in())
+    }
+
+    pub fn save_oauth_credentials(&self, credentials: &OAuthCredentials) -> Result<(), String> {
+        oauth::save_oauth_credentials(&self.app_data_path, credentials)
+    }
+
+    pub fn get_oauth_credentials(&self) -> Option<OAuthCredentials> {
+        oauth::load_oauth_credentials(&self.app_data_path).ok()
+    }
+
+    pub fn get_app_data_path(&self) -> &std::path::Path {
+        &self.app_data_path
+    }
+
+    pub fn reload_oauth_state(&mut self) -> Result<(), String> {
+        self.init_drive()
+    }
+
+    pub fn get_oauth_url(&self) -> Result<String, String> {
+        oauth::get_oauth_url(&self.app_data_path)
+    }
+
+    pub fn disconnect_drive(&mut self) -> Result<(), String> {
+        oauth::disconnect(&self.app_data_path)?;
+        self.remote = None;
+        self.remote_revision = None;
+        self.last_upload = None;
+        self.sync_base = None;
+        sync_base::remove(&self.app_data_path)?;
+        Ok(())
+    }
+}
+
+/// A key for a new passphrase, if it is long enough
+fn new_key(passphrase: &str) -> Result<DataKey, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(AppError::validation(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LENGTH
+        ))
+        .into());
+    }
+    DataKey::generate(passphrase)
+}
+
+/// Trim a tag and reject empty ones
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(AppError::validation("Tag cannot be empty").into());
+    }
+    Ok(tag.to_string())
+}
+
+/// A list capacity must leave room for at least one reminder
+fn validate_capacity(capacity: Option<usize>) -> Result<(), String> {
+    if capacity == Some(0) {
+        return Err(AppError::validation("List capacity must be at least 1").into());
+    }
+    Ok(())
+}
+
+/// Complete the entire OAuth flow in a blocking context (for use in a separate thread)
+pub fn complete_oauth_flow_blocking(app_data_path: &std::path::Path) -> Result<(), String> {
+    oauth::complete_oauth_flow_blocking(app_data_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::MemoryBackend;
+
+    fn create_test_reminder(id: i64, list_type: ListType, sort_order: i64) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            sort_order: sort_key::from_integer(sort_order),
+            ..Reminder::new(format!("Task {}", id), Urgency::Today, list_type)
+        }
+    }
+
+    /// Scratch directory for one test, removed again when dropped. Names must
+    /// be unique per test; the process id keeps concurrent runs apart.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let _ = fs::create_dir_all(&path);
+            TestDir(path)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Storage over a `TestDir`, which is removed once the storage is dropped
+    struct TestStorage {
+        storage: Storage,
+        _dir: TestDir,
+    }
+
+    impl std::ops::Deref for TestStorage {
+        type Target = Storage;
+
+        fn deref(&self) -> &Storage {
+            &self.storage
+        }
+    }
+
+    impl std::ops::DerefMut for TestStorage {
+        fn deref_mut(&mut self) -> &mut Storage {
+            &mut self.storage
+        }
+    }
+
+    fn create_test_storage(name: &str, store: ReminderStore) -> TestStorage {
+        let dir = TestDir::new(name);
+        let backend = MemoryBackend::default();
+
+        TestStorage {
+            storage: Storage {
+                data: store,
+                ..Storage::with_backend(dir.0.clone(), "test-device".to_string(), Box::new(backend))
+            },
+            _dir: dir,
+        }
+    }
+
+    #[test]
+    fn test_promote_from_backlog_when_room() {
+        let mut store = ReminderStore::default();
+
+        for i in 0..5 {
+            store.pending.push(create_test_reminder(i, ListType::Actual, i));
+        }
+
+        store.pending.push(create_test_reminder(100, ListType::Backlog, 0));
+        store.pending.push(create_test_reminder(101, ListType::Backlog, 1));
+
+        let mut storage = create_test_storage("test_promote", store);
+
+        storage.promote_if_room(&ListType::Actual);
+
+        let actual_count = storage.data.pending.iter()
+            .filter(|r| r.list_type == ListType::Actual)
+            .count();
+
+        assert_eq!(actual_count, 6);
+    }
+
+    #[test]
+    fn test_promoted_task_goes_to_end() {
+        let mut store = ReminderStore::default();
+
+        for i in 0..3 {
+            store.pending.push(create_test_reminder(i, ListType::Actual, i));
+        }
+
+        store.pending.push(create_test_reminder(100, ListType::Backlog, 0));
+
+        let mut storage = create_test_storage("test_promoted_to_end", store);
+
+        storage.promote_if_room(&ListType::Actual);
+
+        let promoted = storage.data.pending.iter().find(|r| r.id == "100").unwrap();
+
+        assert_eq!(promoted.list_type, ListType::Actual);
+        assert!(promoted.sort_order > sort_key::from_integer(2)); // After 0, 1, 2
+    }
+
+    #[test]
+    fn test_placing_a_reminder_changes_only_its_key() {
+        let mut store = ReminderStore::default();
+        for i in 0..3 {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
+        }
+        store
+            .pending
+            .push(create_test_reminder(100, ListType::Backlog, 0));
+        let mut storage = create_test_storage("test_placing_changes_one_key", store);
+        let keys = |storage: &Storage| -> Vec<(ReminderId, String)> {
+            let mut keys: Vec<(ReminderId, String)> = storage
+                .data
+                .pending
+                .iter()
+                .map(|r| (r.id.clone(), r.sort_order.clone()))
+                .collect();
+            keys.sort();
+            keys
+        };
+        let changed = |before: &[(ReminderId, String)], after: &[(ReminderId, String)]| {
+            after
+                .iter()
+                .filter(|key| !before.contains(key))
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let before = keys(&storage);
+        let new = Reminder::new("New".to_string(), Urgency::Today, ListType::Actual);
+        let id = storage.add_reminder(new).unwrap();
+        assert_eq!(changed(&before, &keys(&storage)), vec![id.clone()]);
+        assert_eq!(storage.get_actual_reminders()[0].id, id);
+
+        let before = keys(&storage);
+        storage.move_reminder("100", ListType::Actual).unwrap();
+        assert_eq!(changed(&before, &keys(&storage)), vec!["100"]);
+
+        let before = keys(&storage);
+        let order = vec!["100", "0", "1", &id, "2"];
+        storage
+            .reorder_reminders(order.iter().map(|id| id.to_string()).collect())
+            .unwrap();
+        assert_eq!(changed(&before, &keys(&storage)), vec![id.clone()]);
+        let actual = storage.get_actual_reminders();
+        let ids: Vec<&str> = actual.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, order);
+    }
+
+    #[test]
+    fn test_set_due_date_derives_urgency() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_set_due_date", store);
+
+        let due = (Utc::now() + chrono::Duration::days(3)).to_rfc3339();
+        storage.set_due_date("1", due.clone()).unwrap();
+
+        let reminder = &storage.data.pending[0];
+        assert_eq!(reminder.due_at.as_deref(), Some(due.as_str()));
+        assert_eq!(reminder.urgency, Urgency::Soon);
+
+        assert!(storage.set_due_date("1", "not a date".to_string()).is_err());
+
+        storage.clear_due_date("1").unwrap();
+        assert!(storage.data.pending[0].due_at.is_none());
+    }
+
+    #[test]
+    fn test_refresh_due_urgencies_only_escalates() {
+        let mut store = ReminderStore::default();
+        let mut overdue = create_test_reminder(1, ListType::Actual, 0);
+        overdue.urgency = Urgency::Whenever;
+        overdue.due_at = Some((Utc::now() - chrono::Duration::hours(2)).to_rfc3339());
+        let mut far_off = create_test_reminder(2, ListType::Actual, 1);
+        far_off.urgency = Urgency::Now;
+        far_off.due_at = Some((Utc::now() + chrono::Duration::days(30)).to_rfc3339());
+        store.pending.push(overdue);
+        store.pending.push(far_off);
+        let mut storage = create_test_storage("test_refresh_due_urgencies", store);
+
+        assert!(storage.refresh_due_urgencies().unwrap());
+
+        let by_id = |id: &str| storage.data.pending.iter().find(|r| r.id == id).unwrap();
+        assert_eq!(by_id("1").urgency, Urgency::Now);
+        assert_eq!(by_id("2").urgency, Urgency::Now);
+    }
+
+    #[test]
+    fn test_completing_recurring_reminder_spawns_next() {
+        let mut store = ReminderStore::default();
+        let mut recurring = create_test_reminder(1, ListType::Actual, 0);
+        recurring.due_at = Some(
+            chrono::Local::now()
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string(),
+        );
+        recurring.recurrence = Some(RecurrenceRule::parse("FREQ=DAILY;COUNT=2", None).unwrap());
+        store.pending.push(recurring);
+        let mut storage = create_test_storage("test_recurring_spawns_next", store);
+
+        storage.complete_reminder("1", false).unwrap();
+
+        assert_eq!(storage.data.completed.len(), 1);
+        assert_eq!(storage.data.pending.len(), 1);
+        let next = &storage.data.pending[0];
+        assert_ne!(next.id, "1");
+        assert_eq!(next.message, "Task 1");
+        let tomorrow = chrono::Local::now().date_naive() + chrono::Duration::days(1);
+        assert_eq!(next.due_at, Some(tomorrow.format("%Y-%m-%d").to_string()));
+        assert_eq!(next.recurrence.as_ref().unwrap().occurrence, 2);
+
+        // COUNT=2 reached: completing the second occurrence ends the series
+        let next_id = next.id.clone();
+        storage.complete_reminder(&next_id, false).unwrap();
+        assert!(storage.data.pending.is_empty());
+    }
+
+    #[test]
+    fn test_uncomplete_and_undo_remove_spawned_occurrence() {
+        let mut store = ReminderStore::default();
+        let mut recurring = create_test_reminder(1, ListType::Actual, 0);
+        recurring.due_at = Some(
+            chrono::Local::now()
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string(),
+        );
+        recurring.recurrence = Some(RecurrenceRule::parse("FREQ=DAILY", None).unwrap());
+        store.pending.push(recurring);
+        let mut storage = create_test_storage("test_recurring_spawn_removed", store);
+
+        storage.complete_reminder("1", false).unwrap();
+        let spawned = storage.data.completed[0].next_occurrence.clone().unwrap();
+        assert!(storage.data.pending.iter().any(|r| r.id == spawned));
+        storage.uncomplete_reminder("1").unwrap();
+        assert_eq!(storage.data.pending.len(), 1);
+        assert_eq!(storage.data.pending[0].id, "1");
+        assert!(storage.data.pending[0].next_occurrence.is_none());
+        assert!(storage.data.tombstones.iter().any(|t| t.id == spawned));
+        assert!(storage.data.trash.is_empty());
+
+        storage.complete_reminder("1", false).unwrap();
+        let spawned = storage.data.completed[0].next_occurrence.clone().unwrap();
+        storage.undo().unwrap();
+        assert_eq!(storage.data.pending.len(), 1);
+        assert_eq!(storage.data.pending[0].id, "1");
+        assert!(storage.data.tombstones.iter().any(|t| t.id == spawned));
+        assert!(storage.data.trash.is_empty());
+
+        // Redo brings the same occurrence back
+        storage.redo().unwrap();
+        assert!(storage.data.pending.iter().any(|r| r.id == spawned));
+        assert!(storage.data.tombstones.iter().all(|t| t.id != spawned));
+    }
+
+    #[test]
+    fn test_snoozed_reminders_are_hidden_until_woken() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        store
+            .pending
+            .push(create_test_reminder(2, ListType::Backlog, 0));
+        let mut expired = create_test_reminder(3, ListType::Actual, 1);
+        expired.hidden_until = Some((Utc::now() - chrono::Duration::minutes(1)).to_rfc3339());
+        store.pending.push(expired);
+        let mut storage = create_test_storage("test_snooze", store);
+
+        let later = (Utc::now() + chrono::Duration::hours(2)).to_rfc3339();
+        storage.snooze_reminder("2", later.clone()).unwrap();
+        storage.snooze_reminder("1", later).unwrap();
+
+        assert_eq!(storage.get_actual_reminders().len(), 1);
+        assert!(storage.get_backlog_reminders().is_empty());
+        assert_eq!(storage.get_snoozed_reminders().len(), 2);
+        assert_eq!(storage.get_historical_stats(false).3, 0);
+
+        assert_eq!(storage.wake_snoozed_reminders().unwrap(), vec!["3"]);
+        assert!(storage
+            .data
+            .pending
+            .iter()
+            .find(|r| r.id == "3")
+            .unwrap()
+            .hidden_until
+            .is_none());
+
+        storage.unsnooze_reminder("2").unwrap();
+        assert_eq!(storage.get_backlog_reminders().len(), 1);
+
+        let past = (Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+        assert!(storage.snooze_reminder("1", past).is_err());
+    }
+
+    #[test]
+    fn test_waking_into_full_list_bumps_to_overflow() {
+        let mut store = ReminderStore::default();
+        let max = crate::config::MAX_ACTUAL_TASKS as i64;
+        for i in 0..max {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
+        }
+        let mut expired = create_test_reminder(max, ListType::Actual, 0);
+        expired.hidden_until = Some((Utc::now() - chrono::Duration::minutes(1)).to_rfc3339());
+        store.pending.push(expired);
+        let mut later = create_test_reminder(max + 1, ListType::Actual, 1);
+        later.hidden_until = Some((Utc::now() + chrono::Duration::hours(2)).to_rfc3339());
+        store.pending.push(later);
+        let mut storage = create_test_storage("test_wake_into_full_list", store);
+
+        storage.wake_snoozed_reminders().unwrap();
+        assert_eq!(storage.get_actual_reminders().len(), max as usize);
+        assert_eq!(storage.get_backlog_reminders().len(), 1);
+
+        storage.unsnooze_reminder(&(max + 1).to_string()).unwrap();
+        assert_eq!(storage.get_actual_reminders().len(), max as usize);
+        assert_eq!(storage.get_backlog_reminders().len(), 2);
+    }
+
+    #[test]
+    fn test_snoozing_promotes_from_source_list() {
+        let mut store = ReminderStore::default();
+        let max = crate::config::MAX_ACTUAL_TASKS as i64;
+        for i in 0..max {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
+        }
+        store
+            .pending
+            .push(create_test_reminder(max, ListType::Backlog, 0));
+        let mut storage = create_test_storage("test_snooze_promotes", store);
+
+        let later = (Utc::now() + chrono::Duration::hours(2)).to_rfc3339();
+        storage.snooze_reminder("0", later).unwrap();
+        assert_eq!(storage.get_actual_reminders().len(), max as usize);
+        assert!(storage.get_backlog_reminders().is_empty());
+    }
+
+    #[test]
+    fn test_snoozed_reminders_do_not_fill_a_list() {
+        let mut store = ReminderStore::default();
+        let later = (Utc::now() + chrono::Duration::hours(2)).to_rfc3339();
+        for i in 0..crate::config::MAX_ACTUAL_TASKS as i64 {
+            let mut snoozed = create_test_reminder(i, ListType::Actual, i);
+            snoozed.hidden_until = Some(later.clone());
+            store.pending.push(snoozed);
+        }
+        let mut storage = create_test_storage("test_snoozed_not_counted", store);
+        assert!(!storage.is_list_full(&ListType::Actual, None));
+
+        let new = Reminder::new("Visible".to_string(), Urgency::Today, ListType::Actual);
+        storage.add_reminder(new).unwrap();
+        assert_eq!(storage.get_actual_reminders().len(), 1);
+        assert!(storage.get_backlog_reminders().is_empty());
+    }
+
+    #[test]
+    fn test_tags_add_filter_and_rename() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 1));
+        store
+            .pending
+            .push(create_test_reminder(2, ListType::Backlog, 0));
+        store
+            .pending
+            .push(create_test_reminder(3, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_tags", store);
+
+        storage.add_tag("1", " client-a ".to_string()).unwrap();
+        storage.add_tag("3", "Client-A".to_string()).unwrap();
+        storage.add_tag("3", "client-a".to_string()).unwrap();
+        storage.add_tag("2", "errands".to_string()).unwrap();
+        assert!(storage.add_tag("2", "  ".to_string()).is_err());
+
+        let tagged: Vec<String> = storage
+            .get_reminders_by_tag("client-a")
+            .iter()
+            .map(|r| r.id.clone())
+            .collect();
+        assert_eq!(tagged, vec!["3", "1"]);
+        assert_eq!(storage.get_all_tags(), vec!["client-a", "errands"]);
+
+        assert_eq!(
+            storage
+                .rename_tag("CLIENT-A".to_string(), "acme".to_string())
+                .unwrap(),
+            2
+        );
+        assert_eq!(storage.get_reminders_by_tag("acme").len(), 2);
+        assert!(storage.get_reminders_by_tag("client-a").is_empty());
+
+        storage.remove_tag("2", "Errands".to_string()).unwrap();
+        assert!(storage.get_all_tags().iter().all(|t| t != "errands"));
+    }
+
+    #[test]
+    fn test_set_notes_enforces_length_limit() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_set_notes", store);
+
+        storage
+            .set_notes("1", "- [ ] see https://example.com".to_string())
+            .unwrap();
+        assert_eq!(
+            storage.data.pending[0].notes,
+            "- [ ] see https://example.com"
+        );
+        assert_eq!(storage.data.pending[0].message, "Task 1");
+
+        let at_limit = "é".repeat(MAX_NOTES_LENGTH);
+        storage.set_notes("1", at_limit.clone()).unwrap();
+        assert_eq!(storage.data.pending[0].notes, at_limit);
+
+        let too_long = "x".repeat(MAX_NOTES_LENGTH + 1);
+        let err = storage.set_notes("1", too_long).unwrap_err();
+        assert!(err.starts_with("Validation error"));
+        assert_eq!(storage.data.pending[0].notes, at_limit);
+    }
+
+    #[test]
+    fn test_checklist_items_and_progress() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_checklist_items", store);
+
+        let first = storage
+            .add_checklist_item("1", "Draft".to_string())
+            .unwrap();
+        let second = storage
+            .add_checklist_item("1", "Review".to_string())
+            .unwrap();
+        let third = storage.add_checklist_item("1", "Send".to_string()).unwrap();
+        assert!(storage.add_checklist_item("1", " ".to_string()).is_err());
+
+        storage.toggle_checklist_item("1", second).unwrap();
+        assert_eq!(storage.get_checklist_stats(), vec![("1".to_string(), 1, 3)]);
+
+        storage
+            .reorder_checklist_items("1", vec![third, first])
+            .unwrap();
+        let order: Vec<u32> = storage.data.pending[0]
+            .checklist
+            .iter()
+            .map(|i| i.id)
+            .collect();
+        assert_eq!(order, vec![third, first, second]);
+
+        storage.delete_checklist_item("1", first).unwrap();
+        assert_eq!(storage.get_checklist_stats(), vec![("1".to_string(), 1, 2)]);
+    }
+
+    #[test]
+    fn test_complete_with_open_checklist_follows_policy() {
+        let mut store = ReminderStore::default();
+        let mut reminder = create_test_reminder(1, ListType::Actual, 0);
+        reminder
+            .checklist
+            .push(ChecklistItem::new(1, "Open item".to_string()));
+        store.pending.push(reminder);
+        let mut storage = create_test_storage("test_checklist_policy", store);
+
+        storage
+            .set_checklist_policy(ChecklistPolicy::Refuse)
+            .unwrap();
+        assert!(storage.complete_reminder("1", true).is_err());
+
+        storage.set_checklist_policy(ChecklistPolicy::Warn).unwrap();
+        assert!(storage.complete_reminder("1", false).is_err());
+        assert_eq!(storage.data.pending.len(), 1);
+
+        storage
+            .set_checklist_policy(ChecklistPolicy::CompleteAll)
+            .unwrap();
+        storage.complete_reminder("1", false).unwrap();
+        assert!(storage.data.pending.is_empty());
+        assert!(storage.data.completed[0].checklist[0].done);
+    }
+
+    #[test]
+    fn test_warn_policy_completes_once_confirmed() {
+        let mut store = ReminderStore::default();
+        let mut reminder = create_test_reminder(1, ListType::Actual, 0);
+        reminder
+            .checklist
+            .push(ChecklistItem::new(1, "Open item".to_string()));
+        store.pending.push(reminder);
+        let mut storage = create_test_storage("test_checklist_warn_force", store);
+        storage.set_checklist_policy(ChecklistPolicy::Warn).unwrap();
+
+        let err = storage.complete_reminder("1", false).unwrap_err();
+        assert!(err.contains(CONFIRM_OPEN_CHECKLIST));
+        assert_eq!(storage.data.pending.len(), 1);
+
+        storage.complete_reminder("1", true).unwrap();
+        assert!(storage.data.pending.is_empty());
+        assert!(storage.data.completed[0].is_completed);
+        // Confirming completes the reminder but leaves the open items as they were
+        assert!(!storage.data.completed[0].checklist[0].done);
+    }
+
+    #[test]
+    fn test_changes_record_modified_fields() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        store
+            .pending
+            .push(create_test_reminder(2, ListType::Actual, 1));
+        let mut storage = create_test_storage("test_modified_fields", store);
+
+        storage.set_urgency("1", Urgency::Now).unwrap();
+        storage.move_reminder("2", ListType::Backlog).unwrap();
+
+        let first = storage.data.pending.iter().find(|r| r.id == "1").unwrap();
+        assert!(first.updated_at.is_some());
+        assert!(first.modified.contains_key(field::URGENCY));
+        assert!(!first.modified.contains_key(field::MESSAGE));
+
+        let second = storage.data.pending.iter().find(|r| r.id == "2").unwrap();
+        assert!(second.modified.contains_key(field::LIST_TYPE));
+        assert!(second.modified.contains_key(field::SORT_ORDER));
     }
 
-    pub fn get_oauth_status(&self) -> (bool, bool) {
-        (self.has_oauth_credentials(), self.is_logged_in())
+    #[test]
+    fn test_delete_records_tombstone() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_delete_tombstone", store);
+
+        storage.delete_reminder("1").unwrap();
+        storage.delete_reminder("missing").unwrap();
+
+        assert_eq!(storage.data.tombstones.len(), 1);
+        assert_eq!(storage.data.tombstones[0].id, "1");
+        assert_eq!(storage.data.tombstones[0].device, "test-device");
+        // Full clock stamp, so deletions order like the changes they race
+        assert_eq!(storage.data.tombstones[0].deleted_at.split('/').count(), 3);
     }
 
-    pub fn save_oauth_credentials(&self, credentials: &OAuthCredentials) -> Result<(), String> {
-        oauth::save_oauth_credentials(&self.app_data_path, credentials)
+    #[test]
+    fn test_restore_from_trash_reenters_full_list() {
+        let mut store = ReminderStore::default();
+        for i in 0..6 {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
+        }
+        store
+            .pending
+            .push(create_test_reminder(100, ListType::Backlog, 0));
+        let mut storage = create_test_storage("test_trash_restore", store);
+
+        storage.delete_reminder("2").unwrap();
+        assert_eq!(storage.get_trash()[0].list, ListType::Actual);
+        // The Backlog reminder was promoted into the free spot
+        assert_eq!(storage.get_actual_reminders().len(), 6);
+
+        storage.restore_from_trash("2").unwrap();
+        assert!(storage.get_trash().is_empty());
+        assert!(storage.data.tombstones.is_empty());
+        let actual = storage.get_actual_reminders();
+        assert_eq!(actual.len(), 6);
+        assert_eq!(actual[0].id, "2");
+        assert_eq!(storage.get_backlog_reminders().len(), 1);
+        assert!(storage.restore_from_trash("2").is_err());
     }
 
-    pub fn get_oauth_credentials(&self) -> Option<OAuthCredentials> {
-        oauth::load_oauth_credentials(&self.app_data_path).ok()
+    #[test]
+    fn test_trash_restores_completed_and_orphaned_reminders() {
+        let mut storage = create_test_storage("test_trash_lists", ReminderStore::default());
+        let errands = storage.create_list("Errands".to_string(), None).unwrap();
+        storage
+            .add_reminder(create_test_reminder(1, errands.clone(), 0))
+            .unwrap();
+        storage
+            .add_reminder(create_test_reminder(2, ListType::Actual, 0))
+            .unwrap();
+        storage.complete_reminder("2", false).unwrap();
+
+        storage.delete_reminder("1").unwrap();
+        storage.delete_reminder("2").unwrap();
+        storage.delete_list(&errands).unwrap();
+        assert_eq!(storage.get_trash().len(), 2);
+
+        storage.restore_from_trash("1").unwrap();
+        storage.restore_from_trash("2").unwrap();
+        assert_eq!(storage.get_backlog_reminders()[0].id, "1");
+        assert_eq!(storage.get_completed_reminders()[0].id, "2");
+
+        storage.delete_reminder("1").unwrap();
+        assert_eq!(storage.empty_trash().unwrap(), 1);
+        assert!(storage.get_trash().is_empty());
+        assert!(storage.data.tombstones.iter().any(|t| t.id == "1"));
     }
 
-    pub fn get_app_data_path(&self) -> &std::path::Path {
-        &self.app_data_path
+    #[test]
+    fn test_old_completed_reminders_are_archived() {
+        let name = "test_archive_completed";
+        let mut store = ReminderStore::default();
+        for (id, days_ago) in [(1, 400), (2, 800), (3, 10)] {
+            let mut reminder = create_test_reminder(id, ListType::Actual, 0);
+            reminder.is_completed = true;
+            reminder.completed_at =
+                Some((Utc::now() - chrono::Duration::days(days_ago)).to_rfc3339());
+            store.completed.push(reminder);
+        }
+        store.completed[1].message = "Renew passport".to_string();
+        let mut storage = create_test_storage(name, store);
+        storage
+            .connect_remote(Box::new(MemoryBackend::default()))
+            .unwrap();
+
+        storage.save().unwrap();
+        let completed: Vec<ReminderId> = storage
+            .data
+            .completed
+            .iter()
+            .map(|r| r.id.clone())
+            .collect();
+        assert_eq!(completed, vec!["3"]);
+        assert!(storage.data.tombstones.is_empty());
+
+        let years = storage.get_archive_years();
+        let archived: usize = years
+            .iter()
+            .map(|year| storage.get_archived_reminders(*year).unwrap().len())
+            .sum();
+        assert_eq!(archived, 2);
+        let remote = storage.remote.as_mut().unwrap();
+        let mut remote_years = remote.archive_years().unwrap().unwrap();
+        remote_years.reverse();
+        assert_eq!(remote_years, years);
+
+        let found = storage.search_archive("passport").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "2");
+
+        let (_, hourly, _, _) = storage.get_historical_stats(false);
+        assert_eq!(hourly.iter().sum::<usize>(), 1);
+        let (_, hourly, _, _) = storage.get_historical_stats(true);
+        assert_eq!(hourly.iter().sum::<usize>(), 3);
     }
 
-    pub fn reload_oauth_state(&mut self) -> Result<(), String> {
-        self.init_drive()
+    #[test]
+    fn test_custom_list_overflows_into_configured_list() {
+        let mut storage = create_test_storage("test_custom_lists", ReminderStore::default());
+        let waiting = storage.create_list("Waiting".to_string(), None).unwrap();
+        let errands = storage.create_list("Errands".to_string(), Some(2)).unwrap();
+        storage
+            .update_list(
+                &errands,
+                "Errands".to_string(),
+                Some(2),
+                Some(waiting.clone()),
+                None,
+            )
+            .unwrap();
+        assert!(storage.create_list("errands".to_string(), None).is_err());
+
+        for i in 0..3 {
+            let reminder = Reminder {
+                id: i.to_string(),
+                ..Reminder::new(format!("Errand {}", i), Urgency::Today, errands.clone())
+            };
+            storage.add_reminder(reminder).unwrap();
+        }
+
+        assert_eq!(storage.get_list_reminders(&errands).len(), 2);
+        let bumped = storage.get_list_reminders(&waiting);
+        assert_eq!(bumped.len(), 1);
+        assert_eq!(bumped[0].id, "0");
+
+        assert!(storage.delete_list(&ListType::Backlog).is_err());
+        storage.delete_list(&errands).unwrap();
+        assert_eq!(storage.get_backlog_reminders().len(), 2);
+        assert!(storage
+            .get_lists()
+            .iter()
+            .all(|l| l.overflow_list != Some(errands.clone())));
     }
 
-    pub fn get_oauth_url(&self) -> Result<String, String> {
-        oauth::get_oauth_url(&self.app_data_path)
+    #[test]
+    fn test_deleted_list_stays_deleted_after_sync() {
+        let mut storage = create_test_storage("test_deleted_list_sync", ReminderStore::default());
+        let waiting = storage.create_list("Waiting".to_string(), None).unwrap();
+        // Another device still has the list
+        let cloud = storage.data.clone();
+
+        storage.delete_list(&waiting).unwrap();
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud)))
+            .unwrap();
+        assert!(storage.get_lists().iter().all(|l| l.id != waiting));
+        assert_eq!(storage.data.deleted_lists.len(), 1);
     }
 
-    pub fn disconnect_drive(&mut self) -> Result<(), String> {
-        oauth::disconnect(&self.app_data_path)?;
-        self.use_drive = false;
-        self.access_token = None;
-        self.refresh_token = None;
-        self.file_id = None;
-        Ok(())
+    #[test]
+    fn test_sync_with_remote_backend() {
+        let mut cloud = ReminderStore::default();
+        cloud
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        cloud
+            .pending
+            .push(create_test_reminder(2, ListType::Actual, 1));
+
+        let mut storage = create_test_storage("test_remote_sync", ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud)))
+            .unwrap();
+        assert_eq!(storage.get_actual_reminders().len(), 2);
+
+        storage.delete_reminder("1").unwrap();
+        assert!(!storage.cloud_dirty);
+
+        // A fresh read of the remote reflects the deletion
+        storage.refresh_from_cloud().unwrap();
+        let ids: Vec<ReminderId> = storage
+            .get_actual_reminders()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec!["2"]);
     }
-}
 
-/// Complete the entire OAuth flow in a blocking context (for use in a separate thread)
-pub fn complete_oauth_flow_blocking(app_data_path: &std::path::Path) -> Result<(), String> {
-    oauth::complete_oauth_flow_blocking(app_data_path)
-}
+    /// Storage holding reminders 1 and 2, backed up, then with 1 renamed and
+    /// 3 added. Returns the backup's name.
+    fn storage_changed_since_backup(name: &str, remote: bool) -> (TestStorage, String) {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        store
+            .pending
+            .push(create_test_reminder(2, ListType::Actual, 1));
+        let mut storage = create_test_storage(name, store);
+        let backup = backup::create_backup(
+            &storage.app_data_path,
+            &storage.data,
+            &storage.cipher,
+            Utc::now(),
+            MAX_BACKUPS,
+        )
+        .unwrap();
+        if remote {
+            storage
+                .connect_remote(Box::new(MemoryBackend::default()))
+                .unwrap();
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        storage
+            .update_reminder("1", "Renamed".to_string(), Urgency::Now)
+            .unwrap();
+        storage
+            .add_reminder(Reminder {
+                id: "3".to_string(),
+                ..Reminder::new("Task 3".to_string(), Urgency::Today, ListType::Backlog)
+            })
+            .unwrap();
+        (storage, backup)
+    }
 
-    fn create_test_reminder(id: i64, list_type: ListType, sort_order: i64) -> Reminder {
-        Reminder {
-            id,
-            message: format!("Task {}", id),
-            urgency: Urgency::Today,
-            list_type,
-            created_at: Utc::now().to_rfc3339(),
-            is_completed: false,
-            completed_at: None,
-            sort_order,
+    #[test]
+    fn test_restore_backup_replaces_data_and_history() {
+        let (mut storage, backup) = storage_changed_since_backup("test_restore_backup", false);
+
+        storage.restore_backup(&backup).unwrap();
+
+        let messages: Vec<&str> = storage
+            .data
+            .pending
+            .iter()
+            .map(|r| r.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["Task 1", "Task 2"]);
+        assert!(storage.data.tombstones.iter().any(|t| t.id == "3"));
+        assert!(storage.get_history().undo.is_empty());
+        assert_eq!(storage.undo().unwrap(), None);
+    }
+
+    #[test]
+    fn test_restore_backup_wins_over_remote() {
+        let (mut storage, backup) =
+            storage_changed_since_backup("test_restore_backup_remote", true);
+
+        // Another device edits 1 and 3 after our last sync
+        let mut other = storage.data.clone();
+        for reminder in other.pending.iter_mut().filter(|r| r.id != "2") {
+            reminder.message = format!("{} elsewhere", reminder.message);
+            reminder.touch(&[field::MESSAGE]);
+        }
+        storage.remote.as_mut().unwrap().save(&other).unwrap();
+
+        storage.restore_backup(&backup).unwrap();
+        storage.refresh_from_cloud().unwrap();
+
+        let messages: Vec<&str> = storage
+            .data
+            .pending
+            .iter()
+            .map(|r| r.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["Task 1", "Task 2"]);
+        assert!(storage.get_history().undo.is_empty());
+    }
+
+    /// Remote another device writes to right before each of our uploads, for
+    /// its first `writes` uploads, and archives into the same year right
+    /// before each of our archive uploads, for the first `archive_writes`
+    struct ContestedBackend {
+        inner: MemoryBackend,
+        writes: i64,
+        archive_writes: i64,
+    }
+
+    impl StorageBackend for ContestedBackend {
+        fn identity(&self) -> String {
+            "contested".to_string()
+        }
+
+        fn load(&mut self) -> Result<backend::Snapshot, String> {
+            self.inner.load()
+        }
+
+        fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String> {
+            self.inner.save(data)
+        }
+
+        fn revision(&mut self) -> Result<Option<Revision>, String> {
+            self.inner.revision()
+        }
+
+        fn set_cipher(&mut self, cipher: Cipher) -> Result<(), String> {
+            self.inner.set_cipher(cipher)
+        }
+
+        fn save_if_unchanged(
+            &mut self,
+            data: &ReminderStore,
+            expected: Option<&str>,
+        ) -> Result<ConditionalSave, String> {
+            if self.writes > 0 {
+                let mut other = self.inner.load()?.data;
+                let id = 1000 + self.writes;
+                other
+                    .pending
+                    .push(create_test_reminder(id, ListType::Backlog, 0));
+                self.inner.save(&other)?;
+                self.writes -= 1;
+            }
+            self.inner.save_if_unchanged(data, expected)
+        }
+
+        fn archive_years(&mut self) -> Option<Result<Vec<i32>, String>> {
+            self.inner.archive_years()
+        }
+
+        fn load_archive(&mut self, year: i32) -> Option<Result<backend::ArchiveSnapshot, String>> {
+            self.inner.load_archive(year)
+        }
+
+        fn save_archive_if_unchanged(
+            &mut self,
+            year: i32,
+            reminders: &[Reminder],
+            expected: Option<&str>,
+        ) -> Option<Result<ConditionalSave, String>> {
+            if self.archive_writes > 0 {
+                let other = self.inner.load_archive(year)?.unwrap();
+                let mut archived = other.reminders;
+                let mut theirs =
+                    create_test_reminder(2000 + self.archive_writes, ListType::Actual, 0);
+                theirs.is_completed = true;
+                theirs.completed_at = reminders[0].completed_at.clone();
+                archived.push(theirs);
+                self.inner
+                    .save_archive_if_unchanged(year, &archived, other.revision.as_deref())?
+                    .unwrap();
+                self.archive_writes -= 1;
+            }
+            self.inner
+                .save_archive_if_unchanged(year, reminders, expected)
         }
     }
 
     #[test]
-    fn test_promote_from_backlog_when_room() {
+    fn test_upload_retries_after_concurrent_writes() {
+        let name = "test_upload_retries";
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(ContestedBackend {
+                inner: MemoryBackend::with_data(ReminderStore::default()),
+                writes: 1,
+                archive_writes: 0,
+            }))
+            .unwrap();
+        let outcome = storage.get_sync_status().4.unwrap();
+        assert_eq!((outcome.attempts, outcome.saved), (2, true));
+
+        // The other device's reminder was merged in, not overwritten
+        storage.refresh_from_cloud().unwrap();
+        assert_eq!(storage.get_backlog_reminders()[0].id, "1001");
+
+        // A remote that never settles is given up on until the next sync
+        storage.remote = Some(Box::new(ContestedBackend {
+            inner: MemoryBackend::with_data(storage.data.clone()),
+            writes: MAX_UPLOAD_ATTEMPTS as i64,
+            archive_writes: 0,
+        }));
+        storage.remote_revision = Some("1".to_string());
+        let new = Reminder::new("New".to_string(), Urgency::Today, ListType::Actual);
+        storage.add_reminder(new).unwrap();
+        let (_, dirty, _, error, outcome) = storage.get_sync_status();
+        assert!(dirty);
+        assert!(error.unwrap().contains("kept changing"));
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.attempts, MAX_UPLOAD_ATTEMPTS);
+        assert!(!outcome.saved);
+
+        assert!(storage.retry_cloud_sync().unwrap());
+        assert!(storage.get_sync_status().4.unwrap().saved);
+    }
+
+    #[test]
+    fn test_archive_upload_merges_concurrent_archiving() {
+        let old_completed = |id: i64| {
+            let mut reminder = create_test_reminder(id, ListType::Actual, 0);
+            reminder.is_completed = true;
+            reminder.completed_at = Some((Utc::now() - chrono::Duration::days(400)).to_rfc3339());
+            reminder
+        };
         let mut store = ReminderStore::default();
+        store.completed.push(old_completed(1));
+        let year = archive::archive_year(&store.completed[0]).unwrap();
+        let mut storage = create_test_storage("test_archive_contested", store);
+        storage
+            .connect_remote(Box::new(ContestedBackend {
+                inner: MemoryBackend::default(),
+                writes: 0,
+                archive_writes: 1,
+            }))
+            .unwrap();
 
-        for i in 0..5 {
-            store.pending.push(create_test_reminder(i, ListType::Actual, i));
+        // The other device created the year's archive first; ours is added
+        // to it rather than replacing it
+        assert!(storage.data.completed.is_empty());
+        let remote = storage.remote.as_mut().unwrap();
+        let archived = remote.load_archive(year).unwrap().unwrap().reminders;
+        let ids: Vec<&str> = archived.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["2001", "1"]);
+
+        // A remote archive that never settles keeps the reminder completed
+        // until the next save
+        storage.remote = Some(Box::new(ContestedBackend {
+            inner: MemoryBackend::with_data(storage.data.clone()),
+            writes: 0,
+            archive_writes: MAX_UPLOAD_ATTEMPTS as i64,
+        }));
+        storage.remote_revision = Some("1".to_string());
+        storage.data.completed.push(old_completed(3));
+        storage.save().unwrap();
+        assert_eq!(storage.data.completed[0].id, "3");
+    }
+
+    #[test]
+    fn test_reconnect_merges_against_sync_base() {
+        let name = "test_sync_base_reconnect";
+        let mut cloud = ReminderStore::default();
+        for i in 0..2 {
+            cloud
+                .pending
+                .push(create_test_reminder(i + 1, ListType::Actual, i));
         }
 
-        store.pending.push(create_test_reminder(100, ListType::Backlog, 0));
-        store.pending.push(create_test_reminder(101, ListType::Backlog, 1));
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud.clone())))
+            .unwrap();
 
-        let mut storage = Storage {
-            data: store,
-            app_data_path: PathBuf::from("/tmp/test"),
-            use_drive: false,
-            cloud_dirty: false,
-            last_sync_time: None,
-            last_sync_error: None,
-            access_token: None,
-            refresh_token: None,
-            client_id: None,
-            client_secret: None,
-            folder_id: None,
-            file_id: None,
-        };
+        // Deleted while offline, with its tombstone since purged
+        storage.remote = None;
+        storage.sync_base = None;
+        storage.data.pending.retain(|r| r.id != "1");
+
+        // The base read back from disk shows "1" was deleted here, not added there
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud)))
+            .unwrap();
+        let ids: Vec<ReminderId> = storage
+            .get_actual_reminders()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec!["2"]);
+    }
 
-        storage.promote_from_backlog_if_room();
+    #[test]
+    fn test_sync_conflicts_are_reported_and_resolvable() {
+        let name = "test_sync_conflicts";
+        let mut cloud = ReminderStore::default();
+        cloud
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
 
-        let actual_count = storage.data.pending.iter()
-            .filter(|r| r.list_type == ListType::Actual)
-            .count();
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud.clone())))
+            .unwrap();
+        assert!(storage.get_sync_conflicts().is_empty());
 
-        assert_eq!(actual_count, 6);
+        // Renamed offline here, then differently (and later) elsewhere
+        storage.remote = None;
+        storage
+            .update_reminder("1", "Local name".to_string(), Urgency::Today)
+            .unwrap();
+        cloud.pending[0].message = "Cloud name".to_string();
+        cloud.pending[0].touch(&[field::MESSAGE]);
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud)))
+            .unwrap();
+        assert_eq!(storage.get_actual_reminders()[0].message, "Cloud name");
+
+        let conflicts = storage.get_sync_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].field, field::MESSAGE);
+        assert_eq!(conflicts[0].resolution, ConflictChoice::Cloud);
+
+        storage
+            .resolve_conflict(&conflicts[0].id, ConflictChoice::Local)
+            .unwrap();
+        assert_eq!(storage.get_actual_reminders()[0].message, "Local name");
+        assert_eq!(
+            storage.get_sync_conflicts()[0].resolution,
+            ConflictChoice::Local
+        );
+        let remote = storage.remote.as_mut().unwrap().load().unwrap().data;
+        assert_eq!(remote.pending[0].message, "Local name");
     }
 
     #[test]
-    fn test_promoted_task_goes_to_end() {
+    fn test_reopening_conflict_removes_spawned_occurrence() {
+        let name = "test_conflict_reopens_recurring";
+        let mut recurring = create_test_reminder(1, ListType::Actual, 0);
+        recurring.due_at = Some(
+            chrono::Local::now()
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string(),
+        );
+        recurring.recurrence = Some(RecurrenceRule::parse("FREQ=DAILY", None).unwrap());
+        recurring.is_completed = true;
+        recurring.completed_at = Some((Utc::now() - chrono::Duration::hours(1)).to_rfc3339());
+        let mut cloud = ReminderStore::default();
+        cloud.completed.push(recurring);
+
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud.clone())))
+            .unwrap();
+
+        // Reopened elsewhere, then reopened and completed again here
+        storage.remote = None;
+        let mut reopened = cloud.completed.remove(0);
+        reopened.is_completed = false;
+        reopened.completed_at = None;
+        reopened.touch(&[field::COMPLETION]);
+        cloud.pending.push(reopened);
+        storage.uncomplete_reminder("1").unwrap();
+        storage.complete_reminder("1", false).unwrap();
+        let spawned = storage.data.completed[0].next_occurrence.clone().unwrap();
+        storage
+            .connect_remote(Box::new(MemoryBackend::with_data(cloud)))
+            .unwrap();
+
+        let conflicts = storage.get_sync_conflicts();
+        let completion = conflicts
+            .iter()
+            .find(|c| c.field == field::COMPLETION)
+            .unwrap();
+        assert_eq!(completion.local["next_occurrence"], spawned.as_str());
+        assert_eq!(completion.resolution, ConflictChoice::Local);
+
+        storage
+            .resolve_conflict(&completion.id, ConflictChoice::Cloud)
+            .unwrap();
+        let ids: Vec<ReminderId> = storage.data.pending.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(ids, vec!["1"]);
+        assert!(storage.data.tombstones.iter().any(|t| t.id == spawned));
+
+        // Flipping back completes it again with a new occurrence, so the
+        // series goes on; flipping once more discards that one too
+        for _ in 0..2 {
+            storage
+                .resolve_conflict(&completion.id, ConflictChoice::Local)
+                .unwrap();
+            let next = storage.data.completed[0].next_occurrence.clone().unwrap();
+            assert_ne!(next, spawned);
+            let ids: Vec<ReminderId> = storage.data.pending.iter().map(|r| r.id.clone()).collect();
+            assert_eq!(ids, vec![next.clone()]);
+            assert!(storage.data.pending[0].recurrence.is_some());
+
+            storage
+                .resolve_conflict(&completion.id, ConflictChoice::Cloud)
+                .unwrap();
+            let ids: Vec<ReminderId> = storage.data.pending.iter().map(|r| r.id.clone()).collect();
+            assert_eq!(ids, vec!["1"]);
+            assert!(storage.data.tombstones.iter().any(|t| t.id == next));
+        }
+    }
+
+    #[test]
+    fn test_undo_add_restores_bumped_reminder() {
         let mut store = ReminderStore::default();
+        for i in 0..6 {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
+        }
+        let mut storage = create_test_storage("test_undo_add", store);
 
-        for i in 0..3 {
-            store.pending.push(create_test_reminder(i, ListType::Actual, i));
+        let id = storage
+            .add_reminder(create_test_reminder(10, ListType::Actual, 0))
+            .unwrap();
+        assert!(storage.get_backlog_reminders().iter().any(|r| r.id == "5"));
+
+        assert_eq!(storage.undo().unwrap(), Some("Add \"Task 10\"".to_string()));
+        assert!(storage.get_backlog_reminders().is_empty());
+        let actual = storage.get_actual_reminders();
+        assert_eq!(actual.len(), 6);
+        assert!(actual.iter().all(|r| r.id != id));
+        assert!(storage.data.tombstones.iter().any(|t| t.id == id));
+
+        storage.redo().unwrap();
+        assert!(storage.get_actual_reminders().iter().any(|r| r.id == id));
+        assert!(storage.get_backlog_reminders().iter().any(|r| r.id == "5"));
+        assert!(storage.data.tombstones.iter().all(|t| t.id != id));
+    }
+
+    #[test]
+    fn test_undo_complete_returns_promoted_reminder() {
+        let mut store = ReminderStore::default();
+        for i in 0..6 {
+            store
+                .pending
+                .push(create_test_reminder(i, ListType::Actual, i));
         }
+        store
+            .pending
+            .push(create_test_reminder(100, ListType::Backlog, 0));
+        let mut storage = create_test_storage("test_undo_complete", store);
 
-        store.pending.push(create_test_reminder(100, ListType::Backlog, 0));
+        storage.complete_reminder("2", false).unwrap();
+        assert!(storage.get_backlog_reminders().is_empty());
 
-        let mut storage = Storage {
-            data: store,
-            app_data_path: PathBuf::from("/tmp/test"),
-            use_drive: false,
-            cloud_dirty: false,
-            last_sync_time: None,
-            last_sync_error: None,
-            access_token: None,
-            refresh_token: None,
-            client_id: None,
-            client_secret: None,
-            folder_id: None,
-            file_id: None,
+        storage.undo().unwrap();
+        let actual = storage.get_actual_reminders();
+        assert_eq!(actual.len(), 6);
+        assert!(actual
+            .iter()
+            .any(|r| r.id == "2" && r.sort_order == sort_key::from_integer(2)));
+        assert_eq!(storage.get_backlog_reminders()[0].id, "100");
+        assert!(storage.data.completed.is_empty());
+
+        let history = storage.get_history();
+        assert!(history.undo.is_empty());
+        assert_eq!(history.redo[0].reminders, 2);
+        assert_eq!(storage.undo().unwrap(), None);
+    }
+
+    #[test]
+    fn test_undo_delete_wins_over_tombstone() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage("test_undo_delete", store);
+
+        storage.delete_reminder("1").unwrap();
+        storage.undo().unwrap();
+
+        assert_eq!(storage.get_actual_reminders().len(), 1);
+        assert!(storage.data.tombstones.is_empty());
+        assert!(storage.data.trash.is_empty());
+        let restored = &storage.data.pending[0];
+        assert!(restored.modified.contains_key(field::MESSAGE));
+    }
+
+    #[test]
+    fn test_undo_keeps_later_changes_to_other_fields() {
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        store
+            .pending
+            .push(create_test_reminder(2, ListType::Actual, 1));
+        let mut storage = create_test_storage("test_undo_field_level", store);
+
+        storage
+            .update_reminder("1", "Renamed".to_string(), Urgency::Now)
+            .unwrap();
+        storage
+            .update_reminder("2", "Renamed too".to_string(), Urgency::Now)
+            .unwrap();
+        // Changes arriving outside the history, as a sync merge would bring them
+        storage.data.pending[0].notes = "Synced notes".to_string();
+        storage.data.pending[1].message = "Renamed elsewhere".to_string();
+
+        storage.undo().unwrap();
+        storage.undo().unwrap();
+        let first = storage.data.pending.iter().find(|r| r.id == "1").unwrap();
+        assert_eq!(first.message, "Task 1");
+        assert_eq!(first.urgency, Urgency::Today);
+        assert_eq!(first.notes, "Synced notes");
+        // The message changed again since, so only the urgency is undone
+        let second = storage.data.pending.iter().find(|r| r.id == "2").unwrap();
+        assert_eq!(second.message, "Renamed elsewhere");
+        assert_eq!(second.urgency, Urgency::Today);
+
+        storage.redo().unwrap();
+        let first = storage.data.pending.iter().find(|r| r.id == "1").unwrap();
+        assert_eq!(first.message, "Renamed");
+        assert_eq!(first.notes, "Synced notes");
+    }
+
+    #[test]
+    fn test_operations_replay_onto_snapshot() {
+        let name = "test_oplog_replay";
+        let mut store = ReminderStore::default();
+        store
+            .pending
+            .push(create_test_reminder(1, ListType::Actual, 0));
+        let mut storage = create_test_storage(name, store);
+
+        storage
+            .add_reminder(create_test_reminder(2, ListType::Actual, 0))
+            .unwrap();
+        let snapshot = storage.local.load().unwrap().data;
+        storage
+            .update_reminder("1", "Renamed".to_string(), Urgency::Now)
+            .unwrap();
+        storage.complete_reminder("2", false).unwrap();
+        // Neither change needed a new snapshot
+        assert_eq!(storage.local.load().unwrap().data.pending.len(), 2);
+
+        let mut reopened = create_test_storage(name, ReminderStore::default());
+        reopened.local = Box::new(MemoryBackend::with_data(snapshot));
+        reopened.load_local_data().unwrap();
+        assert_eq!(
+            reopened.get_pending_reminders(),
+            storage.get_pending_reminders()
+        );
+        assert_eq!(reopened.data.completed, storage.data.completed);
+
+        let log = storage.get_audit_log(Some("2")).unwrap();
+        let kinds: Vec<oplog::OpKind> = log.iter().map(|op| op.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![oplog::OpKind::Completed, oplog::OpKind::Created]
+        );
+        assert!(log.iter().all(|op| op.device == "test-device"));
+    }
+
+    #[test]
+    fn test_operations_exchanged_with_remote() {
+        let name = "test_oplog_exchange";
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::default()))
+            .unwrap();
+
+        storage
+            .add_reminder(create_test_reminder(1, ListType::Actual, 0))
+            .unwrap();
+        let remote_ops = storage
+            .remote
+            .as_mut()
+            .unwrap()
+            .load_ops()
+            .unwrap()
+            .unwrap();
+        assert_eq!(remote_ops.len(), 1);
+
+        // Another replica's operation arrives through the remote
+        let mut other = create_test_reminder(2, ListType::Backlog, 0);
+        other.touch(&[field::MESSAGE]);
+        let changes = history::diff(&[], &[other]);
+        let ops = oplog::operations_from_changes(&changes, "other-device");
+        storage
+            .remote
+            .as_mut()
+            .unwrap()
+            .push_ops(&ops, None)
+            .unwrap()
+            .unwrap();
+
+        storage.refresh_from_cloud().unwrap();
+        assert_eq!(storage.get_backlog_reminders().len(), 1);
+        let log = storage.get_audit_log(Some("2")).unwrap();
+        assert_eq!(log[0].device, "other-device");
+    }
+
+    #[test]
+    fn test_remote_log_cut_back_once_every_device_read_it() {
+        let name = "test_oplog_compaction";
+        let mut storage = create_test_storage(name, ReminderStore::default());
+        storage
+            .connect_remote(Box::new(MemoryBackend::default()))
+            .unwrap();
+        let remote_ids = |storage: &mut Storage| -> Vec<String> {
+            let ops = storage
+                .remote
+                .as_mut()
+                .unwrap()
+                .load_ops()
+                .unwrap()
+                .unwrap();
+            ops.into_iter().map(|op| op.id).collect()
         };
 
-        storage.promote_from_backlog_if_room();
+        storage
+            .add_reminder(create_test_reminder(1, ListType::Actual, 0))
+            .unwrap();
+        let first = remote_ids(&mut storage);
+        // A laptop that synced before, but hasn't read any operation since
+        storage
+            .data
+            .ops_acknowledged
+            .insert("laptop".to_string(), String::new());
+        storage.refresh_from_cloud().unwrap();
+        assert_eq!(storage.data.ops_acknowledged["test-device"], first[0]);
 
-        let promoted = storage.data.pending.iter()
-            .find(|r| r.id == 100)
+        storage
+            .add_reminder(create_test_reminder(2, ListType::Actual, 1))
             .unwrap();
+        let both = remote_ids(&mut storage);
+        assert_eq!(both.len(), 2);
 
-        assert_eq!(promoted.list_type, ListType::Actual);
-        assert_eq!(promoted.sort_order, 3); // After 0, 1, 2
+        // Once the laptop has read both, pushing drops what every device read
+        storage
+            .data
+            .ops_acknowledged
+            .insert("laptop".to_string(), both[1].clone());
+        storage
+            .add_reminder(create_test_reminder(3, ListType::Actual, 2))
+            .unwrap();
+        let ids = remote_ids(&mut storage);
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], both[1]);
+    }
+
+    #[test]
+    fn test_encryption_enable_unlock_and_disable() {
+        let dir = TestDir::new("test_encryption");
+        let app_data_path = dir.0.clone();
+        let open = || {
+            let _ = fs::create_dir_all(&app_data_path);
+            let local = local::LocalJsonBackend::new(app_data_path.clone());
+            Storage::with_backend(
+                app_data_path.clone(),
+                "test-device".to_string(),
+                Box::new(local),
+            )
+        };
+        let file_contents = || {
+            ["reminders.json", "oplog.jsonl"]
+                .iter()
+                .map(|file| fs::read_to_string(app_data_path.join(file)).unwrap_or_default())
+                .collect::<String>()
+        };
+
+        let mut storage = open();
+        let mut reminder = create_test_reminder(1, ListType::Actual, 0);
+        reminder.message = "Call Acme Corp".to_string();
+        storage.add_reminder(reminder).unwrap();
+        assert!(file_contents().contains("Acme"));
+
+        assert!(storage.enable_encryption("short").is_err());
+        storage.enable_encryption("correct horse").unwrap();
+        storage
+            .update_reminder("1", "Call Acme Corp back".to_string(), Urgency::Now)
+            .unwrap();
+        assert!(!file_contents().contains("Acme"));
+
+        // Restarting needs the passphrase
+        let mut reopened = open();
+        reopened.locked = true;
+        assert!(reopened
+            .add_reminder(create_test_reminder(2, ListType::Actual, 0))
+            .is_err());
+        assert!(reopened.unlock("wrong horse").is_err());
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(
+            reopened.get_actual_reminders()[0].message,
+            "Call Acme Corp back"
+        );
+        assert!(reopened.get_history().undo.is_empty());
+
+        reopened
+            .change_passphrase("correct horse", "battery staple")
+            .unwrap();
+        assert!(reopened.disable_encryption("correct horse").is_err());
+        reopened.disable_encryption("battery staple").unwrap();
+        assert!(file_contents().contains("Acme"));
+        assert!(!reopened.get_encryption_status().enabled);
+    }
+
+    #[test]
+    fn test_switch_to_sqlite_imports_encrypted_json() {
+        let dir = TestDir::new("test_switch_local_backend");
+        let app_data_path = dir.0.clone();
+        let open = |kind: LocalBackendKind| {
+            let local: Box<dyn StorageBackend> = match kind {
+                LocalBackendKind::Json => {
+                    Box::new(local::LocalJsonBackend::new(app_data_path.clone()))
+                }
+                LocalBackendKind::Sqlite => {
+                    Box::new(sqlite::SqliteBackend::open(&app_data_path).unwrap())
+                }
+            };
+            let mut storage =
+                Storage::with_backend(app_data_path.clone(), "test-device".to_string(), local);
+            storage.local_kind = kind;
+            storage.locked = true;
+            storage
+        };
+
+        let mut storage = open(LocalBackendKind::Json);
+        storage.locked = false;
+        let mut reminder = create_test_reminder(1, ListType::Actual, 0);
+        reminder.message = "Call Acme Corp".to_string();
+        storage.add_reminder(reminder).unwrap();
+        storage.enable_encryption("correct horse").unwrap();
+
+        // reminders.json is sealed, so nothing can be imported before unlocking
+        let mut reopened = open(local::load_backend_kind(&app_data_path));
+        assert_eq!(reopened.get_local_backend().kind, LocalBackendKind::Json);
+        assert!(reopened
+            .switch_local_backend(LocalBackendKind::Sqlite)
+            .is_err());
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(
+            reopened
+                .switch_local_backend(LocalBackendKind::Sqlite)
+                .unwrap(),
+            1
+        );
+        assert!(!app_data_path.join("reminders.json").exists());
+        let db = fs::read(app_data_path.join("reminders.db")).unwrap();
+        assert!(!String::from_utf8_lossy(&db).contains("Acme"));
+
+        let mut reopened = open(local::load_backend_kind(&app_data_path));
+        assert_eq!(reopened.get_local_backend().kind, LocalBackendKind::Sqlite);
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get_actual_reminders()[0].message, "Call Acme Corp");
+
+        reopened
+            .switch_local_backend(LocalBackendKind::Json)
+            .unwrap();
+        assert!(!app_data_path.join("reminders.db").exists());
+        assert!(!app_data_path.join("reminders.json.imported").exists());
+        let mut reopened = open(local::load_backend_kind(&app_data_path));
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get_actual_reminders()[0].message, "Call Acme Corp");
     }
 }
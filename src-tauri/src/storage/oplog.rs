@@ -0,0 +1,470 @@
+use crate::clock::{self, is_newer};
+use crate::reminder::{field, Reminder, ReminderId};
+use crate::storage::backup::write_atomic;
+use crate::storage::crypto::Cipher;
+use crate::storage::history::ReminderChange;
+use crate::storage::merge::{ReminderStore, Tombstone};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OpKind {
+    Created,
+    Edited,
+    Moved,
+    Completed,
+    Uncompleted,
+    Deleted,
+    Reordered,
+}
+
+/// One change to one reminder. Logged next to the snapshot, which stays the
+/// source of truth: replicas exchange operations to pick up each other's
+/// changes between snapshot merges, and the log shows recent changes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Operation {
+    pub id: String, // UUID v7, unique across devices
+    pub at: String, // Clock stamp ("<ISO time>/<counter>/<device id>")
+    pub device: String,
+    pub reminder_id: ReminderId,
+    pub kind: OpKind,
+    #[serde(default)]
+    pub changes: Map<String, Value>, // Serialized reminder fields the operation set
+}
+
+/// Operations describing the given reminder changes
+pub fn operations_from_changes(changes: &[ReminderChange], device: &str) -> Vec<Operation> {
+    let at = clock::now().to_string();
+    changes
+        .iter()
+        .filter_map(|change| {
+            let (kind, changes) = match (&change.before, &change.after) {
+                (None, Some(after)) => (OpKind::Created, to_object(after)),
+                (Some(_), None) => (OpKind::Deleted, Map::new()),
+                (Some(before), Some(after)) => {
+                    let changes = changed_values(before, after);
+                    (classify(after, &changes), changes)
+                }
+                (None, None) => return None,
+            };
+            Some(Operation {
+                id: uuid::Uuid::now_v7().to_string(),
+                at: at.clone(),
+                device: device.to_string(),
+                reminder_id: change.id.clone(),
+                kind,
+                changes,
+            })
+        })
+        .collect()
+}
+
+fn to_object(reminder: &Reminder) -> Map<String, Value> {
+    match serde_json::to_value(reminder) {
+        Ok(Value::Object(object)) => object,
+        _ => Map::new(),
+    }
+}
+
+/// Serialized fields of `after` that differ from `before`
+fn changed_values(before: &Reminder, after: &Reminder) -> Map<String, Value> {
+    let before = to_object(before);
+    to_object(after)
+        .into_iter()
+        .filter(|(key, value)| before.get(key) != Some(value))
+        .collect()
+}
+
+fn classify(after: &Reminder, changes: &Map<String, Value>) -> OpKind {
+    const BOOKKEEPING: [&str; 3] = ["sort_order", "updated_at", "modified"];
+
+    if changes.contains_key("is_completed") {
+        if after.is_completed {
+            OpKind::Completed
+        } else {
+            OpKind::Uncompleted
+        }
+    } else if changes.contains_key("list_type") {
+        OpKind::Moved
+    } else if changes
+        .keys()
+        .all(|key| BOOKKEEPING.contains(&key.as_str()))
+    {
+        OpKind::Reordered
+    } else {
+        OpKind::Edited
+    }
+}
+
+/// The tracked field (see `field`) a serialized reminder key belongs to
+fn tracked_field(key: &str) -> Option<&'static str> {
+    match key {
+        "is_completed" | "completed_at" | "next_occurrence" => Some(field::COMPLETION),
+        _ => field::ALL.iter().find(|f| **f == key).copied(),
+    }
+}
+
+/// Apply an operation to a store. Each field is only overwritten if the
+/// operation changed it at least as recently as the store did, so replaying
+/// operations twice or slightly out of order gives the same result.
+pub fn apply_operation(store: &mut ReminderStore, op: &Operation) {
+    let existing = take_reminder(store, &op.reminder_id);
+
+    let result = match (op.kind, existing) {
+        (OpKind::Deleted, Some(reminder)) if is_newer(&op.at, reminder.last_stamp()) => {
+            Some(reminder) // Changed after the deletion
+        }
+        (OpKind::Deleted, _) => {
+            if !store.tombstones.iter().any(|t| t.id == op.reminder_id) {
+                store.tombstones.push(Tombstone {
+                    id: op.reminder_id.clone(),
+                    deleted_at: op.at.clone(),
+                    device: op.device.clone(),
+                });
+            }
+            None
+        }
+        (_, Some(reminder)) => Some(apply_changes(reminder, op)),
+        (OpKind::Created, None) => {
+            let deleted_later = store
+                .tombstones
+                .iter()
+                .any(|t| t.id == op.reminder_id && !is_newer(&t.deleted_at, &op.at));
+            if deleted_later {
+                None
+            } else {
+                serde_json::from_value(Value::Object(op.changes.clone())).ok()
+            }
+        }
+        // Changes to a reminder we no longer have
+        (_, None) => None,
+    };
+
+    if let Some(reminder) = result {
+        if reminder.is_completed {
+            store.completed.push(reminder);
+        } else {
+            store.pending.push(reminder);
+        }
+    }
+}
+
+fn take_reminder(store: &mut ReminderStore, id: &str) -> Option<Reminder> {
+    if let Some(pos) = store.pending.iter().position(|r| r.id == id) {
+        return Some(store.pending.remove(pos));
+    }
+    let pos = store.completed.iter().position(|r| r.id == id)?;
+    Some(store.completed.remove(pos))
+}
+
+fn apply_changes(reminder: Reminder, op: &Operation) -> Reminder {
+    let mut object = to_object(&reminder);
+    let op_modified = op.changes.get("modified").and_then(Value::as_object);
+
+    for (key, value) in &op.changes {
+        match key.as_str() {
+            "modified" => {
+                // Keep the newest stamp for each field
+                let Some(stamps) = value.as_object() else {
+                    continue;
+                };
+                let mut merged = reminder.modified.clone();
+                for (name, stamp) in stamps {
+                    let Some(stamp) = stamp.as_str() else {
+                        continue;
+                    };
+                    if merged
+                        .get(name)
+                        .is_none_or(|current| !is_newer(stamp, current))
+                    {
+                        merged.insert(name.clone(), stamp.to_string());
+                    }
+                }
+                object.insert(key.clone(), serde_json::json!(merged));
+            }
+            "updated_at" => {
+                let newer = value
+                    .as_str()
+                    .is_some_and(|stamp| !is_newer(stamp, reminder.last_modified()));
+                if newer {
+                    object.insert(key.clone(), value.clone());
+                }
+            }
+            _ => {
+                if let Some(name) = tracked_field(key) {
+                    let stamp = op_modified
+                        .and_then(|stamps| stamps.get(name))
+                        .and_then(Value::as_str)
+                        .unwrap_or(&op.at);
+                    if is_newer(stamp, reminder.field_modified(name)) {
+                        continue;
+                    }
+                }
+                object.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    serde_json::from_value(Value::Object(object)).unwrap_or(reminder)
+}
+
+/// Operations from log text, one JSON object per line, each encrypted on its
+/// own when encryption is enabled. Lines that can't be read (such as one torn
+/// by a crash mid-append) are skipped.
+pub fn parse_ops(content: &str, cipher: &Cipher) -> Vec<Operation> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let op = cipher
+                .open(line.to_string())
+                .and_then(|line| serde_json::from_str(&line).map_err(|e| e.to_string()));
+            match op {
+                Ok(op) => Some(op),
+                Err(e) => {
+                    eprintln!("Skipping unreadable operation log entry: {}", e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn format_ops(ops: &[Operation], cipher: &Cipher) -> Result<String, String> {
+    let mut content = String::new();
+    for op in ops {
+        let line = serde_json::to_string(op).map_err(|e| e.to_string())?;
+        content.push_str(&cipher.seal(line)?);
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// The operations logged after the one with id `last`; all of them if it
+/// isn't in `ops`
+pub fn ops_after(ops: Vec<Operation>, last: Option<&str>) -> Vec<Operation> {
+    match last.and_then(|last| ops.iter().position(|op| op.id == last)) {
+        Some(pos) => ops.into_iter().skip(pos + 1).collect(),
+        None => ops,
+    }
+}
+
+/// oplog.jsonl in the app data directory. Appended to, and cut back when the
+/// snapshot is compacted.
+pub struct OpLog {
+    path: PathBuf,
+    cipher: Cipher,
+}
+
+impl OpLog {
+    pub fn new(app_data_path: &Path) -> Self {
+        Self {
+            path: app_data_path.join("oplog.jsonl"),
+            cipher: Cipher::default(),
+        }
+    }
+
+    pub fn set_cipher(&mut self, cipher: Cipher) {
+        self.cipher = cipher;
+    }
+
+    /// Every logged operation, oldest first
+    pub fn read(&self) -> Result<Vec<Operation>, String> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(parse_ops(&content, &self.cipher)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn read_after(&self, last: Option<&str>) -> Result<Vec<Operation>, String> {
+        self.read().map(|ops| ops_after(ops, last))
+    }
+
+    /// Append operations and flush them to disk
+    pub fn append(&self, ops: &[Operation]) -> Result<(), String> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(|e| e.to_string())?;
+
+        // Start on a fresh line if a crash left the last entry unfinished
+        let mut content = String::new();
+        if file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())? > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1)).map_err(|e| e.to_string())?;
+            file.read_exact(&mut last).map_err(|e| e.to_string())?;
+            if last[0] != b'\n' {
+                content.push('\n');
+            }
+        }
+        content.push_str(&format_ops(ops, &self.cipher)?);
+
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Drop the operations up to and including the one with id `last` that
+    /// are no newer than the compaction `point`, keeping the newest `keep`
+    /// anyway. Nothing is dropped if `last` isn't logged.
+    pub fn truncate(&self, last: &str, point: &str, keep: usize) -> Result<(), String> {
+        let ops = self.read()?;
+        let Some(pos) = ops.iter().position(|op| op.id == last) else {
+            return Ok(());
+        };
+        let total = ops.len();
+        let newest = total.saturating_sub(keep);
+        let kept: Vec<Operation> = ops
+            .into_iter()
+            .enumerate()
+            .filter(|(i, op)| *i > pos || *i >= newest || op.id.as_str() > point)
+            .map(|(_, op)| op)
+            .collect();
+        if kept.len() == total {
+            return Ok(());
+        }
+        self.rewrite(&kept)
+    }
+
+    /// Replace the whole log, such as to encrypt it with a new key
+    pub fn rewrite(&self, ops: &[Operation]) -> Result<(), String> {
+        write_atomic(&self.path, format_ops(ops, &self.cipher)?.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+    use crate::storage::history::diff;
+    use std::env;
+
+    fn reminder(message: &str) -> Reminder {
+        Reminder::new(message.to_string(), Urgency::Today, ListType::Actual)
+    }
+
+    #[test]
+    fn test_operations_replay_to_same_state() {
+        let mut task = reminder("Write report");
+        let removed = reminder("Old");
+        let before = vec![removed.clone()];
+
+        let created =
+            operations_from_changes(&diff(&before, &[removed.clone(), task.clone()]), "a");
+        assert_eq!(created[0].kind, OpKind::Created);
+
+        let mut moved = task.clone();
+        moved.list_type = ListType::Backlog;
+        moved.touch(&[field::LIST_TYPE]);
+        let moves = operations_from_changes(&diff(&[task.clone()], &[moved.clone()]), "a");
+        assert_eq!(moves[0].kind, OpKind::Moved);
+        assert!(!moves[0].changes.contains_key("message"));
+        task = moved;
+
+        let deletes = operations_from_changes(&diff(std::slice::from_ref(&removed), &[]), "a");
+        assert_eq!(deletes[0].kind, OpKind::Deleted);
+
+        let mut store = ReminderStore {
+            pending: vec![removed],
+            ..Default::default()
+        };
+        for op in created.iter().chain(&moves).chain(&deletes) {
+            apply_operation(&mut store, op);
+        }
+        assert_eq!(store.pending, vec![task.clone()]);
+        assert_eq!(store.tombstones.len(), 1);
+
+        // Replaying again changes nothing
+        for op in created.iter().chain(&moves).chain(&deletes) {
+            apply_operation(&mut store, op);
+        }
+        assert_eq!(store.pending, vec![task]);
+    }
+
+    #[test]
+    fn test_older_operation_does_not_overwrite_newer_field() {
+        let original = reminder("Original");
+        let mut older = original.clone();
+        older.message = "Older".to_string();
+        older.touch(&[field::MESSAGE]);
+        let old_ops =
+            operations_from_changes(&diff(std::slice::from_ref(&original), &[older]), "a");
+
+        let mut newer = original.clone();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        newer.message = "Newer".to_string();
+        newer.touch(&[field::MESSAGE]);
+
+        let mut store = ReminderStore {
+            pending: vec![newer],
+            ..Default::default()
+        };
+        apply_operation(&mut store, &old_ops[0]);
+        assert_eq!(store.pending[0].message, "Newer");
+    }
+
+    #[test]
+    fn test_log_appends_and_skips_torn_line() {
+        let temp_dir = env::temp_dir().join("test_oplog_append");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let _ = fs::create_dir_all(&temp_dir);
+        let log = OpLog::new(&temp_dir);
+
+        let task = reminder("Task");
+        let first = operations_from_changes(&diff(&[], std::slice::from_ref(&task)), "a");
+        log.append(&first).unwrap();
+
+        // Simulate a crash halfway through writing an entry
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(temp_dir.join("oplog.jsonl"))
+            .unwrap();
+        file.write_all(b"{\"id\":\"tor").unwrap();
+
+        let second = operations_from_changes(&diff(std::slice::from_ref(&task), &[]), "a");
+        log.append(&second).unwrap();
+
+        let ops = log.read().unwrap();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(log.read_after(Some(&first[0].id)).unwrap(), second);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_truncate_keeps_unpushed_and_unread_operations() {
+        let temp_dir = env::temp_dir().join("test_oplog_truncate");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let _ = fs::create_dir_all(&temp_dir);
+        let log = OpLog::new(&temp_dir);
+
+        let tasks: Vec<Reminder> = (1..=5).map(|i| reminder(&format!("Task {}", i))).collect();
+        let mut ops = operations_from_changes(&diff(&[], &tasks), "a");
+        for (i, op) in ops.iter_mut().enumerate() {
+            op.id = format!("000{}", i + 1);
+        }
+        log.append(&ops).unwrap();
+        let ids = || -> Vec<String> { log.read().unwrap().into_iter().map(|op| op.id).collect() };
+
+        // Pushed up to 0004, read everywhere up to 0002
+        log.truncate("0004", "0002", 5).unwrap();
+        assert_eq!(ids().len(), 5);
+        log.truncate("0004", "0002", 1).unwrap();
+        assert_eq!(ids(), vec!["0003", "0004", "0005"]);
+        log.truncate("missing", "0005", 0).unwrap();
+        assert_eq!(ids().len(), 3);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
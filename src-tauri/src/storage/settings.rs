@@ -0,0 +1,113 @@
+use crate::clock::{self, is_newer};
+use crate::config::{
+    DEFAULT_ARCHIVE_AFTER_DAYS, DEFAULT_TOMBSTONE_RETENTION_DAYS, DEFAULT_TRASH_RETENTION_DAYS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Names of the settings whose changes are tracked for merging
+pub mod setting_field {
+    pub const CHECKLIST_POLICY: &str = "checklist_policy";
+    pub const TOMBSTONE_RETENTION_DAYS: &str = "tombstone_retention_days";
+    pub const TRASH_RETENTION_DAYS: &str = "trash_retention_days";
+    pub const ARCHIVE_AFTER_DAYS: &str = "archive_after_days";
+}
+
+/// What completing a reminder does while checklist items are still open
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecklistPolicy {
+    /// Tick off the open items and complete the reminder
+    #[default]
+    CompleteAll,
+    /// Refuse unless the caller confirms (force), then complete
+    Warn,
+    /// Refuse until every item is done
+    Refuse,
+}
+
+/// User preferences stored (and synced) with the reminders
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoreSettings {
+    #[serde(default)]
+    pub checklist_policy: ChecklistPolicy,
+    /// Days a deletion is remembered so other devices don't bring it back
+    #[serde(default = "default_tombstone_retention_days")]
+    pub tombstone_retention_days: u32,
+    /// Days deleted reminders can be restored from the trash
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    /// Days after completion a reminder moves to the yearly archive
+    #[serde(default = "default_archive_after_days")]
+    pub archive_after_days: u32,
+    #[serde(default)]
+    pub modified: HashMap<String, String>, // setting name -> clock stamp of last change
+}
+
+impl Default for StoreSettings {
+    fn default() -> Self {
+        Self {
+            checklist_policy: ChecklistPolicy::default(),
+            tombstone_retention_days: DEFAULT_TOMBSTONE_RETENTION_DAYS,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            archive_after_days: DEFAULT_ARCHIVE_AFTER_DAYS,
+            modified: HashMap::new(),
+        }
+    }
+}
+
+impl StoreSettings {
+    /// Record that `fields` (see `setting_field`) changed now
+    pub fn touch(&mut self, fields: &[&str]) {
+        let stamp = clock::now().to_string();
+        for field in fields {
+            self.modified.insert(field.to_string(), stamp.clone());
+        }
+    }
+
+    /// Clock stamp of the last change to one setting; empty if it was never
+    /// changed since tracking began
+    pub fn field_modified(&self, field: &str) -> &str {
+        self.modified
+            .get(field)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+/// Merge two devices' settings, each setting taken from whichever side
+/// changed it last; ties go to `a`
+pub fn merge_settings(a: &StoreSettings, b: &StoreSettings) -> StoreSettings {
+    let pick = |field: &str| {
+        if is_newer(a.field_modified(field), b.field_modified(field)) {
+            b
+        } else {
+            a
+        }
+    };
+    let mut merged = a.clone();
+    merged.checklist_policy = pick(setting_field::CHECKLIST_POLICY).checklist_policy;
+    merged.tombstone_retention_days =
+        pick(setting_field::TOMBSTONE_RETENTION_DAYS).tombstone_retention_days;
+    merged.trash_retention_days = pick(setting_field::TRASH_RETENTION_DAYS).trash_retention_days;
+    merged.archive_after_days = pick(setting_field::ARCHIVE_AFTER_DAYS).archive_after_days;
+
+    for (name, stamp) in &b.modified {
+        if is_newer(a.field_modified(name), stamp) {
+            merged.modified.insert(name.clone(), stamp.clone());
+        }
+    }
+    merged
+}
+
+fn default_tombstone_retention_days() -> u32 {
+    DEFAULT_TOMBSTONE_RETENTION_DAYS
+}
+
+fn default_trash_retention_days() -> u32 {
+    DEFAULT_TRASH_RETENTION_DAYS
+}
+
+fn default_archive_after_days() -> u32 {
+    DEFAULT_ARCHIVE_AFTER_DAYS
+}
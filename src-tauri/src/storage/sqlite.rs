@@ -0,0 +1,475 @@
+use crate::error::AppError;
+use crate::reminder::{Reminder, ReminderId};
+use crate::storage::backend::{Revision, Snapshot, StorageBackend};
+use crate::storage::backup::write_atomic;
+use crate::storage::crypto::Cipher;
+use crate::storage::lists::default_lists;
+use crate::storage::merge::{ReminderStore, Tombstone};
+use crate::storage::migrations::{NEWER_VERSION_ERROR, SCHEMA_VERSION};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS reminders (
+    id TEXT PRIMARY KEY,
+    is_completed INTEGER NOT NULL,
+    completed_at_utc TEXT,
+    list_type TEXT NOT NULL,
+    sort_order TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_reminders_completed
+    ON reminders (is_completed, completed_at_utc);
+CREATE TABLE IF NOT EXISTS tombstones (
+    id TEXT PRIMARY KEY,
+    deleted_at TEXT NOT NULL,
+    device TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// reminders.db in the app data directory: one row per reminder, every save
+/// in a single transaction. With encryption, each reminder's data and the
+/// settings, lists and trash are encrypted; ids, lists, order and completion
+/// times stay readable for the indexes.
+pub struct SqliteBackend {
+    path: PathBuf,
+    conn: Connection,
+    /// Serialized reminders as last read or written, so saves only write the
+    /// rows that changed
+    rows: HashMap<ReminderId, String>,
+    cipher: Cipher,
+    vacuum_after_save: bool, // Clear out pages still holding data under the old key
+}
+
+impl SqliteBackend {
+    /// Open (or create) reminders.db
+    pub fn open(app_data_path: &Path) -> Result<Self, String> {
+        let path = app_data_path.join("reminders.db");
+        let conn = Connection::open(&path).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            path,
+            conn,
+            rows: HashMap::new(),
+            cipher: Cipher::default(),
+            vacuum_after_save: false,
+        })
+    }
+
+    /// Replace the database's contents with `data`, loaded from reminders.json
+    /// by the caller with the current cipher set. reminders.json is kept as
+    /// reminders.json.imported.
+    pub fn import(&mut self, data: &ReminderStore) -> Result<(), String> {
+        self.save(data)?;
+        let json_path = self.path.with_file_name("reminders.json");
+        if json_path.exists() {
+            fs::rename(&json_path, self.imported_json_path()).map_err(|e| e.to_string())?;
+        }
+
+        eprintln!(
+            "Imported {} pending, {} completed reminders into {}",
+            data.pending.len(),
+            data.completed.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>, String> {
+        self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// A meta value stored encrypted when encryption is enabled
+    fn get_sealed_meta(&self, key: &str) -> Result<Option<String>, String> {
+        self.get_meta(key)?
+            .map(|value| self.cipher.open(value))
+            .transpose()
+    }
+
+    fn imported_json_path(&self) -> PathBuf {
+        self.path.with_file_name("reminders.json.imported")
+    }
+}
+
+/// Delete reminders.db and the copy of reminders.json kept from the import,
+/// once the data lives in reminders.json again. Nothing is left behind that
+/// later passphrase changes wouldn't re-encrypt.
+pub fn remove_database(app_data_path: &Path) -> Result<(), String> {
+    for name in [
+        "reminders.db",
+        "reminders.db-wal",
+        "reminders.db-shm",
+        "reminders.json.imported",
+    ] {
+        let path = app_data_path.join(name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Completion time normalized to UTC with a fixed format, so the text column
+/// sorts and compares chronologically
+fn normalize_time(time: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(time).ok().map(|dt| {
+        dt.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+    })
+}
+
+impl StorageBackend for SqliteBackend {
+    fn identity(&self) -> String {
+        format!("sqlite:{}", self.path.display())
+    }
+
+    fn load(&mut self) -> Result<Snapshot, String> {
+        let mut store = ReminderStore::default();
+        let mut rows = HashMap::new();
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM reminders ORDER BY sort_order")
+            .map_err(|e| e.to_string())?;
+        let data = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?;
+        for json in data {
+            let json = self.cipher.open(json.map_err(|e| e.to_string())?)?;
+            let reminder: Reminder = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+            rows.insert(reminder.id.clone(), json);
+            if reminder.is_completed {
+                store.completed.push(reminder);
+            } else {
+                store.pending.push(reminder);
+            }
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, deleted_at, device FROM tombstones")
+            .map_err(|e| e.to_string())?;
+        let tombstones = stmt
+            .query_map([], |row| {
+                Ok(Tombstone {
+                    id: row.get(0)?,
+                    deleted_at: row.get(1)?,
+                    device: row.get(2)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        for tombstone in tombstones {
+            store.tombstones.push(tombstone.map_err(|e| e.to_string())?);
+        }
+
+        // Rows are written in the current format, except for the integer
+        // positions of rows from before sort keys, which `Reminder` reads as
+        // keys and the next save rewrites. Data from a newer version is refused
+        if let Some(version) = self.get_meta("schema_version")? {
+            let version: u32 = version
+                .parse()
+                .map_err(|_| format!("Invalid schema version: {}", version))?;
+            if version > SCHEMA_VERSION {
+                return Err(AppError::storage(format!(
+                    "Database was {} (schema version {}, this version reads up to {}). Please update the app.",
+                    NEWER_VERSION_ERROR, version, SCHEMA_VERSION
+                ))
+                .into());
+            }
+        }
+        if let Some(settings) = self.get_sealed_meta("settings")? {
+            store.settings = serde_json::from_str(&settings).map_err(|e| e.to_string())?;
+        }
+        store.lists = match self.get_sealed_meta("lists")? {
+            Some(lists) => serde_json::from_str(&lists).map_err(|e| e.to_string())?,
+            None => default_lists(),
+        };
+        if let Some(trash) = self.get_sealed_meta("trash")? {
+            store.trash = serde_json::from_str(&trash).map_err(|e| e.to_string())?;
+        }
+        if let Some(deleted) = self.get_sealed_meta("deleted_lists")? {
+            store.deleted_lists = serde_json::from_str(&deleted).map_err(|e| e.to_string())?;
+        }
+        if let Some(acknowledged) = self.get_sealed_meta("ops_acknowledged")? {
+            store.ops_acknowledged =
+                serde_json::from_str(&acknowledged).map_err(|e| e.to_string())?;
+        }
+        store.last_op = self.get_meta("last_op")?.filter(|id| !id.is_empty());
+
+        self.rows = rows;
+        Ok(Snapshot {
+            data: store,
+            revision: self.get_meta("revision")?,
+        })
+    }
+
+    fn save(&mut self, data: &ReminderStore) -> Result<Option<Revision>, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let mut written = HashMap::new();
+
+        for reminder in data.pending.iter().chain(data.completed.iter()) {
+            let json = serde_json::to_string(reminder).map_err(|e| e.to_string())?;
+            if self.rows.get(&reminder.id) != Some(&json) {
+                tx.execute(
+                    "INSERT OR REPLACE INTO reminders
+                        (id, is_completed, completed_at_utc, list_type, sort_order, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        reminder.id,
+                        reminder.is_completed,
+                        reminder.completed_at.as_deref().and_then(normalize_time),
+                        reminder.list_type.as_str(),
+                        reminder.sort_order,
+                        self.cipher.seal(json.clone())?,
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            written.insert(reminder.id.clone(), json);
+        }
+
+        let stored_ids: Vec<String> = {
+            let mut stmt = tx
+                .prepare("SELECT id FROM reminders")
+                .map_err(|e| e.to_string())?;
+            let ids = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            ids.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+        };
+        for id in stored_ids.iter().filter(|id| !written.contains_key(*id)) {
+            tx.execute("DELETE FROM reminders WHERE id = ?1", [id])
+                .map_err(|e| e.to_string())?;
+        }
+
+        tx.execute("DELETE FROM tombstones", [])
+            .map_err(|e| e.to_string())?;
+        for tombstone in &data.tombstones {
+            tx.execute(
+                "INSERT OR REPLACE INTO tombstones (id, deleted_at, device) VALUES (?1, ?2, ?3)",
+                params![tombstone.id, tombstone.deleted_at, tombstone.device],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        let settings = serde_json::to_string(&data.settings).map_err(|e| e.to_string())?;
+        let settings = self.cipher.seal(settings)?;
+        let lists = serde_json::to_string(&data.lists).map_err(|e| e.to_string())?;
+        let lists = self.cipher.seal(lists)?;
+        let trash = serde_json::to_string(&data.trash).map_err(|e| e.to_string())?;
+        let trash = self.cipher.seal(trash)?;
+        let deleted_lists =
+            serde_json::to_string(&data.deleted_lists).map_err(|e| e.to_string())?;
+        let deleted_lists = self.cipher.seal(deleted_lists)?;
+        let ops_acknowledged =
+            serde_json::to_string(&data.ops_acknowledged).map_err(|e| e.to_string())?;
+        let ops_acknowledged = self.cipher.seal(ops_acknowledged)?;
+        let last_op = data.last_op.clone().unwrap_or_default();
+        let schema_version = SCHEMA_VERSION.to_string();
+        for (key, value) in [
+            ("settings", settings),
+            ("lists", lists),
+            ("trash", trash),
+            ("deleted_lists", deleted_lists),
+            ("ops_acknowledged", ops_acknowledged),
+            ("last_op", last_op),
+            ("schema_version", schema_version),
+        ] {
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES ('revision', '1')
+             ON CONFLICT(key) DO UPDATE SET value = CAST(value AS INTEGER) + 1",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+        self.rows = written;
+
+        if self.vacuum_after_save {
+            self.conn
+                .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+                .map_err(|e| e.to_string())?;
+            self.vacuum_after_save = false;
+        }
+        self.revision()
+    }
+
+    fn revision(&mut self) -> Result<Option<Revision>, String> {
+        self.get_meta("revision")
+    }
+
+    fn query_completed(&self) -> Option<Result<Vec<Reminder>, String>> {
+        let query = || -> Result<Vec<Reminder>, String> {
+            let mut stmt = self
+                .conn
+                .prepare(
+                    "SELECT data FROM reminders WHERE is_completed = 1
+                     ORDER BY completed_at_utc DESC",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            rows.map(|json| {
+                let json = self.cipher.open(json.map_err(|e| e.to_string())?)?;
+                serde_json::from_str(&json).map_err(|e| e.to_string())
+            })
+            .collect()
+        };
+        Some(query())
+    }
+
+    fn query_completion_times(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> Option<Result<Vec<DateTime<Utc>>, String>> {
+        let since = since
+            .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true))
+            .unwrap_or_default();
+        let query = || -> Result<Vec<DateTime<Utc>>, String> {
+            let mut stmt = self
+                .conn
+                .prepare(
+                    "SELECT completed_at_utc FROM reminders
+                     WHERE is_completed = 1 AND completed_at_utc >= ?1",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([since], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            let mut times = Vec::new();
+            for time in rows {
+                let time = time.map_err(|e| e.to_string())?;
+                if let Ok(dt) = DateTime::parse_from_rfc3339(&time) {
+                    times.push(dt.with_timezone(&Utc));
+                }
+            }
+            Ok(times)
+        };
+        Some(query())
+    }
+
+    /// Every row is rewritten by the next save. The copy of reminders.json
+    /// kept from the import is rewritten right away.
+    fn set_cipher(&mut self, cipher: Cipher) -> Result<(), String> {
+        let imported = self.imported_json_path();
+        if imported.exists() {
+            let content = fs::read_to_string(&imported).map_err(|e| e.to_string())?;
+            let content = cipher.seal(cipher.open(content)?)?;
+            write_atomic(&imported, content.as_bytes())?;
+        }
+
+        self.cipher = cipher;
+        self.rows.clear();
+        self.vacuum_after_save = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Urgency};
+    use std::env;
+
+    fn completed(id: &str, completed_at: &str) -> Reminder {
+        Reminder {
+            id: id.to_string(),
+            is_completed: true,
+            completed_at: Some(completed_at.to_string()),
+            ..Reminder::new(format!("Task {}", id), Urgency::Today, ListType::Actual)
+        }
+    }
+
+    #[test]
+    fn test_save_load_and_query() {
+        let temp_dir = env::temp_dir().join("test_sqlite_roundtrip");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut backend = SqliteBackend::open(&temp_dir).unwrap();
+        assert_eq!(backend.revision().unwrap(), None);
+
+        let mut store = ReminderStore::default();
+        store.pending.push(Reminder::new(
+            "Pending".to_string(),
+            Urgency::Now,
+            ListType::Backlog,
+        ));
+        store
+            .completed
+            .push(completed("a", "2024-01-01T10:00:00+02:00"));
+        store.completed.push(completed("b", "2024-01-02T10:00:00Z"));
+        let first = backend.save(&store).unwrap();
+
+        store.completed.retain(|r| r.id != "a");
+        let second = backend.save(&store).unwrap();
+        assert_ne!(first, second);
+
+        let mut reopened = SqliteBackend::open(&temp_dir).unwrap();
+        let loaded = reopened.load().unwrap().data;
+        assert_eq!(loaded.pending.len(), 1);
+        assert_eq!(loaded.pending[0].list_type, ListType::Backlog);
+        assert_eq!(loaded.completed.len(), 1);
+
+        let since = DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let times = reopened
+            .query_completion_times(Some(since))
+            .unwrap()
+            .unwrap();
+        assert_eq!(times.len(), 1);
+        let completed = reopened.query_completed().unwrap().unwrap();
+        assert_eq!(completed[0].id, "b");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_import_keeps_json_copy() {
+        use crate::storage::local::{load_local, save_local};
+
+        let temp_dir = env::temp_dir().join("test_sqlite_import");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut store = ReminderStore::default();
+        store.completed.push(completed("a", "2024-01-01T10:00:00Z"));
+        save_local(&temp_dir, &store, &Cipher::default()).unwrap();
+
+        // Opening the database alone doesn't touch reminders.json
+        let mut backend = SqliteBackend::open(&temp_dir).unwrap();
+        assert_eq!(backend.revision().unwrap(), None);
+        assert!(temp_dir.join("reminders.json").exists());
+
+        let store = load_local(&temp_dir, &Cipher::default()).unwrap();
+        backend.import(&store).unwrap();
+        assert_eq!(backend.load().unwrap().data.completed.len(), 1);
+        assert!(!temp_dir.join("reminders.json").exists());
+        assert!(temp_dir.join("reminders.json.imported").exists());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
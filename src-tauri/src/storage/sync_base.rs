@@ -0,0 +1,102 @@
+use crate::storage::backup::write_atomic;
+use crate::storage::crypto::Cipher;
+use crate::storage::merge::ReminderStore;
+use crate::storage::migrations::SCHEMA_VERSION;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SYNC_BASE_FILE: &str = "sync_base.json";
+
+/// The store as last synced with a remote, the common ancestor for merging
+/// local and remote changes
+#[derive(Serialize, Deserialize)]
+struct SyncBaseFile {
+    schema_version: u32,
+    /// Identity of the remote it was synced with
+    remote: String,
+    store: ReminderStore,
+}
+
+fn sync_base_path(app_data_path: &Path) -> PathBuf {
+    app_data_path.join(SYNC_BASE_FILE)
+}
+
+/// The store last synced with `remote`. None if there is none, or it can't
+/// be used: merges then fall back to comparing both sides directly.
+pub fn load(app_data_path: &Path, remote: &str, cipher: &Cipher) -> Option<ReminderStore> {
+    let content = fs::read_to_string(sync_base_path(app_data_path)).ok()?;
+    let file: SyncBaseFile = match cipher
+        .open(content)
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Ignoring unreadable sync base: {}", e);
+            return None;
+        }
+    };
+    // Bases aren't migrated; the next sync writes a current one
+    if file.schema_version != SCHEMA_VERSION || file.remote != remote {
+        return None;
+    }
+    Some(file.store)
+}
+
+/// Record `store` as last synced with `remote`
+pub fn save(
+    app_data_path: &Path,
+    remote: &str,
+    store: &ReminderStore,
+    cipher: &Cipher,
+) -> Result<(), String> {
+    let file = SyncBaseFile {
+        schema_version: SCHEMA_VERSION,
+        remote: remote.to_string(),
+        store: store.clone(),
+    };
+    let content = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+    let content = cipher.seal(content)?;
+    write_atomic(&sync_base_path(app_data_path), content.as_bytes())
+}
+
+/// Forget the last synced store
+pub fn remove(app_data_path: &Path) -> Result<(), String> {
+    match fs::remove_file(sync_base_path(app_data_path)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::{ListType, Reminder, Urgency};
+
+    #[test]
+    fn test_sync_base_is_kept_per_remote() {
+        let dir = std::env::temp_dir().join("test_sync_base");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let cipher = Cipher::default();
+        assert!(load(&dir, "memory", &cipher).is_none());
+
+        let store = ReminderStore {
+            pending: vec![Reminder::new(
+                "Synced".to_string(),
+                Urgency::Today,
+                ListType::Actual,
+            )],
+            ..Default::default()
+        };
+        save(&dir, "memory", &store, &cipher).unwrap();
+        let loaded = load(&dir, "memory", &cipher).unwrap();
+        assert_eq!(loaded.pending, store.pending);
+        assert!(load(&dir, "drive:other", &cipher).is_none());
+
+        remove(&dir).unwrap();
+        assert!(load(&dir, "memory", &cipher).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
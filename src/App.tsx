@@ -17,6 +17,7 @@ import { SettingsDialog } from "./components/SettingsDialog";
 import { ToastContainer } from "./components/Toast";
 import { ReportsTab } from "./components/ReportsTab";
 import { OrganizePrompt } from "./components/OrganizePrompt";
+import { UnlockPrompt } from "./components/UnlockPrompt";
 import { useReminders } from "./hooks/useReminders";
 import { useDragReorder } from "./hooks/useDragReorder";
 import type { Reminder } from "./types";
@@ -47,7 +48,7 @@ function App() {
   const [activeTab, setActiveTab] = useState<TabType>("tasks");
   const [editingReminder, setEditingReminder] = useState<Reminder | null>(null);
   const [showSettings, setShowSettings] = useState(false);
-  const [focusedReminderId, setFocusedReminderId] = useState<number | null>(null);
+  const [focusedReminderId, setFocusedReminderId] = useState<string | null>(null);
   const [updateAvailable, setUpdateAvailable] = useState<{ version: string; download: () => Promise<void> } | null>(null);
   const [updating, setUpdating] = useState(false);
   const [checkingForUpdates, setCheckingForUpdates] = useState(false);
@@ -94,7 +95,7 @@ function App() {
   });
 
   // Handle focus from list - emit to bar
-  const handleFocusReminder = useCallback(async (id: number | null) => {
+  const handleFocusReminder = useCallback(async (id: string | null) => {
     // Don't trigger focus if we just finished dragging
     if (justFinishedDragActual.current || justFinishedDragBacklog.current) return;
     setFocusedReminderId(id);
@@ -219,7 +220,7 @@ function App() {
 
   // Listen for focus-reminder event from the reminder bar
   useEffect(() => {
-    const unlisten = listen<{ id: number | null }>("focus-reminder", (event) => {
+    const unlisten = listen<{ id: string | null }>("focus-reminder", (event) => {
       if (isLocalFocusRef.current) {
         isLocalFocusRef.current = false;
         return;
@@ -470,6 +471,8 @@ function App() {
         completedToday={stats.today}
         onOpenTasks={() => setActiveTab("tasks")}
       />
+
+      <UnlockPrompt onUnlocked={refresh} />
     </div>
   );
 }
@@ -3,7 +3,7 @@ import type { Reminder } from "../types";
 
 interface CompletedSectionProps {
   reminders: Reminder[];
-  onDelete: (id: number) => void;
+  onDelete: (id: string) => void;
 }
 
 export function CompletedSection({ reminders, onDelete }: CompletedSectionProps) {
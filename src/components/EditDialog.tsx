@@ -3,7 +3,7 @@ import type { Reminder, UrgencyType } from "../types";
 
 interface EditDialogProps {
   reminder: Reminder;
-  onSave: (id: number, message: string, urgency: UrgencyType) => Promise<void>;
+  onSave: (id: string, message: string, urgency: UrgencyType) => Promise<void>;
   onClose: () => void;
 }
 
@@ -0,0 +1,151 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import type { EncryptionStatus } from "../types";
+
+type EncryptionAction = "enable" | "disable" | "change";
+
+export function EncryptionSettings() {
+  const [enabled, setEnabled] = useState(false);
+  const [action, setAction] = useState<EncryptionAction | null>(null);
+  const [current, setCurrent] = useState("");
+  const [passphrase, setPassphrase] = useState("");
+  const [confirm, setConfirm] = useState("");
+  const [working, setWorking] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<EncryptionStatus>("get_encryption_status")
+      .then((status) => setEnabled(status.enabled))
+      .catch(console.error);
+  }, []);
+
+  const needsCurrent = action === "disable" || action === "change";
+  const needsNew = action === "enable" || action === "change";
+  const canSubmit =
+    (!needsCurrent || current.length > 0) &&
+    (!needsNew || (passphrase.length > 0 && passphrase === confirm));
+
+  const reset = () => {
+    setAction(null);
+    setCurrent("");
+    setPassphrase("");
+    setConfirm("");
+    setError(null);
+  };
+
+  const handleSubmit = async () => {
+    if (!canSubmit) return;
+    setWorking(true);
+    setError(null);
+    try {
+      if (action === "enable") {
+        await invoke("enable_encryption", { passphrase });
+        setEnabled(true);
+      } else if (action === "disable") {
+        await invoke("disable_encryption", { passphrase: current });
+        setEnabled(false);
+      } else if (action === "change") {
+        await invoke("change_passphrase", { current, new: passphrase });
+      }
+      reset();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setWorking(false);
+    }
+  };
+
+  const inputClass = "w-full px-3 py-2 bg-dark-600 border border-dark-500 rounded text-white text-sm focus:outline-none focus:border-accent-blue";
+
+  return (
+    <div className="pt-4 border-t border-dark-600">
+      <p className="text-sm text-gray-400 mb-3">Encryption</p>
+
+      <div className="flex items-center gap-2 mb-3">
+        <div className={`w-2 h-2 rounded-full ${enabled ? "bg-green-500" : "bg-gray-500"}`} />
+        <span className="text-sm text-gray-300">
+          {enabled ? "Reminders are encrypted with a passphrase" : "Reminders are stored as plain text"}
+        </span>
+      </div>
+
+      {action ? (
+        <div className="space-y-2 p-3 bg-dark-700 rounded-lg">
+          {needsCurrent && (
+            <input
+              type="password"
+              placeholder="Current passphrase"
+              value={current}
+              onChange={(e) => setCurrent(e.target.value)}
+              className={inputClass}
+            />
+          )}
+          {needsNew && (
+            <>
+              <input
+                type="password"
+                placeholder="New passphrase"
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                className={inputClass}
+              />
+              <input
+                type="password"
+                placeholder="Repeat new passphrase"
+                value={confirm}
+                onChange={(e) => setConfirm(e.target.value)}
+                className={inputClass}
+              />
+              <p className="text-xs text-gray-500">
+                The passphrase can't be recovered. Other synced devices need it too.
+              </p>
+            </>
+          )}
+          <div className="flex gap-2 mt-2">
+            <button
+              onClick={handleSubmit}
+              disabled={working || !canSubmit}
+              className="px-3 py-1.5 bg-accent-blue hover:bg-blue-600 disabled:bg-dark-600 text-white text-sm rounded transition-colors"
+            >
+              {working ? "Working..." : "Confirm"}
+            </button>
+            <button
+              onClick={reset}
+              className="px-3 py-1.5 bg-dark-600 hover:bg-dark-500 text-white text-sm rounded transition-colors"
+            >
+              Cancel
+            </button>
+          </div>
+        </div>
+      ) : (
+        <div className="flex flex-wrap gap-2">
+          {!enabled && (
+            <button
+              onClick={() => setAction("enable")}
+              className="px-3 py-1.5 bg-dark-600 hover:bg-dark-500 text-white text-sm rounded-lg transition-colors"
+            >
+              Enable Encryption
+            </button>
+          )}
+          {enabled && (
+            <>
+              <button
+                onClick={() => setAction("change")}
+                className="px-3 py-1.5 bg-dark-600 hover:bg-dark-500 text-white text-sm rounded-lg transition-colors"
+              >
+                Change Passphrase
+              </button>
+              <button
+                onClick={() => setAction("disable")}
+                className="px-3 py-1.5 bg-dark-600 hover:bg-dark-500 text-white text-sm rounded-lg transition-colors"
+              >
+                Disable Encryption
+              </button>
+            </>
+          )}
+        </div>
+      )}
+
+      {error && <p className="text-xs mt-2 text-red-400">{error}</p>}
+    </div>
+  );
+}
@@ -0,0 +1,67 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import type { LocalBackendKind, LocalBackendStatus } from "../types";
+
+const LABELS: Record<LocalBackendKind, string> = {
+  json: "reminders.json",
+  sqlite: "SQLite database",
+};
+
+export function LocalStorageSettings() {
+  const [status, setStatus] = useState<LocalBackendStatus | null>(null);
+  const [working, setWorking] = useState(false);
+  const [result, setResult] = useState<string | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<LocalBackendStatus>("get_local_backend")
+      .then(setStatus)
+      .catch(console.error);
+  }, []);
+
+  if (!status) return null;
+  const target: LocalBackendKind = status.kind === "json" ? "sqlite" : "json";
+
+  const handleSwitch = async () => {
+    setWorking(true);
+    setResult(null);
+    setError(null);
+    try {
+      const moved = await invoke<number>("switch_local_backend", { kind: target });
+      setStatus({ kind: target, error: null });
+      setResult(`Moved ${moved} reminders to the ${LABELS[target]}`);
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setWorking(false);
+    }
+  };
+
+  return (
+    <div className="pt-4 border-t border-dark-600">
+      <p className="text-sm text-gray-400 mb-3">Local Storage</p>
+
+      <div className="flex items-center gap-2 mb-1">
+        <div className={`w-2 h-2 rounded-full ${status.error ? "bg-yellow-500" : "bg-green-500"}`} />
+        <span className="text-sm text-gray-300">Stored in the {LABELS[status.kind]}</span>
+      </div>
+      {status.error && (
+        <p className="text-xs text-yellow-400 mb-1 ml-4">
+          The database couldn't be opened ({status.error}); using reminders.json instead
+        </p>
+      )}
+      <div className="mb-3" />
+
+      <button
+        onClick={handleSwitch}
+        disabled={working}
+        className="px-3 py-1.5 bg-dark-600 hover:bg-dark-500 disabled:bg-dark-700 text-white text-sm rounded-lg transition-colors"
+      >
+        {working ? "Moving..." : `Move to ${LABELS[target]}`}
+      </button>
+
+      {result && <p className="text-xs mt-2 text-green-400">{result}</p>}
+      {error && <p className="text-xs mt-2 text-red-400">{error}</p>}
+    </div>
+  );
+}
@@ -4,7 +4,7 @@ import type { Reminder, UrgencyType } from "../types";
 
 interface NotificationPopupProps {
   reminder: Reminder;
-  onComplete: (id: number) => void;
+  onComplete: (id: string) => void;
   onDismiss: () => void;
 }
 
@@ -8,12 +8,12 @@ interface ReminderItemProps {
   isLeaving?: boolean;
   isDragging?: boolean;
   isDragOver?: boolean;
-  onComplete: (id: number) => void;
-  onDelete: (id: number) => void;
+  onComplete: (id: string) => void;
+  onDelete: (id: string) => void;
   onEdit: (reminder: Reminder) => void;
-  onMove?: (id: number, toList: ListType) => void;
-  onSetUrgency?: (id: number, urgency: UrgencyType) => void;
-  onFocus?: (id: number | null) => void;
+  onMove?: (id: string, toList: ListType) => void;
+  onSetUrgency?: (id: string, urgency: UrgencyType) => void;
+  onFocus?: (id: string | null) => void;
   onMouseDown?: (e: React.MouseEvent) => void;
 }
 
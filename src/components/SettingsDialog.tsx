@@ -2,6 +2,9 @@ import { useState, useEffect, useRef, useCallback } from "react";
 import { enable, disable, isEnabled } from "@tauri-apps/plugin-autostart";
 import { invoke } from "@tauri-apps/api/core";
 import { getVersion } from "@tauri-apps/api/app";
+import { EncryptionSettings } from "./EncryptionSettings";
+import { LocalStorageSettings } from "./LocalStorageSettings";
+import type { UploadOutcome } from "../types";
 
 interface ShortcutInputProps {
   onSave: (shortcut: string) => void;
@@ -85,6 +88,7 @@ interface SettingsDialogProps {
     cloudDirty: boolean;
     lastSyncTime: string | null;
     lastSyncError: string | null;
+    lastUpload: UploadOutcome | null;
   };
 }
 
@@ -337,6 +341,13 @@ export function SettingsDialog({ onClose, onRefreshFromCloud, onCheckForUpdates,
                 Last synced: {new Date(parentSyncStatus.lastSyncTime).toLocaleString()}
               </p>
             )}
+            {isLoggedIn && parentSyncStatus?.lastUpload && parentSyncStatus.lastUpload.attempts > 1 && (
+              <p className={`text-xs mb-1 ml-4 ${parentSyncStatus.lastUpload.saved ? "text-gray-500" : "text-yellow-400"}`}>
+                {parentSyncStatus.lastUpload.saved
+                  ? `Merged changes from another device before uploading (${parentSyncStatus.lastUpload.attempts} attempts)`
+                  : `Cloud kept changing; upload gave up after ${parentSyncStatus.lastUpload.attempts} attempts`}
+              </p>
+            )}
             {isLoggedIn && parentSyncStatus?.cloudDirty && (
               <p className="text-xs text-yellow-400 mb-1 ml-4">
                 Unsynced local changes
@@ -468,6 +479,10 @@ export function SettingsDialog({ onClose, onRefreshFromCloud, onCheckForUpdates,
             )}
           </div>
 
+          <LocalStorageSettings />
+
+          <EncryptionSettings />
+
           {/* About */}
           <div className="pt-4 border-t border-dark-600">
             <p className="text-xs text-gray-500 mb-2">Reminder App v{appVersion || "..."}</p>
@@ -0,0 +1,71 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import type { EncryptionStatus } from "../types";
+
+interface UnlockPromptProps {
+  onUnlocked: () => void;
+}
+
+export function UnlockPrompt({ onUnlocked }: UnlockPromptProps) {
+  const [locked, setLocked] = useState(false);
+  const [passphrase, setPassphrase] = useState("");
+  const [unlocking, setUnlocking] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  useEffect(() => {
+    invoke<EncryptionStatus>("get_encryption_status")
+      .then((status) => setLocked(status.locked))
+      .catch(console.error);
+  }, []);
+
+  const handleUnlock = async () => {
+    if (!passphrase) return;
+    setUnlocking(true);
+    setError(null);
+    try {
+      await invoke("unlock_storage", { passphrase });
+      setLocked(false);
+      setPassphrase("");
+      onUnlocked();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  if (!locked) return null;
+
+  return (
+    <div className="fixed inset-0 bg-black/50 flex items-center justify-center z-50 animate-fade-in">
+      <div className="bg-dark-800 rounded-xl p-6 w-full max-w-sm shadow-2xl animate-slide-up">
+        <div className="text-center mb-4">
+          <h2 className="text-lg font-semibold text-white">Reminders are encrypted</h2>
+          <p className="text-sm text-gray-400 mt-1">
+            Enter your passphrase to unlock them
+          </p>
+        </div>
+
+        <input
+          type="password"
+          autoFocus
+          placeholder="Passphrase"
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          onKeyDown={(e) => e.key === "Enter" && handleUnlock()}
+          className="w-full px-3 py-2 bg-dark-600 border border-dark-500 rounded text-white text-sm
+                     focus:outline-none focus:border-accent-blue"
+        />
+        {error && <p className="text-xs mt-2 text-red-400">{error}</p>}
+
+        <button
+          onClick={handleUnlock}
+          disabled={unlocking || !passphrase}
+          className="w-full mt-4 px-4 py-2 bg-accent-blue hover:bg-blue-600 disabled:bg-dark-600 text-white rounded-lg transition-colors font-medium"
+        >
+          {unlocking ? "Unlocking..." : "Unlock"}
+        </button>
+      </div>
+    </div>
+  );
+}
@@ -1,22 +1,47 @@
 import { useState, useEffect, useCallback, useMemo, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { emit } from "@tauri-apps/api/event";
-import type { Reminder, UrgencyType, ListType } from "../types";
+import type { Reminder, UrgencyType, ListType, UploadOutcome } from "../types";
 import { showToast } from "../components/Toast";
 
+// Optimistically added reminders carry a temp id until the backend responds
+function isTempId(id: string): boolean {
+  return id.startsWith("temp-");
+}
+
+// Sort keys compare as plain text (not locale-aware), ties broken by id
+function byPosition(a: Reminder, b: Reminder): number {
+  if (a.sort_order !== b.sort_order) return a.sort_order < b.sort_order ? -1 : 1;
+  return a.id < b.id ? -1 : a.id > b.id ? 1 : 0;
+}
+
+// Placeholder keys until the backend's arrive: "" sorts before every key and
+// "~" after every key
+const TOP_KEY = "";
+const BOTTOM_KEY = "~";
+
+// Ends the backend's refusal under the "warn" checklist policy (config.rs)
+const CONFIRM_OPEN_CHECKLIST = "confirm to complete anyway";
+
+// Snoozed reminders stay hidden until the backend wakes them up
+function isSnoozed(reminder: Reminder): boolean {
+  return !!reminder.hidden_until && new Date(reminder.hidden_until) > new Date();
+}
+
 export function useReminders() {
   const [pending, setPending] = useState<Reminder[]>([]);
   const [completed, setCompleted] = useState<Reminder[]>([]);
   const [stats, setStats] = useState<{ today: number; week: number }>({ today: 0, week: 0 });
   const [loading, setLoading] = useState(true);
   const [syncing, setSyncing] = useState(false);
-  const [leavingIds, setLeavingIds] = useState<Set<number>>(new Set());
+  const [leavingIds, setLeavingIds] = useState<Set<string>>(new Set());
   const [syncStatus, setSyncStatus] = useState<{
     useDrive: boolean;
     cloudDirty: boolean;
     lastSyncTime: string | null;
     lastSyncError: string | null;
-  }>({ useDrive: false, cloudDirty: false, lastSyncTime: null, lastSyncError: null });
+    lastUpload: UploadOutcome | null;
+  }>({ useDrive: false, cloudDirty: false, lastSyncTime: null, lastSyncError: null, lastUpload: null });
 
   // Refs for stable callback access to current state
   const pendingRef = useRef(pending);
@@ -26,12 +51,12 @@ export function useReminders() {
 
   // Derived state: actual and backlog lists
   const actual = useMemo(() =>
-    pending.filter(r => r.list_type === "actual").sort((a, b) => a.sort_order - b.sort_order),
+    pending.filter(r => r.list_type === "actual" && !isSnoozed(r)).sort(byPosition),
     [pending]
   );
 
   const backlog = useMemo(() =>
-    pending.filter(r => r.list_type === "backlog").sort((a, b) => a.sort_order - b.sort_order),
+    pending.filter(r => r.list_type === "backlog" && !isSnoozed(r)).sort(byPosition),
     [pending]
   );
 
@@ -59,7 +84,7 @@ export function useReminders() {
     listType: ListType = "actual"
   ) => {
     // Optimistic update with temp ID
-    const tempId = -Date.now();
+    const tempId = `temp-${Date.now()}`;
     const tempReminder: Reminder = {
       id: tempId,
       message,
@@ -67,13 +92,13 @@ export function useReminders() {
       list_type: listType,
       is_completed: false,
       created_at: new Date().toISOString(),
-      sort_order: listType === "actual" ? -1 : 9999,
+      sort_order: listType === "actual" ? TOP_KEY : BOTTOM_KEY,
     };
     setPending(prev => [tempReminder, ...prev]);
     showToast("Task added", "success");
 
     // Persist in background - reconcile temp ID with real ID when backend responds
-    invoke<number>("add_reminder", { message, urgency, listType })
+    invoke<string>("add_reminder", { message, urgency, listType })
       .then((realId) => {
         // Replace temp ID with real ID so subsequent operations work
         setPending(prev => prev.map(r =>
@@ -98,13 +123,13 @@ export function useReminders() {
       });
   }, []);
 
-  const completeReminder = useCallback((id: number) => {
+  const completeReminder = useCallback((id: string) => {
     // Find the reminder using ref for current state
     const reminder = pendingRef.current.find(r => r.id === id);
     if (!reminder) return;
 
     // For temp IDs (negative), we need to track by content since ID might change
-    const isTemp = id < 0;
+    const isTemp = isTempId(id);
     const matchKey = isTemp ? `${reminder.message}|${reminder.created_at}` : null;
 
     // Start leaving animation
@@ -135,10 +160,42 @@ export function useReminders() {
     // Update stats optimistically
     setStats(prev => ({ today: prev.today + 1, week: prev.week + 1 }));
 
-    // Show toast with undo immediately
+    // Persist in background - only if not a temp ID
+    // Temp IDs don't exist in backend yet, so completing them is a no-op on backend
+    let persisted: Promise<unknown> = Promise.resolve();
+    if (!isTemp) {
+      const persist = (force: boolean): Promise<unknown> =>
+        invoke("complete_reminder", { id, force })
+          .then(() => emit("refresh-reminders"))
+          .catch((error) => {
+            // The "warn" checklist policy asks before completing with open items
+            if (!force && String(error).includes(CONFIRM_OPEN_CHECKLIST)) {
+              const open = (reminder.checklist ?? []).filter(item => !item.done).length;
+              const items = open === 1 ? "1 checklist item is" : `${open} checklist items are`;
+              if (window.confirm(`${items} still open. Complete "${reminder.message}" anyway?`)) {
+                return persist(true);
+              }
+              return refresh(); // Revert when the user backs out
+            }
+            console.error("Failed to complete reminder:", error);
+            showToast("Failed to complete task", "error");
+            refresh(); // Revert on error
+          });
+      persisted = persist(false);
+    } else {
+      // For temp IDs, just emit refresh for the bar
+      emit("refresh-reminders").catch(() => {});
+    }
+
+    // Show toast with undo immediately. Undo reopens this reminder once its
+    // completion has gone through, rather than whatever was done last.
     showToast("Completed", "success", async () => {
       try {
-        await invoke("uncomplete_reminder", { id });
+        // Temp IDs never reached the backend, so there is nothing to undo there
+        if (!isTemp) {
+          await persisted;
+          await invoke("uncomplete_reminder", { id });
+        }
         await refresh();
         await emit("refresh-reminders");
         showToast("Restored", "info");
@@ -148,29 +205,14 @@ export function useReminders() {
         await refresh();
       }
     });
-
-    // Persist in background - only if not a temp ID
-    // Temp IDs don't exist in backend yet, so completing them is a no-op on backend
-    if (!isTemp) {
-      invoke("complete_reminder", { id })
-        .then(() => emit("refresh-reminders"))
-        .catch((error) => {
-          console.error("Failed to complete reminder:", error);
-          showToast("Failed to complete task", "error");
-          refresh(); // Revert on error
-        });
-    } else {
-      // For temp IDs, just emit refresh for the bar
-      emit("refresh-reminders").catch(() => {});
-    }
   }, [refresh]);
 
-  const deleteReminder = useCallback((id: number, skipAnimation = false) => {
+  const deleteReminder = useCallback((id: string, skipAnimation = false) => {
     // Find the reminder using refs for current state
     const reminder = pendingRef.current.find(r => r.id === id) || completedRef.current.find(r => r.id === id);
 
     // For temp IDs (negative), track by content
-    const isTemp = id < 0;
+    const isTemp = isTempId(id);
     const matchKey = reminder ? `${reminder.message}|${reminder.created_at}` : null;
 
     if (!skipAnimation) {
@@ -200,27 +242,10 @@ export function useReminders() {
       });
     }
 
-    // Show toast with undo option immediately
-    if (reminder) {
-      showToast("Deleted", "info", async () => {
-        try {
-          await invoke("add_reminder", {
-            message: reminder.message,
-            urgency: reminder.urgency,
-            listType: reminder.list_type,
-          });
-          await refresh();
-          await emit("refresh-reminders");
-          showToast("Restored", "info");
-        } catch (e) {
-          console.error("Failed to undo:", e);
-        }
-      });
-    }
-
     // Persist in background - only if not a temp ID
+    let persisted: Promise<unknown> = Promise.resolve();
     if (!isTemp) {
-      invoke("delete_reminder", { id })
+      persisted = invoke("delete_reminder", { id })
         .then(() => emit("refresh-reminders"))
         .catch((error) => {
           console.error("Failed to delete reminder:", error);
@@ -231,9 +256,27 @@ export function useReminders() {
       // For temp IDs, just emit refresh for the bar
       emit("refresh-reminders").catch(() => {});
     }
+
+    // Show toast with undo option immediately. Undo restores this reminder
+    // from the trash once its deletion has gone through.
+    if (reminder) {
+      showToast("Deleted", "info", async () => {
+        try {
+          if (!isTemp) {
+            await persisted;
+            await invoke("restore_from_trash", { id });
+          }
+          await refresh();
+          await emit("refresh-reminders");
+          showToast("Restored", "info");
+        } catch (e) {
+          console.error("Failed to undo:", e);
+        }
+      });
+    }
   }, [refresh]);
 
-  const updateReminder = useCallback(async (id: number, message: string, urgency: UrgencyType) => {
+  const updateReminder = useCallback(async (id: string, message: string, urgency: UrgencyType) => {
     // Optimistic update FIRST
     setPending(prev => prev.map(r =>
       r.id === id ? { ...r, message, urgency } : r
@@ -259,7 +302,7 @@ export function useReminders() {
     }
   }, [refresh]);
 
-  const moveReminder = useCallback((id: number, toList: ListType) => {
+  const moveReminder = useCallback((id: string, toList: ListType) => {
     // Optimistic update: move locally first for instant feedback
     setPending(prev => {
       const reminder = prev.find(r => r.id === id);
@@ -267,14 +310,14 @@ export function useReminders() {
 
       return prev.map(r => {
         if (r.id === id) {
-          return { ...r, list_type: toList, sort_order: toList === "actual" ? -1 : 9999 };
+          return { ...r, list_type: toList, sort_order: toList === "actual" ? TOP_KEY : BOTTOM_KEY };
         }
         return r;
       });
     });
 
     // Only call backend for real IDs
-    if (id >= 0) {
+    if (!isTempId(id)) {
       invoke("move_reminder", { id, toList })
         .then(() => emit("refresh-reminders"))
         .catch((error) => {
@@ -287,12 +330,12 @@ export function useReminders() {
     }
   }, [refresh]);
 
-  const setUrgency = useCallback((id: number, urgency: UrgencyType) => {
+  const setUrgency = useCallback((id: string, urgency: UrgencyType) => {
     // Optimistic update: update locally first for instant feedback
     setPending(prev => prev.map(r => r.id === id ? { ...r, urgency } : r));
 
     // Only call backend for real IDs
-    if (id >= 0) {
+    if (!isTempId(id)) {
       invoke("set_urgency", { id, urgency })
         .then(() => emit("refresh-reminders"))
         .catch((error) => {
@@ -323,7 +366,7 @@ export function useReminders() {
     }
   }, [refresh]);
 
-  const reorderReminders = useCallback(async (orderedIds: number[]) => {
+  const reorderReminders = useCallback(async (orderedIds: string[]) => {
     // Optimistic update: reorder locally FIRST for instant feedback
     setPending(prev => {
       const idToReminder = new Map(prev.map(r => [r.id, r]));
@@ -331,7 +374,8 @@ export function useReminders() {
       for (const id of orderedIds) {
         const reminder = idToReminder.get(id);
         if (reminder) {
-          reordered.push({ ...reminder, sort_order: reordered.length });
+          // Digits sort before every real key
+          reordered.push({ ...reminder, sort_order: String(reordered.length).padStart(6, "0") });
         }
       }
       // Add any reminders not in orderedIds (shouldn't happen, but just in case)
@@ -344,7 +388,7 @@ export function useReminders() {
     });
 
     // Filter out temp IDs for backend call
-    const realIds = orderedIds.filter(id => id >= 0);
+    const realIds = orderedIds.filter(id => !isTempId(id));
 
     // Persist locally (fast), then sync to cloud in background
     try {
@@ -405,9 +449,9 @@ export function useReminders() {
   useEffect(() => {
     const checkStatus = async () => {
       try {
-        const [useDrive, cloudDirty, lastSyncTime, lastSyncError] =
-          await invoke<[boolean, boolean, string | null, string | null]>("get_sync_status");
-        setSyncStatus({ useDrive, cloudDirty, lastSyncTime, lastSyncError });
+        const [useDrive, cloudDirty, lastSyncTime, lastSyncError, lastUpload] =
+          await invoke<[boolean, boolean, string | null, string | null, UploadOutcome | null]>("get_sync_status");
+        setSyncStatus({ useDrive, cloudDirty, lastSyncTime, lastSyncError, lastUpload });
       } catch (e) {
         console.log("Sync status check failed:", e);
       }
@@ -1,15 +1,135 @@
 export type UrgencyType = "now" | "today" | "soon" | "whenever";
-export type ListType = "actual" | "backlog";
+// Built-in lists, or the id of a user-created list
+export type ListType = "actual" | "backlog" | (string & {});
 
-export interface Reminder {
+export interface RecurrenceRule {
+  freq: "daily" | "weekly" | "monthly" | "after_completion";
+  interval?: number;
+  weekdays?: string[]; // "Mon".."Sun"
+  day_of_month?: number;
+  days?: number;
+  until?: string | null; // "YYYY-MM-DD"
+  count?: number | null;
+  occurrence: number;
+}
+
+export interface ChecklistItem {
   id: number;
+  text: string;
+  done: boolean;
+  created_at: string; // ISO string
+  completed_at?: string | null; // ISO string
+}
+
+export interface Reminder {
+  id: string;
   message: string;
   urgency: UrgencyType;
   list_type: ListType;
   created_at: string; // ISO string
   is_completed: boolean;
   completed_at?: string; // ISO string
+  next_occurrence?: string | null; // Id of the occurrence completing this one spawned
+  sort_order: string; // Fractional sort key; compare as plain text
+  due_at?: string | null; // "YYYY-MM-DD" or ISO string
+  recurrence?: RecurrenceRule | null;
+  hidden_until?: string | null; // ISO string; snoozed until then
+  tags?: string[];
+  notes?: string; // Markdown body
+  checklist?: ChecklistItem[];
+  updated_at?: string | null; // ISO string
+  modified?: Record<string, string>; // field name -> clock stamp of last change ("<ISO time>/<counter>/<device id>")
+}
+
+export interface ListDefinition {
+  id: ListType;
+  name: string;
   sort_order: number;
+  capacity?: number | null; // null = unlimited
+  overflow_list?: ListType | null;
+  source_list?: ListType | null;
+  built_in: boolean;
+  modified?: Record<string, string>; // field name -> clock stamp of last change
+}
+
+export interface HistoryItem {
+  label: string;
+  at: string; // ISO string
+  reminders: number; // How many reminders the action changed
+}
+
+export interface HistoryListing {
+  undo: HistoryItem[]; // Most recent first
+  redo: HistoryItem[];
+}
+
+export type OpKind =
+  | "created"
+  | "edited"
+  | "moved"
+  | "completed"
+  | "uncompleted"
+  | "deleted"
+  | "reordered";
+
+export interface Operation {
+  id: string;
+  at: string; // Clock stamp ("<ISO time>/<counter>/<device id>")
+  device: string;
+  reminder_id: string;
+  kind: OpKind;
+  changes: Partial<Reminder>; // Fields the operation set
+}
+
+export interface BackupInfo {
+  name: string;
+  created_at: string; // ISO string
+  size_bytes: number;
+}
+
+export type LocalBackendKind = "json" | "sqlite";
+
+export interface LocalBackendStatus {
+  kind: LocalBackendKind;
+  error: string | null; // The chosen backend couldn't be opened; reminders.json is used
+}
+
+export interface EncryptionStatus {
+  enabled: boolean;
+  locked: boolean; // Encrypted and the passphrase hasn't been entered yet
+}
+
+export interface TrashedReminder {
+  reminder: Reminder;
+  list: ListType; // List it was deleted from
+  deleted_at: string; // ISO string
+  device: string;
+}
+
+export type Finding =
+  | { kind: "duplicate_id"; id: string; copies: number }
+  | { kind: "in_both_lists"; id: string }
+  | { kind: "completion_mismatch"; id: string; is_completed: boolean }
+  | { kind: "duplicate_sort_order"; list: ListType; sort_order: string; ids: string[] }
+  | { kind: "invalid_sort_order"; list: ListType; id: string; sort_order: string };
+
+export type ConflictChoice = "local" | "cloud";
+
+export interface SyncConflict {
+  id: string;
+  reminder_id: string;
+  message: string; // The reminder's message after the merge
+  field: string; // Field name, as in Reminder.modified
+  local: unknown; // The field's value on each side; completion is { is_completed, completed_at }
+  cloud: unknown;
+  resolution: ConflictChoice;
+  detected_at: string; // ISO string
+}
+
+export interface UploadOutcome {
+  attempts: number; // More than 1: the cloud changed meanwhile and was merged in first
+  saved: boolean; // False if the cloud kept changing and the upload was given up
+  at: string; // ISO string
 }
 
 export interface ReminderStore {